@@ -5,6 +5,7 @@ import {
   INGL_MINT_AUTHORITY_KEY,
   GLOBAL_GEM_KEY,
   GEM_ACCOUNT_CONST,
+  METADATA_REGISTRY_KEY,
   INGL_MINTING_POOL_KEY,
   INGL_NFT_COLLECTION_KEY,
   BTC_HISTORY_BUFFER_KEY,
@@ -91,6 +92,47 @@ const [authorized_withdrawer_key] = PublicKey.findProgramAddressSync(
   [Buffer.from(AUTHORIZED_WITHDRAWER_KEY)],
   INGL_PROGRAM_ID
 );
+const [metadata_registry_key] = PublicKey.findProgramAddressSync(
+  [Buffer.from(METADATA_REGISTRY_KEY)],
+  INGL_PROGRAM_ID
+);
+
+const getMetadataUpdateAccounts = (tokenMint: PublicKey): AccountMeta[] => {
+  const [metadata_account_key] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from('metadata'),
+      METAPLEX_PROGRAM_ID.toBuffer(),
+      tokenMint.toBuffer(),
+    ],
+    METAPLEX_PROGRAM_ID
+  );
+  const metadataAccount: AccountMeta = {
+    pubkey: metadata_account_key,
+    isSigner: false,
+    isWritable: true,
+  };
+  const mintAuthorityAccount: AccountMeta = {
+    pubkey: mint_authority_key,
+    isSigner: false,
+    isWritable: false,
+  };
+  const metadataRegistryAccount: AccountMeta = {
+    pubkey: metadata_registry_key,
+    isSigner: false,
+    isWritable: false,
+  };
+  const metaplexProgramAccount: AccountMeta = {
+    pubkey: METAPLEX_PROGRAM_ID,
+    isSigner: false,
+    isWritable: false,
+  };
+  return [
+    metadataAccount,
+    mintAuthorityAccount,
+    metadataRegistryAccount,
+    metaplexProgramAccount,
+  ];
+};
 
 export async function mintInglGem(
   walletConnection: { connection: Connection; wallet: WalletContextState },
@@ -274,6 +316,8 @@ export async function mintInglGem(
     ],
   });
   try {
+    // A gem's mint takes about 240k compute units. Batch mints (MintNFTs, at most two gems per
+    // transaction) must request that much for every gem in the batch.
     await signAndConfirmTransaction(
       walletConnection,
       mintNftInstruction,
@@ -761,6 +805,7 @@ const getAllocateInstructionAccounts = async (
     globalGemAccount,
     pDPoolAccount,
     mintingPoolAccount,
+    ...getMetadataUpdateAccounts(tokenMint),
 
     systemProgramAccount,
   ];
@@ -908,6 +953,7 @@ const getDelegateInstructionAccounts = async ({
     globalGemAccount,
     sysvarClockAccount,
     stakeConfigProgramAccount,
+    ...getMetadataUpdateAccounts(tokenMint),
 
     systemProgramAccount,
     stakeProgramAccount,
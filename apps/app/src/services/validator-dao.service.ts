@@ -4,6 +4,7 @@ import {
   GEM_ACCOUNT_CONST,
   GlobalGems,
   PROPOSAL_KEY,
+  PROTOCOL_CONFIG_KEY,
   ValidatorProposal,
   Instruction,
 } from '@ingl/state';
@@ -260,7 +261,17 @@ export const voteValidatorProposal = async (
     isWritable: true,
   };
 
-  accounts = [payerAccount, proposalAccount];
+  const [protocol_config_pubkey] = PublicKey.findProgramAddressSync(
+    [Buffer.from(PROTOCOL_CONFIG_KEY)],
+    INGL_PROGRAM_ID
+  );
+  const protocolConfigAccount: AccountMeta = {
+    pubkey: protocol_config_pubkey,
+    isSigner: false,
+    isWritable: false,
+  };
+
+  accounts = [payerAccount, proposalAccount, protocolConfigAccount];
 
   for (let i = 0; i < nftPubkeys.length; i++) {
     const mint = nftPubkeys[i];
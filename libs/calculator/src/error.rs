@@ -0,0 +1,16 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CalculatorError {
+    #[error("Invalid gem account data")]
+    InvalidGemAccount,
+
+    #[error("Invalid ingl vote data account data")]
+    InvalidVoteData,
+
+    #[error("Invalid protocol config account data")]
+    InvalidProtocolConfig,
+
+    #[error("Invalid stake history sysvar data")]
+    InvalidStakeHistory,
+}
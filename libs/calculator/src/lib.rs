@@ -0,0 +1,201 @@
+//! Off-chain reward calculator for ingl gems. Given the raw bytes of a gem account, the ingl vote data of the vote account it is
+//! delegated to and the stake history sysvar, computes what the gem can claim, what it earned per epoch, its annualized yield,
+//! and what redeeming it would cost, with the same functions nft_withdraw and redeem_nft use.
+pub mod error;
+
+use ingl::{
+    rewards::{first_active_epoch, gem_claimable_rewards, gem_epoch_rarity_bonus, gem_epoch_reward, reward_epoch},
+    state::{
+        constants::TREASURY_FEE_MULTIPLYER, split_redeem_fee, FundsLocation, GemAccountV0_0_1, GemAccountVersions,
+        InglVoteAccountData, ProtocolConfig,
+    },
+};
+use solana_program::{
+    borsh::try_from_slice_unchecked,
+    clock::{DEFAULT_MS_PER_SLOT, DEFAULT_SLOTS_PER_EPOCH},
+    stake_history::StakeHistory,
+};
+
+use crate::error::CalculatorError;
+
+/// Epochs in a year at the cluster's default epoch length and slot time.
+pub const DEFAULT_EPOCHS_PER_YEAR: f64 = 365.25 * 86_400_000.0 / (DEFAULT_SLOTS_PER_EPOCH as f64 * DEFAULT_MS_PER_SLOT as f64);
+
+/// What a gem earned from one reward snapshot.
+#[derive(Clone, Debug)]
+pub struct EpochYield {
+    pub epoch: u64, // Epoch whose stake the snapshot rewarded.
+    pub reward: u64,
+    pub rarity_bonus: u64,
+    pub yield_rate: f64, // reward + rarity_bonus over the gem's class lamports.
+    pub claimed: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct YieldHistory {
+    pub first_active_epoch: Option<u64>, // None while the gem's stake is still activating, or if it isn't delegated.
+    pub epochs: Vec<EpochYield>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Annualized {
+    pub apr: f64,
+    pub apy: f64, // Assumes every epoch's rewards are compounded.
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RedeemQuote {
+    pub fee: u64,
+    pub treasury_fee: u64,
+    pub team_fee: u64,
+    pub refund: u64,
+    pub redeemable: bool, // redeem_nft only accepts unallocated gems past their redeemable date.
+}
+
+#[derive(Clone, Debug)]
+pub struct GemReport {
+    pub class_lamports: u64,
+    pub pending: u64,
+    pub history: YieldHistory,
+    pub annualized: Annualized,
+    pub redeem: RedeemQuote,
+}
+
+pub fn decode_gem(data: &[u8]) -> Result<GemAccountV0_0_1, CalculatorError> {
+    let version: GemAccountVersions = try_from_slice_unchecked(data.get(0..1).ok_or(CalculatorError::InvalidGemAccount)?)
+        .map_err(|_| CalculatorError::InvalidGemAccount)?;
+    if !matches!(version, GemAccountVersions::GemAccountV0_0_1) {
+        return Err(CalculatorError::InvalidGemAccount);
+    }
+    let gem_data = try_from_slice_unchecked(data).map_err(|_| CalculatorError::InvalidGemAccount)?;
+    GemAccountV0_0_1::validate(gem_data).map_err(|_| CalculatorError::InvalidGemAccount)
+}
+
+pub fn decode_vote_data(data: &[u8]) -> Result<InglVoteAccountData, CalculatorError> {
+    try_from_slice_unchecked::<InglVoteAccountData>(data)
+        .map_err(|_| CalculatorError::InvalidVoteData)?
+        .validate()
+        .map_err(|_| CalculatorError::InvalidVoteData)
+}
+
+pub fn decode_protocol_config(data: &[u8]) -> Result<ProtocolConfig, CalculatorError> {
+    try_from_slice_unchecked::<ProtocolConfig>(data)
+        .map_err(|_| CalculatorError::InvalidProtocolConfig)?
+        .validate()
+        .map_err(|_| CalculatorError::InvalidProtocolConfig)
+}
+
+/// Decodes the stake history sysvar's account data.
+pub fn decode_stake_history(data: &[u8]) -> Result<StakeHistory, CalculatorError> {
+    bincode::deserialize(data).map_err(|_| CalculatorError::InvalidStakeHistory)
+}
+
+/// Lamports nft_withdraw would pay the gem now. `ingl_vote_account_data` must belong to the vote account the gem is delegated to.
+pub fn pending_rewards(gem_data: &GemAccountV0_0_1, ingl_vote_account_data: &InglVoteAccountData, stake_history: &StakeHistory) -> u64 {
+    match gem_data.funds_location {
+        FundsLocation::VoteAccount { .. } => gem_claimable_rewards(gem_data, ingl_vote_account_data, stake_history),
+        _ => 0,
+    }
+}
+
+/// Every snapshot the gem earned from since its last delegation, claimed or not.
+pub fn yield_history(gem_data: &GemAccountV0_0_1, ingl_vote_account_data: &InglVoteAccountData, stake_history: &StakeHistory) -> YieldHistory {
+    let delegation_epoch = match (&gem_data.funds_location, gem_data.last_delegation_epoch) {
+        (FundsLocation::VoteAccount { .. }, Some(epoch)) => epoch,
+        _ => return YieldHistory::default(),
+    };
+    let first_active = match first_active_epoch(delegation_epoch, stake_history) {
+        Some(epoch) => epoch,
+        None => return YieldHistory::default(),
+    };
+    let interested_epoch = gem_data.last_withdrawal_epoch.unwrap_or(delegation_epoch).max(delegation_epoch);
+    let class_lamports = gem_data.class.get_class_lamports();
+
+    let epochs = ingl_vote_account_data
+        .vote_rewards
+        .iter()
+        .filter(|epoch_reward| reward_epoch(epoch_reward) >= first_active)
+        .map(|epoch_reward| {
+            let reward = gem_epoch_reward(class_lamports, epoch_reward);
+            let rarity_bonus = match &gem_data.rarity {
+                Some(rarity) => ingl_vote_account_data
+                    .rarity_bonuses
+                    .iter()
+                    .filter(|rarity_bonus| rarity_bonus.epoch_number == epoch_reward.epoch_number)
+                    .map(|rarity_bonus| gem_epoch_rarity_bonus(class_lamports, rarity, rarity_bonus))
+                    .sum(),
+                None => 0,
+            };
+            EpochYield {
+                epoch: reward_epoch(epoch_reward),
+                reward,
+                rarity_bonus,
+                yield_rate: (reward + rarity_bonus) as f64 / class_lamports as f64,
+                claimed: epoch_reward.epoch_number <= interested_epoch,
+            }
+        })
+        .collect();
+    YieldHistory { first_active_epoch: Some(first_active), epochs }
+}
+
+/// Annualizes the yield earned from the gem's first active epoch through the last rewarded one.
+/// Rewards of epochs no snapshot was taken for are paid by the next snapshot, so the yield is averaged over the whole span.
+pub fn annualize(history: &YieldHistory, epochs_per_year: f64) -> Annualized {
+    let (first_active, last) = match (history.first_active_epoch, history.epochs.last()) {
+        (Some(first_active), Some(last)) => (first_active, last),
+        _ => return Annualized::default(),
+    };
+    let span = (last.epoch + 1).saturating_sub(first_active).max(1) as f64;
+    let epoch_rate = history.epochs.iter().map(|epoch_yield| epoch_yield.yield_rate).sum::<f64>() / span;
+    Annualized {
+        apr: epoch_rate * epochs_per_year,
+        apy: (1.0 + epoch_rate).powf(epochs_per_year) - 1.0,
+    }
+}
+
+/// What redeem_nft would charge for the gem at `now`, a unix timestamp.
+pub fn redeem_quote(gem_data: &GemAccountV0_0_1, protocol_config: &ProtocolConfig, now: u32) -> RedeemQuote {
+    let class_lamports = gem_data.class.get_class_lamports();
+    let fee = protocol_config.redeem_fee(class_lamports, now.saturating_sub(gem_data.date_created));
+    let (treasury_fee, team_fee) = split_redeem_fee(fee, TREASURY_FEE_MULTIPLYER);
+    RedeemQuote {
+        fee,
+        treasury_fee,
+        team_fee,
+        refund: class_lamports.saturating_sub(fee),
+        redeemable: matches!(gem_data.funds_location, FundsLocation::MintingPool) && gem_data.redeemable_date <= now,
+    }
+}
+
+/// Computes everything above from raw account data. `vote_data` is the ingl vote data of the gem's vote account,
+/// and may be None for gems that aren't delegated.
+pub fn gem_report(
+    gem_account: &[u8],
+    vote_data: Option<&[u8]>,
+    stake_history: &[u8],
+    protocol_config: &[u8],
+    now: u32,
+    epochs_per_year: f64,
+) -> Result<GemReport, CalculatorError> {
+    let gem_data = decode_gem(gem_account)?;
+    let stake_history = decode_stake_history(stake_history)?;
+    let protocol_config = decode_protocol_config(protocol_config)?;
+
+    let (pending, history) = match vote_data {
+        Some(vote_data) => {
+            let ingl_vote_account_data = decode_vote_data(vote_data)?;
+            (
+                pending_rewards(&gem_data, &ingl_vote_account_data, &stake_history),
+                yield_history(&gem_data, &ingl_vote_account_data, &stake_history),
+            )
+        }
+        None => (0, YieldHistory::default()),
+    };
+    Ok(GemReport {
+        class_lamports: gem_data.class.get_class_lamports(),
+        pending,
+        annualized: annualize(&history, epochs_per_year),
+        history,
+        redeem: redeem_quote(&gem_data, &protocol_config, now),
+    })
+}
@@ -0,0 +1,210 @@
+use borsh::BorshSerialize;
+use ingl::state::{
+    constants::*, Class, ClassLimit, FundsLocation, GemAccountV0_0_1, GemAccountVersions, InglVoteAccountData, ProtocolConfig,
+    RandomnessSource, Rarity, RarityBonus, RebalancingData, VoteRewards,
+};
+use ingl_calculator::{annualize, gem_report, EpochYield, GemReport, YieldHistory, DEFAULT_EPOCHS_PER_YEAR};
+use solana_program::{
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    stake_history::{StakeHistory, StakeHistoryEntry},
+};
+
+const MINTED_AT: u32 = 1_000_000;
+
+/// A Rare Emerald delegated during epoch 10 and paid through the snapshot taken in epoch 12.
+fn gem(funds_location: FundsLocation) -> GemAccountV0_0_1 {
+    GemAccountV0_0_1 {
+        struct_id: GemAccountVersions::GemAccountV0_0_1,
+        validation_phrase: GEM_ACCOUNT_VAL_PHRASE,
+        date_created: MINTED_AT,
+        class: Class::Emerald,
+        redeemable_date: MINTED_AT + 100,
+        numeration: 1,
+        rarity: Some(Rarity::Rare),
+        funds_location,
+        rarity_seed_time: None,
+        date_allocated: Some(MINTED_AT),
+        last_voted_proposal: None,
+        last_withdrawal_epoch: Some(12),
+        last_delegation_epoch: Some(10),
+        all_withdraws: vec![],
+        all_votes: vec![],
+        is_liquid: false,
+        auto_delegate: false,
+        compound_rewards: false,
+        randomness_source: RandomnessSource::PriceFeeds,
+        rarity_commit_slot: 0,
+        rarity_rolls: 0,
+        rarity_vrf_round: 0,
+    }
+}
+
+fn delegated_gem() -> GemAccountV0_0_1 {
+    gem(FundsLocation::VoteAccount { vote_account_id: Pubkey::new_unique() })
+}
+
+fn snapshot(epoch_number: u64) -> VoteRewards {
+    VoteRewards {
+        validation_phrase: VOTE_REWARDS_VAL_PHRASE,
+        epoch_number,
+        total_reward: LAMPORTS_PER_SOL,
+        total_stake: 100 * LAMPORTS_PER_SOL,
+    }
+}
+
+fn rarity_bonus(epoch_number: u64) -> RarityBonus {
+    RarityBonus {
+        epoch_number,
+        pool: 10_000_000,
+        total_stake: 100 * LAMPORTS_PER_SOL,
+        bonus_bps: [0, 0, 500, 1000, 2000],
+        claimed: 0,
+    }
+}
+
+/// Snapshots from epoch 11 to 14, each crediting 1 SOL to 100 SOL of stake. The one taken in epoch 11 rewards the stake
+/// of epoch 10, during which the gem was still activating.
+fn vote_data() -> InglVoteAccountData {
+    InglVoteAccountData {
+        validation_phrase: INGL_VOTE_ACCOUNT_DATA_VAL_PHRASE,
+        total_delegated: 100 * LAMPORTS_PER_SOL,
+        last_withdraw_epoch: 14,
+        dealloced: 0,
+        rebalancing_data: RebalancingData { pending_validator_rewards: 0, unclaimed_validator_rewards: 0, is_rebalancing_active: false },
+        validator_id: Pubkey::new_unique(),
+        last_total_staked: 100 * LAMPORTS_PER_SOL,
+        is_t_stake_initialized: true,
+        pending_delegation_total: 0,
+        vote_rewards: (11..=14).map(snapshot).collect(),
+        liquid_delegated: 0,
+        stake_activation_epoch: Some(10),
+        active_stake: 100 * LAMPORTS_PER_SOL,
+        liquid_active_stake: 0,
+        pending_activations: vec![],
+        rarity_bonuses: vec![rarity_bonus(12), rarity_bonus(14)],
+        stake_tracking_initialized: true,
+    }
+}
+
+/// The cluster's stake delegated in epoch 10 finishes warming up within the epoch, so the gem is active from epoch 11.
+fn stake_history() -> Vec<u8> {
+    let mut stake_history = StakeHistory::default();
+    for epoch in 10..=14 {
+        let activating = if epoch == 10 { 100 * LAMPORTS_PER_SOL } else { 0 };
+        stake_history.add(epoch, StakeHistoryEntry { effective: 1000 * LAMPORTS_PER_SOL, activating, deactivating: 0 });
+    }
+    bincode::serialize(&stake_history).unwrap()
+}
+
+fn protocol_config() -> ProtocolConfig {
+    ProtocolConfig {
+        validation_phrase: PROTOCOL_CONFIG_VAL_PHRASE,
+        marketplace_fee_bps: 0,
+        presale_merkle_root: [0; 32],
+        presale_start: 0,
+        public_mint_start: 0,
+        class_limits: [ClassLimit::default(); 6],
+        randomness_source: RandomnessSource::PriceFeeds,
+        vrf_account: Pubkey::default(),
+        reroll_fee: 0,
+        reroll_treasury_bps: 0,
+        rarity_bonus_pool_bps: 0,
+        rarity_reward_bonus_bps: [0, 0, 500, 1000, 2000],
+        rarity_vote_bonus_bps: [0; 5],
+        redeem_fee_schedule: vec![], // Falls back to DEFAULT_REDEEM_FEE_SCHEDULE.
+        reroll_rewards_vote_account: Pubkey::default(),
+    }
+}
+
+fn report(gem: &GemAccountV0_0_1, now: u32) -> GemReport {
+    gem_report(
+        &gem.try_to_vec().unwrap(),
+        Some(&vote_data().try_to_vec().unwrap()),
+        &stake_history(),
+        &protocol_config().try_to_vec().unwrap(),
+        now,
+        DEFAULT_EPOCHS_PER_YEAR,
+    )
+    .unwrap()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-12, "{} != {}", actual, expected);
+}
+
+#[test]
+fn pending_rewards_match_what_nft_withdraw_pays() {
+    let report = report(&delegated_gem(), MINTED_AT);
+    // The NFTs' 60% of each snapshot, scaled by 10 of the 100 SOL staked, for the snapshots of epochs 13 and 14.
+    let per_snapshot = 60_000_000;
+    // The snapshot of epoch 14's pool, scaled by the gem's stake and by the Rare bonus over the largest one.
+    let bonus = 10_000_000 / 10 * 500 / 2000;
+    assert_eq!(report.pending, 2 * per_snapshot + bonus);
+
+    let epochs: Vec<(u64, u64, u64, bool)> = report
+        .history
+        .epochs
+        .iter()
+        .map(|epoch_yield| (epoch_yield.epoch, epoch_yield.reward, epoch_yield.rarity_bonus, epoch_yield.claimed))
+        .collect();
+    assert_eq!(report.history.first_active_epoch, Some(11));
+    assert_eq!(epochs, [(11, per_snapshot, bonus, true), (12, per_snapshot, 0, false), (13, per_snapshot, bonus, false)]);
+    let unclaimed: u64 = report
+        .history
+        .epochs
+        .iter()
+        .filter(|epoch_yield| !epoch_yield.claimed)
+        .map(|epoch_yield| epoch_yield.reward + epoch_yield.rarity_bonus)
+        .sum();
+    assert_eq!(unclaimed, report.pending);
+}
+
+#[test]
+fn undelegated_gems_have_nothing_pending() {
+    let report = report(&gem(FundsLocation::PDPool), MINTED_AT);
+    assert_eq!(report.pending, 0);
+    assert!(report.history.epochs.is_empty());
+    assert_eq!(report.annualized.apr, 0.0);
+}
+
+#[test]
+fn redeem_quote_follows_the_fee_schedule() {
+    let minting_pool_gem = gem(FundsLocation::MintingPool);
+    let quote = |elapsed: u32| report(&minting_pool_gem, MINTED_AT + elapsed).redeem;
+    let class_lamports = 10 * LAMPORTS_PER_SOL;
+    let fee = |fee_bps: u64| class_lamports * fee_bps / 10_000;
+
+    // Each point of DEFAULT_REDEEM_FEE_SCHEDULE, then halfway between the first two, then past the last one.
+    for (elapsed, fee_bps) in DEFAULT_REDEEM_FEE_SCHEDULE {
+        assert_eq!(quote(elapsed).fee, fee(fee_bps as u64), "{} seconds after the mint", elapsed);
+    }
+    assert_eq!(quote(7_884_000).fee, fee(933));
+    assert_eq!(quote(40_000_000).fee, 0);
+
+    let start = quote(0);
+    assert_eq!(start.treasury_fee, start.fee * TREASURY_FEE_MULTIPLYER as u64 / 100);
+    assert_eq!(start.treasury_fee + start.team_fee, start.fee);
+    assert_eq!(start.refund, class_lamports - start.fee);
+    assert!(!start.redeemable);
+    assert!(quote(100).redeemable);
+    assert!(!report(&delegated_gem(), MINTED_AT + 100).redeem.redeemable);
+}
+
+fn epoch_yield(epoch: u64, yield_rate: f64) -> EpochYield {
+    EpochYield { epoch, reward: 0, rarity_bonus: 0, yield_rate, claimed: false }
+}
+
+#[test]
+fn annualizes_over_the_whole_active_span() {
+    // The snapshot of epoch 12 also pays for epoch 11, which had none, so 2% is earned over the three epochs from 10 to 12.
+    let history = YieldHistory { first_active_epoch: Some(10), epochs: vec![epoch_yield(10, 0.01), epoch_yield(12, 0.02)] };
+    let annualized = annualize(&history, 10.0);
+    assert_close(annualized.apr, 0.1);
+    assert_close(annualized.apy, 1.01f64.powi(10) - 1.0);
+
+    let activating = YieldHistory { first_active_epoch: None, epochs: vec![] };
+    assert_eq!(annualize(&activating, 10.0).apy, 0.0);
+    let unrewarded = YieldHistory { first_active_epoch: Some(10), epochs: vec![] };
+    assert_eq!(annualize(&unrewarded, 10.0).apr, 0.0);
+}
@@ -91,6 +91,8 @@ export const COLLECTION_HOLDER_KEY = 'collection_holder';
 export const INGL_MINT_AUTHORITY_KEY = 'mint_authority';
 export const INGL_MINTING_POOL_KEY = 'minting_pool';
 export const GLOBAL_GEM_KEY = 'global_gem_account';
+export const PROTOCOL_CONFIG_KEY = 'ingl_protocol_config';
+export const METADATA_REGISTRY_KEY = 'ingl_metadata_registry';
 export const GEM_ACCOUNT_CONST = 'gem_account';
 export const COUNCIL_MINT_KEY = 'council_mint';
 export const PROPOSAL_KEY = 'ingl_proposals';
@@ -304,6 +306,24 @@ export class GlobalGems {
   @field({ type: vec(fixedArray('u8', 32)) })
   public validator_list!: PublicKey[];
 
+  @field({ type: fixedArray('u32', 6) })
+  public class_minted!: number[];
+
+  @field({ type: fixedArray('u32', 6) })
+  public class_supply!: number[];
+
+  @field({ type: fixedArray(fixedArray('u32', 5), 6) })
+  public class_rarity_supply!: number[][];
+
+  @field({ type: fixedArray('u64', 6) })
+  public class_allocated!: BN[];
+
+  @field({ type: fixedArray('u64', 6) })
+  public class_delegated!: BN[];
+
+  @field({ type: 'u32' })
+  public treasury_proposal_numeration!: number;
+
   constructor(properties?: {
     validation_phrase: number;
     counter: number;
@@ -315,6 +335,12 @@ export class GlobalGems {
     proposal_numeration: number;
     pending_delegation_total: BN;
     validator_list: PublicKey[];
+    class_minted: number[];
+    class_supply: number[];
+    class_rarity_supply: number[][];
+    class_allocated: BN[];
+    class_delegated: BN[];
+    treasury_proposal_numeration: number;
   }) {
     if (properties) {
       this.validation_phrase = properties.validation_phrase;
@@ -327,6 +353,13 @@ export class GlobalGems {
       this.proposal_numeration = properties.proposal_numeration;
       this.pending_delegation_total = properties.pending_delegation_total;
       this.validator_list = properties.validator_list;
+      this.class_minted = properties.class_minted;
+      this.class_supply = properties.class_supply;
+      this.class_rarity_supply = properties.class_rarity_supply;
+      this.class_allocated = properties.class_allocated;
+      this.class_delegated = properties.class_delegated;
+      this.treasury_proposal_numeration =
+        properties.treasury_proposal_numeration;
     }
   }
 }
@@ -0,0 +1,86 @@
+use std::str::FromStr;
+
+use ingl::{events::InglEvent, instruction::InstructionEnum};
+use solana_program::{borsh::try_from_slice_unchecked, pubkey::Pubkey};
+
+use crate::{error::IndexerError, rpc::RpcTransaction};
+
+/// An ingl instruction of a successful transaction, with its accounts resolved and the events it logged.
+#[derive(Debug)]
+pub struct DecodedInstruction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub index: usize, // Position among the transaction's top level instructions.
+    pub instruction: InstructionEnum,
+    pub accounts: Vec<Pubkey>,
+    pub events: Vec<InglEvent>,
+}
+
+impl DecodedInstruction {
+    pub fn account(&self, position: usize) -> Result<Pubkey, IndexerError> {
+        self.accounts.get(position).copied().ok_or_else(|| IndexerError::MissingAccount {
+            signature: self.signature.clone(),
+            index: self.index,
+            position,
+        })
+    }
+}
+
+/// Decodes the top level instructions sent to `program_id`. Failed transactions changed no state and yield nothing.
+/// The program isn't invoked through CPI, so inner instructions are ignored.
+pub fn decode_transaction(transaction: &RpcTransaction, program_id: &Pubkey) -> Result<Vec<DecodedInstruction>, IndexerError> {
+    if !transaction.succeeded() {
+        return Ok(Vec::new());
+    }
+    let keys = transaction
+        .account_keys()
+        .into_iter()
+        .map(parse_pubkey)
+        .collect::<Result<Vec<Pubkey>, IndexerError>>()?;
+    let mut instruction_logs = split_instruction_logs(transaction.logs());
+
+    let mut decoded = Vec::new();
+    for (index, instruction) in transaction.transaction.message.instructions.iter().enumerate() {
+        if keys.get(instruction.program_id_index as usize) != Some(program_id) {
+            continue;
+        }
+        let invalid = || IndexerError::InvalidInstruction { signature: transaction.signature().to_string(), index };
+        let data = bs58::decode(&instruction.data).into_vec().map_err(|_| invalid())?;
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|position| keys.get(*position as usize).copied().ok_or_else(invalid))
+            .collect::<Result<Vec<Pubkey>, IndexerError>>()?;
+        let logs = instruction_logs.get_mut(index).map(std::mem::take).unwrap_or_default();
+
+        decoded.push(DecodedInstruction {
+            signature: transaction.signature().to_string(),
+            slot: transaction.slot,
+            block_time: transaction.block_time,
+            index,
+            instruction: try_from_slice_unchecked::<InstructionEnum>(&data).map_err(|_| invalid())?,
+            accounts,
+            events: InglEvent::from_logs(&logs),
+        });
+    }
+    Ok(decoded)
+}
+
+pub fn parse_pubkey(key: &str) -> Result<Pubkey, IndexerError> {
+    Pubkey::from_str(key).map_err(|_| IndexerError::InvalidKey(key.to_string()))
+}
+
+/// Groups log lines by the top level instruction that produced them. Each one starts with an `invoke [1]` line.
+fn split_instruction_logs(logs: &[String]) -> Vec<Vec<&str>> {
+    let mut grouped: Vec<Vec<&str>> = Vec::new();
+    for line in logs {
+        if line.starts_with("Program ") && line.ends_with(" invoke [1]") {
+            grouped.push(Vec::new());
+        }
+        if let Some(current) = grouped.last_mut() {
+            current.push(line);
+        }
+    }
+    grouped
+}
@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum IndexerError {
+    #[error("Couldn't read or write the index files: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed transaction JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid account key {0}")]
+    InvalidKey(String),
+
+    #[error("Instruction {index} of transaction {signature} can't be decoded")]
+    InvalidInstruction { signature: String, index: usize },
+
+    #[error("Instruction {index} of transaction {signature} is missing account {position}")]
+    MissingAccount { signature: String, index: usize, position: usize },
+}
@@ -0,0 +1,46 @@
+//! Offline indexer for the ingl program. Replays the program's transactions, as fetched with `getTransaction`,
+//! into JSON-lines tables of gems, votes, rewards and pool movements.
+pub mod decode;
+pub mod error;
+pub mod output;
+pub mod replay;
+pub mod rpc;
+
+use std::{fs, path::Path};
+
+use solana_program::pubkey::Pubkey;
+
+use crate::{decode::decode_transaction, error::IndexerError, replay::Replayer, rpc::RpcTransaction};
+
+/// Reads transactions from either a JSON array or one JSON object per line, ordered by slot.
+/// Transactions of the same slot keep the order they appear in, which should be their order within the block.
+pub fn load_transactions(path: &Path) -> Result<Vec<RpcTransaction>, IndexerError> {
+    let contents = fs::read_to_string(path)?;
+    let mut transactions: Vec<RpcTransaction> = if contents.trim_start().starts_with('[') {
+        serde_json::from_str(&contents)?
+    } else {
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+    transactions.sort_by_key(|transaction| transaction.slot);
+    Ok(transactions)
+}
+
+/// Replays every transaction of `transactions` sent to `program_id`.
+pub fn replay(transactions: &[RpcTransaction], program_id: Pubkey) -> Result<Replayer, IndexerError> {
+    let mut replayer = Replayer::new(program_id);
+    for transaction in transactions {
+        let decoded = decode_transaction(transaction, &program_id)?;
+        replayer.apply_transaction(transaction, &decoded)?;
+    }
+    Ok(replayer)
+}
+
+pub fn index_file(input: &Path, output_dir: &Path, program_id: Pubkey) -> Result<Replayer, IndexerError> {
+    let replayer = replay(&load_transactions(input)?, program_id)?;
+    output::write_tables(&replayer, output_dir)?;
+    Ok(replayer)
+}
@@ -0,0 +1,33 @@
+use std::{env, path::Path, process};
+
+use ingl_indexer::{decode::parse_pubkey, index_file};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} <transactions.json> <output_dir> [program_id]", args[0]);
+        process::exit(1);
+    }
+    let program_id = match args.get(3) {
+        Some(key) => parse_pubkey(key).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(1);
+        }),
+        None => ingl::state::constants::id(),
+    };
+
+    match index_file(Path::new(&args[1]), Path::new(&args[2]), program_id) {
+        Ok(replayer) => println!(
+            "Indexed {} gems, {} votes, {} reward events and {} pool movements into {}",
+            replayer.gems.len(),
+            replayer.votes.len(),
+            replayer.rewards.len(),
+            replayer.pool_movements.len(),
+            args[2]
+        ),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{error::IndexerError, replay::Replayer};
+
+/// Writes the replayed tables to `output_dir`: one JSON object per line for gems, votes, rewards and pool movements,
+/// and a single JSON object for the global state.
+pub fn write_tables(replayer: &Replayer, output_dir: &Path) -> Result<(), IndexerError> {
+    fs::create_dir_all(output_dir)?;
+    write_lines(&output_dir.join("gems.jsonl"), &replayer.gem_records())?;
+    write_lines(&output_dir.join("votes.jsonl"), &replayer.votes)?;
+    write_lines(&output_dir.join("rewards.jsonl"), &replayer.rewards)?;
+    write_lines(&output_dir.join("pool_movements.jsonl"), &replayer.pool_movements)?;
+    let mut global = BufWriter::new(File::create(output_dir.join("global.json"))?);
+    serde_json::to_writer_pretty(&mut global, &replayer.global_record())?;
+    global.flush()?;
+    Ok(())
+}
+
+fn write_lines<T: Serialize>(path: &Path, rows: &[T]) -> Result<(), IndexerError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for row in rows {
+        serde_json::to_writer(&mut writer, row)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
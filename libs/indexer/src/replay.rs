@@ -0,0 +1,632 @@
+use std::collections::BTreeMap;
+
+use ingl::{
+    events::InglEvent,
+    instruction::InstructionEnum,
+    processor::{GEM_BURN_ACCOUNTS_LEN, GEM_MINT_ACCOUNTS_LEN},
+    state::{constants::*, Class, ClassLimit, FundsLocation, GlobalGems, ProtocolConfig, RandomnessSource, Rarity},
+};
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    decode::{parse_pubkey, DecodedInstruction},
+    error::IndexerError,
+    rpc::RpcTransaction,
+};
+
+/// Replayed state of a gem.
+#[derive(Clone, Debug)]
+pub struct IndexedGem {
+    pub owner: Pubkey, // Holder of the gem's token, a program escrow while the gem is listed, liquid or fractionalized.
+    pub class: Class,
+    pub numeration: u32,
+    pub funds_location: FundsLocation,
+    pub rarity: Option<Rarity>,
+    pub rarity_rolls: u32,
+    pub minted_signature: String,
+    pub burned: bool,
+    pub rewards_claimed: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GemRecord {
+    pub mint: String,
+    pub owner: String,
+    pub class: String,
+    pub numeration: u32,
+    pub funds_location: String,
+    pub rarity: Option<String>,
+    pub rarity_rolls: u32,
+    pub minted_signature: String,
+    pub burned: bool,
+    pub rewards_claimed: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VoteRecord {
+    pub signature: String,
+    pub slot: u64,
+    pub proposal: String,
+    pub mint: String,
+    pub choice: String, // The chosen validator for validator proposals, `approve` or `reject` for treasury proposals.
+    pub weight: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RewardRecord {
+    pub signature: String,
+    pub slot: u64,
+    pub event: InglEventRecord,
+}
+
+/// Serializable mirror of the reward events.
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind")]
+pub enum InglEventRecord {
+    RewardsProcessed { vote_account: String, epoch: u64, total_reward: u64, total_stake: u64 },
+    RewardsClaimed { mint: String, owner: String, lamports: u64 },
+}
+
+#[derive(Serialize, Debug)]
+pub struct PoolMovement {
+    pub signature: String,
+    pub slot: u64,
+    pub instructions: Vec<String>,
+    pub pool: &'static str,
+    pub account: String,
+    pub delta: i128,
+    pub balance: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GlobalRecord {
+    pub counter: u32,
+    pub total_raised: u64,
+    pub pd_pool_total: u64,
+    pub delegated_total: u64,
+    pub is_proposal_ongoing: bool,
+    pub proposal_numeration: u32,
+    pub validator_list: Vec<String>,
+    pub class_minted: [u32; 6],
+    pub class_supply: [u32; 6],
+    pub class_rarity_supply: [[u32; 5]; 6],
+    pub class_allocated: [u64; 6],
+    pub class_delegated: [u64; 6],
+    pub treasury_proposal_numeration: u32,
+}
+
+struct ValidatorProposalState {
+    validator_ids: Vec<Pubkey>,
+    votes: Vec<u32>,
+}
+
+/// Rebuilds program state by applying decoded instructions in order, with the same bookkeeping as the processor.
+/// Outcomes that depend on on-chain randomness or balances, such as rarities, re-routed delegations and reward amounts,
+/// are taken from the events the instructions logged, so they are only known for transactions that emit events.
+/// Rebalancing isn't replayed, so the pending delegation and deallocated totals are left out of the global record.
+/// Gems change hands through the program's escrows and through plain SPL transfers alike, so their holders are taken from
+/// the token balances of every transaction given, and transfers made in transactions that weren't given are missed.
+pub struct Replayer {
+    pub program_id: Pubkey,
+    pub global: GlobalGems,
+    pub protocol_config: ProtocolConfig,
+    pub gems: BTreeMap<Pubkey, IndexedGem>,
+    pub votes: Vec<VoteRecord>,
+    pub rewards: Vec<RewardRecord>,
+    pub pool_movements: Vec<PoolMovement>,
+    validator_proposals: BTreeMap<Pubkey, ValidatorProposalState>,
+    pools: Vec<(&'static str, Pubkey)>,
+}
+
+impl Replayer {
+    pub fn new(program_id: Pubkey) -> Self {
+        let pools = [
+            ("minting_pool", INGL_MINTING_POOL_KEY),
+            ("pd_pool", PD_POOL_KEY),
+            ("treasury", INGL_TREASURY_ACCOUNT_KEY),
+            ("legacy_treasury", TREASURY_ACCOUNT_KEY),
+            ("team_vault", TEAM_VAULT_KEY),
+            ("mint_authority", INGL_MINT_AUTHORITY_KEY),
+            ("authorized_withdrawer", AUTHORIZED_WITHDRAWER_KEY),
+            ("liquid_reserve", LIQUID_RESERVE_KEY),
+        ]
+        .iter()
+        .map(|(name, seed)| (*name, Pubkey::find_program_address(&[seed.as_ref()], &program_id).0))
+        .collect();
+
+        Replayer {
+            program_id,
+            // Mirrors the state mint_collection and init_protocol_config create.
+            global: GlobalGems {
+                validation_phrase: GLOBAL_GEMS_VAL_PHRASE,
+                counter: 0,
+                total_raised: 0,
+                pd_pool_total: 0,
+                delegated_total: 0,
+                is_proposal_ongoing: false,
+                proposal_numeration: 0,
+                pending_delegation_total: 0,
+                validator_list: Vec::new(),
+                dealloced_total: 0,
+                class_minted: [0; 6],
+                class_supply: [0; 6],
+                class_rarity_supply: [[0; 5]; 6],
+                class_allocated: [0; 6],
+                class_delegated: [0; 6],
+                treasury_proposal_numeration: 0,
+            },
+            protocol_config: ProtocolConfig {
+                validation_phrase: PROTOCOL_CONFIG_VAL_PHRASE,
+                marketplace_fee_bps: 0,
+                presale_merkle_root: [0; 32],
+                presale_start: 0,
+                public_mint_start: 0,
+                class_limits: [ClassLimit::default(); 6],
+                randomness_source: RandomnessSource::PriceFeeds,
+                vrf_account: Pubkey::default(),
+                reroll_fee: 0,
+                reroll_treasury_bps: 0,
+                rarity_bonus_pool_bps: 0,
+                rarity_reward_bonus_bps: [0; 5],
+                rarity_vote_bonus_bps: [0; 5],
+                redeem_fee_schedule: Vec::new(),
+                reroll_rewards_vote_account: Pubkey::default(),
+            },
+            gems: BTreeMap::new(),
+            votes: Vec::new(),
+            rewards: Vec::new(),
+            pool_movements: Vec::new(),
+            validator_proposals: BTreeMap::new(),
+            pools,
+        }
+    }
+
+    /// Applies a transaction's decoded instructions, then records how it moved the program's pools.
+    pub fn apply_transaction(&mut self, transaction: &RpcTransaction, decoded: &[DecodedInstruction]) -> Result<(), IndexerError> {
+        for instruction in decoded {
+            self.apply(instruction)?;
+        }
+        self.record_holders(transaction);
+        if !decoded.is_empty() {
+            self.record_pool_movements(transaction, decoded);
+        }
+        Ok(())
+    }
+
+    /// Moves each known gem to the holder of the token account left holding it, whichever instruction moved it.
+    fn record_holders(&mut self, transaction: &RpcTransaction) {
+        if !transaction.succeeded() {
+            return;
+        }
+        for balance in transaction.post_token_balances() {
+            if balance.ui_token_amount.amount != "1" {
+                continue;
+            }
+            let (mint, owner) = match (parse_pubkey(&balance.mint), balance.owner.as_deref().map(parse_pubkey)) {
+                (Ok(mint), Some(Ok(owner))) => (mint, owner),
+                _ => continue,
+            };
+            if let Some(gem) = self.gems.get_mut(&mint) {
+                gem.owner = owner;
+            }
+        }
+    }
+
+    pub fn apply(&mut self, ix: &DecodedInstruction) -> Result<(), IndexerError> {
+        match &ix.instruction {
+            InstructionEnum::RegisterValidatorId => {
+                let validator = ix.account(3)?;
+                if !self.global.validator_list.contains(&validator) {
+                    self.global.validator_list.push(validator);
+                }
+            }
+            InstructionEnum::CreateValidatorSelectionProposal => {
+                let validator_ids = self.global.validator_list.clone();
+                let votes = vec![0; validator_ids.len()];
+                self.validator_proposals.insert(ix.account(2)?, ValidatorProposalState { validator_ids, votes });
+                self.global.is_proposal_ongoing = true;
+                self.global.proposal_numeration += 1;
+            }
+            InstructionEnum::VoteValidatorProposal { num_nfts, validator_index } => {
+                let proposal = ix.account(1)?;
+                for i in 0..*num_nfts as usize {
+                    let mint = ix.account(3 + 3 * i)?;
+                    let weight = match self.gems.get(&mint) {
+                        Some(gem) => self.protocol_config.vote_weight(gem.class, &gem.rarity),
+                        None => continue,
+                    };
+                    let mut choice = String::new();
+                    if let Some(state) = self.validator_proposals.get_mut(&proposal) {
+                        if let Some(votes) = state.votes.get_mut(*validator_index as usize) {
+                            *votes += weight;
+                            choice = state.validator_ids[*validator_index as usize].to_string();
+                        }
+                    }
+                    self.votes.push(VoteRecord {
+                        signature: ix.signature.clone(),
+                        slot: ix.slot,
+                        proposal: proposal.to_string(),
+                        mint: mint.to_string(),
+                        choice,
+                        weight: weight as u64,
+                    });
+                }
+            }
+            InstructionEnum::FinalizeProposal => {
+                if let Some(state) = self.validator_proposals.get(&ix.account(1)?) {
+                    let (winner_index, _) = state.votes.iter().enumerate().fold((0, 0), |max, (ind, &val)| if val > max.1 { (ind, val) } else { max });
+                    if let Some(winner) = state.validator_ids.get(winner_index) {
+                        self.global.validator_list.retain(|validator| validator != winner);
+                    }
+                }
+                self.global.is_proposal_ongoing = false;
+            }
+            InstructionEnum::MintNft(class) => self.mint(ix, ix.account(1)?, ix.account(0)?, *class, FundsLocation::MintingPool, true),
+            InstructionEnum::MintAllowlistNFT { class, .. } => self.mint(ix, ix.account(1)?, ix.account(0)?, *class, FundsLocation::MintingPool, true),
+            InstructionEnum::MintCompoundedNFT(class) => {
+                let class_lamports = class.get_class_lamports();
+                self.global.pd_pool_total += class_lamports;
+                self.global.add_class_funds(*class, &FundsLocation::PDPool);
+                // The gem goes to the reserve's owner, who follows the reserve, so cranked mints are credited correctly.
+                self.mint(ix, ix.account(1)?, ix.account(GEM_MINT_ACCOUNTS_LEN + 1)?, *class, FundsLocation::PDPool, true)
+            }
+            InstructionEnum::MintNFTs { classes } => {
+                for (i, class) in classes.iter().enumerate() {
+                    self.mint(ix, ix.account(12 + 5 * i)?, ix.account(0)?, *class, FundsLocation::MintingPool, true);
+                }
+            }
+            InstructionEnum::MergeNFTs { class, num_nfts } => {
+                let mut funds_location = None;
+                for i in 0..*num_nfts as usize {
+                    if let Some(gem) = self.burn(ix.account(GEM_MINT_ACCOUNTS_LEN + GEM_BURN_ACCOUNTS_LEN * i)?) {
+                        funds_location.get_or_insert(gem.funds_location);
+                    }
+                }
+                let funds_location = funds_location.unwrap_or(FundsLocation::MintingPool);
+                self.global.add_class_funds(*class, &funds_location);
+                self.mint(ix, ix.account(1)?, ix.account(0)?, *class, funds_location, false);
+            }
+            InstructionEnum::SplitNFT { class, num_nfts } => {
+                let funds_location = self.burn(ix.account(0)?).map(|gem| gem.funds_location).unwrap_or(FundsLocation::MintingPool);
+                for j in 0..*num_nfts as usize {
+                    let first = GEM_BURN_ACCOUNTS_LEN + GEM_MINT_ACCOUNTS_LEN * j;
+                    self.global.add_class_funds(*class, &funds_location);
+                    self.mint(ix, ix.account(first + 1)?, ix.account(first)?, *class, funds_location.clone(), false);
+                }
+            }
+            InstructionEnum::Redeem => {
+                self.burn(ix.account(1)?);
+            }
+            InstructionEnum::ImprintRarity => {
+                for event in &ix.events {
+                    if let InglEvent::RarityImprinted { mint, rarity, rolls } = event {
+                        if let Some(gem) = self.gems.get_mut(mint) {
+                            self.global.add_rarity(gem.class, rarity);
+                            gem.rarity = Some(rarity.clone());
+                            gem.rarity_rolls = *rolls;
+                        }
+                    }
+                }
+            }
+            InstructionEnum::RerollRarity => {
+                if let Some(gem) = self.gems.get_mut(&ix.account(2)?) {
+                    if let Some(rarity) = gem.rarity.take() {
+                        self.global.remove_rarity(gem.class, &rarity);
+                    }
+                    gem.rarity_rolls += 1;
+                }
+            }
+            InstructionEnum::AllocateNFT => {
+                if let Some(gem) = self.gems.get_mut(&ix.account(1)?) {
+                    self.global.pd_pool_total += gem.class.get_class_lamports();
+                    self.global.add_class_funds(gem.class, &FundsLocation::PDPool);
+                    gem.funds_location = FundsLocation::PDPool;
+                }
+            }
+            InstructionEnum::DeAllocateNFT => {
+                if let Some(gem) = self.gems.get_mut(&ix.account(1)?) {
+                    self.global.pd_pool_total = self.global.pd_pool_total.saturating_sub(gem.class.get_class_lamports());
+                    self.global.remove_class_funds(gem.class, &FundsLocation::PDPool);
+                    gem.funds_location = FundsLocation::MintingPool;
+                }
+            }
+            InstructionEnum::DelegateNFT => self.delegate(ix.account(3)?, ix.account(1)?),
+            InstructionEnum::AutoDelegateNFTs { .. } => {
+                for event in &ix.events {
+                    if let InglEvent::Delegated { mint, vote_account, .. } = event {
+                        self.delegate(*mint, *vote_account);
+                    }
+                }
+            }
+            InstructionEnum::UnDelegateNFT => {
+                if let Some(gem) = self.gems.get_mut(&ix.account(4)?) {
+                    let class_lamports = gem.class.get_class_lamports();
+                    self.global.pd_pool_total += class_lamports;
+                    self.global.delegated_total = self.global.delegated_total.saturating_sub(class_lamports);
+                    self.global.remove_class_funds(gem.class, &gem.funds_location);
+                    self.global.add_class_funds(gem.class, &FundsLocation::PDPool);
+                    gem.funds_location = FundsLocation::PDPool;
+                }
+            }
+            InstructionEnum::SetRarityMultipliers { bonus_pool_bps, reward_bonus_bps, vote_bonus_bps } => {
+                self.protocol_config.rarity_bonus_pool_bps = *bonus_pool_bps;
+                self.protocol_config.rarity_reward_bonus_bps = *reward_bonus_bps;
+                self.protocol_config.rarity_vote_bonus_bps = *vote_bonus_bps;
+            }
+            InstructionEnum::CreateTreasuryProposal { .. } => self.global.treasury_proposal_numeration += 1,
+            InstructionEnum::VoteTreasuryProposal { approve, num_nfts } => {
+                let proposal = ix.account(1)?;
+                for i in 0..*num_nfts as usize {
+                    let mint = ix.account(3 + 4 * i)?;
+                    if let Some(gem) = self.gems.get(&mint) {
+                        self.votes.push(VoteRecord {
+                            signature: ix.signature.clone(),
+                            slot: ix.slot,
+                            proposal: proposal.to_string(),
+                            mint: mint.to_string(),
+                            choice: String::from(if *approve { "approve" } else { "reject" }),
+                            weight: gem.class.get_class_lamports(),
+                        });
+                    }
+                }
+            }
+            // These move a gem into or out of a program escrow, or to a buyer. Its new holder comes from the token balances.
+            InstructionEnum::ListNFT { .. }
+            | InstructionEnum::CancelListing
+            | InstructionEnum::BuyNFT
+            | InstructionEnum::FractionalizeNFT
+            | InstructionEnum::ReclaimFractionalizedNFT
+            | InstructionEnum::LiquifyNFT
+            | InstructionEnum::UnLiquifyNFT => {}
+            // Nothing the replayer tracks changes, or the change is only known from on-chain state (see the type's doc).
+            InstructionEnum::MintNewCollection
+            | InstructionEnum::CreateVoteAccount
+            | InstructionEnum::ChangeVoteAccountsValidatorIdentity
+            | InstructionEnum::InitRarityImprint
+            | InstructionEnum::ValidatorWithdraw
+            | InstructionEnum::NFTWithdraw { .. }
+            | InstructionEnum::ProcessRewards
+            | InstructionEnum::CloseProposal
+            | InstructionEnum::InitRebalance
+            | InstructionEnum::FinalizeRebalance
+            | InstructionEnum::InjectTestingData { .. }
+            | InstructionEnum::InitLiquidStaking
+            | InstructionEnum::SetAutoDelegate { .. }
+            | InstructionEnum::DelegateVoteStake
+            | InstructionEnum::SetCompounding { .. }
+            | InstructionEnum::WithdrawCompoundReserve
+            | InstructionEnum::DepositVaultShares { .. }
+            | InstructionEnum::WithdrawVaultShares { .. }
+            | InstructionEnum::HarvestVaultRewards
+            | InstructionEnum::ClaimVaultRewards
+            | InstructionEnum::InitProtocolConfig
+            | InstructionEnum::SetMarketplaceFee { .. }
+            | InstructionEnum::SetMintPhases { .. }
+            | InstructionEnum::SetClassLimits { .. }
+            | InstructionEnum::InitMetadataRegistry
+            | InstructionEnum::SetMetadataUri { .. }
+            | InstructionEnum::SetMetadataTemplates { .. }
+            | InstructionEnum::RefreshGemMetadata
+            | InstructionEnum::SetRandomnessSource { .. }
+            | InstructionEnum::SetRerollFee { .. }
+            | InstructionEnum::SetRedeemFeeSchedule { .. }
+            | InstructionEnum::QuoteRedeemFee
+            | InstructionEnum::ExecuteTreasuryProposal
+            | InstructionEnum::MigrateLegacyTreasury
+            | InstructionEnum::InitTeamVesting { .. }
+            | InstructionEnum::SetTeamBeneficiary { .. }
+            | InstructionEnum::ClaimTeamVesting
+            | InstructionEnum::RedeemLiquidRewards { .. }
+            | InstructionEnum::SweepRarityBonuses
+            | InstructionEnum::SetLiquidAutoDelegate { .. }
+            | InstructionEnum::SetGemUriBase { .. }
+            | InstructionEnum::SetGemStatistics { .. } => {}
+        }
+
+        // Reward amounts depend on stake history and balances, so they come from the events of whichever instruction paid them.
+        for event in &ix.events {
+            let record = match event {
+                InglEvent::RewardsProcessed { vote_account, epoch, total_reward, total_stake } => InglEventRecord::RewardsProcessed {
+                    vote_account: vote_account.to_string(),
+                    epoch: *epoch,
+                    total_reward: *total_reward,
+                    total_stake: *total_stake,
+                },
+                InglEvent::RewardsClaimed { mint, owner, lamports } => {
+                    if let Some(gem) = self.gems.get_mut(mint) {
+                        gem.rewards_claimed += lamports;
+                    }
+                    InglEventRecord::RewardsClaimed { mint: mint.to_string(), owner: owner.to_string(), lamports: *lamports }
+                }
+                _ => continue,
+            };
+            self.rewards.push(RewardRecord { signature: ix.signature.clone(), slot: ix.slot, event: record });
+        }
+        Ok(())
+    }
+
+    /// Mirrors mint_gem. Conversions neither raise funds nor move lamports, so `paid` is false for them.
+    fn mint(&mut self, ix: &DecodedInstruction, mint: Pubkey, owner: Pubkey, class: Class, funds_location: FundsLocation, paid: bool) {
+        self.global.counter += 1;
+        self.global.class_minted[class.index()] += 1;
+        self.global.add_gem(class);
+        if paid {
+            self.global.total_raised += class.get_class_lamports();
+        }
+        self.gems.insert(
+            mint,
+            IndexedGem {
+                owner,
+                class,
+                numeration: self.global.counter,
+                funds_location,
+                rarity: None,
+                rarity_rolls: 0,
+                minted_signature: ix.signature.clone(),
+                burned: false,
+                rewards_claimed: 0,
+            },
+        );
+    }
+
+    /// Mirrors the global bookkeeping of redeem_nft and record_burned_gems.
+    fn burn(&mut self, mint: Pubkey) -> Option<IndexedGem> {
+        let gem = self.gems.get_mut(&mint)?;
+        gem.burned = true;
+        self.global.remove_gem(gem.class, &gem.rarity);
+        self.global.remove_class_funds(gem.class, &gem.funds_location);
+        Some(gem.clone())
+    }
+
+    /// Mirrors the gem and global bookkeeping of delegate_gem.
+    fn delegate(&mut self, mint: Pubkey, vote_account: Pubkey) {
+        if let Some(gem) = self.gems.get_mut(&mint) {
+            let class_lamports = gem.class.get_class_lamports();
+            self.global.pd_pool_total = self.global.pd_pool_total.saturating_sub(class_lamports);
+            self.global.delegated_total += class_lamports;
+            self.global.remove_class_funds(gem.class, &FundsLocation::PDPool);
+            gem.funds_location = FundsLocation::VoteAccount { vote_account_id: vote_account };
+            self.global.add_class_funds(gem.class, &gem.funds_location);
+        }
+    }
+
+    fn record_pool_movements(&mut self, transaction: &RpcTransaction, decoded: &[DecodedInstruction]) {
+        let meta = match &transaction.meta {
+            Some(meta) => meta,
+            None => return,
+        };
+        let instructions: Vec<String> = decoded.iter().map(|ix| instruction_name(&ix.instruction).to_string()).collect();
+        for (position, key) in transaction.account_keys().iter().enumerate() {
+            let pool = match self.pools.iter().find(|(_, pool_key)| pool_key.to_string() == *key) {
+                Some((name, _)) => *name,
+                None => continue,
+            };
+            let (pre, post) = match (meta.pre_balances.get(position), meta.post_balances.get(position)) {
+                (Some(pre), Some(post)) if pre != post => (*pre, *post),
+                _ => continue,
+            };
+            self.pool_movements.push(PoolMovement {
+                signature: transaction.signature().to_string(),
+                slot: transaction.slot,
+                instructions: instructions.clone(),
+                pool,
+                account: key.to_string(),
+                delta: post as i128 - pre as i128,
+                balance: post,
+            });
+        }
+    }
+
+    pub fn gem_records(&self) -> Vec<GemRecord> {
+        self.gems
+            .iter()
+            .map(|(mint, gem)| GemRecord {
+                mint: mint.to_string(),
+                owner: gem.owner.to_string(),
+                class: format!("{:?}", gem.class),
+                numeration: gem.numeration,
+                funds_location: format!("{:?}", gem.funds_location),
+                rarity: gem.rarity.as_ref().map(|rarity| format!("{:?}", rarity)),
+                rarity_rolls: gem.rarity_rolls,
+                minted_signature: gem.minted_signature.clone(),
+                burned: gem.burned,
+                rewards_claimed: gem.rewards_claimed,
+            })
+            .collect()
+    }
+
+    pub fn global_record(&self) -> GlobalRecord {
+        GlobalRecord {
+            counter: self.global.counter,
+            total_raised: self.global.total_raised,
+            pd_pool_total: self.global.pd_pool_total,
+            delegated_total: self.global.delegated_total,
+            is_proposal_ongoing: self.global.is_proposal_ongoing,
+            proposal_numeration: self.global.proposal_numeration,
+            validator_list: self.global.validator_list.iter().map(Pubkey::to_string).collect(),
+            class_minted: self.global.class_minted,
+            class_supply: self.global.class_supply,
+            class_rarity_supply: self.global.class_rarity_supply,
+            class_allocated: self.global.class_allocated,
+            class_delegated: self.global.class_delegated,
+            treasury_proposal_numeration: self.global.treasury_proposal_numeration,
+        }
+    }
+}
+
+/// The instruction's variant name, e.g. `DelegateNFT`.
+pub fn instruction_name(instruction: &InstructionEnum) -> &'static str {
+    match instruction {
+        InstructionEnum::MintNft(_) => "MintNft",
+        InstructionEnum::MintNewCollection => "MintNewCollection",
+        InstructionEnum::Redeem => "Redeem",
+        InstructionEnum::ImprintRarity => "ImprintRarity",
+        InstructionEnum::AllocateNFT => "AllocateNFT",
+        InstructionEnum::DeAllocateNFT => "DeAllocateNFT",
+        InstructionEnum::CreateVoteAccount => "CreateVoteAccount",
+        InstructionEnum::ChangeVoteAccountsValidatorIdentity => "ChangeVoteAccountsValidatorIdentity",
+        InstructionEnum::DelegateNFT => "DelegateNFT",
+        InstructionEnum::UnDelegateNFT => "UnDelegateNFT",
+        InstructionEnum::InitRarityImprint => "InitRarityImprint",
+        InstructionEnum::RegisterValidatorId => "RegisterValidatorId",
+        InstructionEnum::CreateValidatorSelectionProposal => "CreateValidatorSelectionProposal",
+        InstructionEnum::VoteValidatorProposal { .. } => "VoteValidatorProposal",
+        InstructionEnum::FinalizeProposal => "FinalizeProposal",
+        InstructionEnum::ValidatorWithdraw => "ValidatorWithdraw",
+        InstructionEnum::NFTWithdraw { .. } => "NFTWithdraw",
+        InstructionEnum::ProcessRewards => "ProcessRewards",
+        InstructionEnum::CloseProposal => "CloseProposal",
+        InstructionEnum::InitRebalance => "InitRebalance",
+        InstructionEnum::FinalizeRebalance => "FinalizeRebalance",
+        InstructionEnum::InjectTestingData { .. } => "InjectTestingData",
+        InstructionEnum::InitLiquidStaking => "InitLiquidStaking",
+        InstructionEnum::LiquifyNFT => "LiquifyNFT",
+        InstructionEnum::UnLiquifyNFT => "UnLiquifyNFT",
+        InstructionEnum::SetAutoDelegate { .. } => "SetAutoDelegate",
+        InstructionEnum::AutoDelegateNFTs { .. } => "AutoDelegateNFTs",
+        InstructionEnum::DelegateVoteStake => "DelegateVoteStake",
+        InstructionEnum::SetCompounding { .. } => "SetCompounding",
+        InstructionEnum::MintCompoundedNFT(_) => "MintCompoundedNFT",
+        InstructionEnum::WithdrawCompoundReserve => "WithdrawCompoundReserve",
+        InstructionEnum::MergeNFTs { .. } => "MergeNFTs",
+        InstructionEnum::SplitNFT { .. } => "SplitNFT",
+        InstructionEnum::FractionalizeNFT => "FractionalizeNFT",
+        InstructionEnum::DepositVaultShares { .. } => "DepositVaultShares",
+        InstructionEnum::WithdrawVaultShares { .. } => "WithdrawVaultShares",
+        InstructionEnum::HarvestVaultRewards => "HarvestVaultRewards",
+        InstructionEnum::ClaimVaultRewards => "ClaimVaultRewards",
+        InstructionEnum::ReclaimFractionalizedNFT => "ReclaimFractionalizedNFT",
+        InstructionEnum::InitProtocolConfig => "InitProtocolConfig",
+        InstructionEnum::SetMarketplaceFee { .. } => "SetMarketplaceFee",
+        InstructionEnum::ListNFT { .. } => "ListNFT",
+        InstructionEnum::CancelListing => "CancelListing",
+        InstructionEnum::BuyNFT => "BuyNFT",
+        InstructionEnum::SetMintPhases { .. } => "SetMintPhases",
+        InstructionEnum::MintAllowlistNFT { .. } => "MintAllowlistNFT",
+        InstructionEnum::SetClassLimits { .. } => "SetClassLimits",
+        InstructionEnum::MintNFTs { .. } => "MintNFTs",
+        InstructionEnum::InitMetadataRegistry => "InitMetadataRegistry",
+        InstructionEnum::SetMetadataUri { .. } => "SetMetadataUri",
+        InstructionEnum::SetMetadataTemplates { .. } => "SetMetadataTemplates",
+        InstructionEnum::RefreshGemMetadata => "RefreshGemMetadata",
+        InstructionEnum::SetRandomnessSource { .. } => "SetRandomnessSource",
+        InstructionEnum::SetRerollFee { .. } => "SetRerollFee",
+        InstructionEnum::RerollRarity => "RerollRarity",
+        InstructionEnum::SetRarityMultipliers { .. } => "SetRarityMultipliers",
+        InstructionEnum::SetRedeemFeeSchedule { .. } => "SetRedeemFeeSchedule",
+        InstructionEnum::QuoteRedeemFee => "QuoteRedeemFee",
+        InstructionEnum::CreateTreasuryProposal { .. } => "CreateTreasuryProposal",
+        InstructionEnum::VoteTreasuryProposal { .. } => "VoteTreasuryProposal",
+        InstructionEnum::ExecuteTreasuryProposal => "ExecuteTreasuryProposal",
+        InstructionEnum::MigrateLegacyTreasury => "MigrateLegacyTreasury",
+        InstructionEnum::InitTeamVesting { .. } => "InitTeamVesting",
+        InstructionEnum::SetTeamBeneficiary { .. } => "SetTeamBeneficiary",
+        InstructionEnum::ClaimTeamVesting => "ClaimTeamVesting",
+        InstructionEnum::RedeemLiquidRewards { .. } => "RedeemLiquidRewards",
+        InstructionEnum::SweepRarityBonuses => "SweepRarityBonuses",
+        InstructionEnum::SetLiquidAutoDelegate { .. } => "SetLiquidAutoDelegate",
+        InstructionEnum::SetGemUriBase { .. } => "SetGemUriBase",
+        InstructionEnum::SetGemStatistics { .. } => "SetGemStatistics",
+    }
+}
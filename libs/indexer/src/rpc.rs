@@ -0,0 +1,99 @@
+use serde::Deserialize;
+
+/// A transaction as returned by `getTransaction` with the `json` encoding.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransaction {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub transaction: RpcTransactionBody,
+    pub meta: Option<RpcTransactionMeta>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RpcTransactionBody {
+    pub signatures: Vec<String>,
+    pub message: RpcMessage,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcMessage {
+    pub account_keys: Vec<String>,
+    pub instructions: Vec<RpcInstruction>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcInstruction {
+    pub program_id_index: u8,
+    pub accounts: Vec<u8>,
+    pub data: String, // Base58.
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransactionMeta {
+    pub err: Option<serde_json::Value>,
+    #[serde(default)]
+    pub pre_balances: Vec<u64>,
+    #[serde(default)]
+    pub post_balances: Vec<u64>,
+    #[serde(default)]
+    pub log_messages: Option<Vec<String>>,
+    #[serde(default)]
+    pub post_token_balances: Option<Vec<RpcTokenBalance>>,
+    #[serde(default)]
+    pub loaded_addresses: Option<RpcLoadedAddresses>,
+}
+
+/// A token account's balance after the transaction. `owner` is the wallet or program account holding the token account.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTokenBalance {
+    pub account_index: u32,
+    pub mint: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+    pub ui_token_amount: RpcTokenAmount,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RpcTokenAmount {
+    pub amount: String, // Raw amount, as a decimal string.
+}
+
+/// Address lookup table keys of versioned transactions, which follow the message's static keys.
+#[derive(Deserialize, Debug, Default)]
+pub struct RpcLoadedAddresses {
+    pub writable: Vec<String>,
+    pub readonly: Vec<String>,
+}
+
+impl RpcTransaction {
+    pub fn signature(&self) -> &str {
+        self.transaction.signatures.first().map(String::as_str).unwrap_or_default()
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.meta.as_ref().is_none_or(|meta| meta.err.is_none())
+    }
+
+    /// Every account key the instructions index into, static keys first.
+    pub fn account_keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self.transaction.message.account_keys.iter().map(String::as_str).collect();
+        if let Some(loaded) = self.meta.as_ref().and_then(|meta| meta.loaded_addresses.as_ref()) {
+            keys.extend(loaded.writable.iter().map(String::as_str));
+            keys.extend(loaded.readonly.iter().map(String::as_str));
+        }
+        keys
+    }
+
+    pub fn post_token_balances(&self) -> &[RpcTokenBalance] {
+        self.meta.as_ref().and_then(|meta| meta.post_token_balances.as_deref()).unwrap_or_default()
+    }
+
+    pub fn logs(&self) -> &[String] {
+        self.meta.as_ref().and_then(|meta| meta.log_messages.as_deref()).unwrap_or_default()
+    }
+}
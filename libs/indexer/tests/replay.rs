@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use ingl::state::{constants::id, Rarity};
+use ingl_indexer::{load_transactions, replay, replay::Replayer};
+use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+/// Two gems minted by one holder: the Ruby is allocated, sent to a second holder with a plain SPL transfer and imprinted,
+/// the Emerald is redeemed, and a failed mint in between changes nothing.
+fn fixture() -> Replayer {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/transactions.jsonl");
+    replay(&load_transactions(&path).unwrap(), id()).unwrap()
+}
+
+fn key(byte: u8) -> Pubkey {
+    Pubkey::new_from_array([byte; 32])
+}
+
+#[test]
+fn replays_the_global_record() {
+    let global = fixture().global_record();
+    assert_eq!(global.counter, 2);
+    assert_eq!(global.total_raised, 510 * LAMPORTS_PER_SOL);
+    assert_eq!(global.pd_pool_total, 500 * LAMPORTS_PER_SOL);
+    assert_eq!(global.delegated_total, 0);
+    assert_eq!(global.class_minted, [1, 0, 0, 1, 0, 0]);
+    assert_eq!(global.class_supply, [1, 0, 0, 0, 0, 0]);
+    assert_eq!(global.class_rarity_supply[0][Rarity::Rare.index()], 1);
+    assert_eq!(global.class_rarity_supply.iter().flatten().sum::<u32>(), 1);
+    assert_eq!(global.class_allocated, [500 * LAMPORTS_PER_SOL, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn follows_gems_to_their_holders() {
+    let replayer = fixture();
+    let ruby = &replayer.gems[&key(3)];
+    assert_eq!(ruby.owner, key(2));
+    assert!(!ruby.burned);
+    assert!(matches!(ruby.rarity, Some(Rarity::Rare)));
+
+    let emerald = &replayer.gems[&key(4)];
+    assert_eq!(emerald.owner, key(1));
+    assert!(emerald.burned);
+    assert!(!replayer.gems.contains_key(&key(5)));
+}
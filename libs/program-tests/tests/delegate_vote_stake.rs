@@ -0,0 +1,165 @@
+//! Runs delegate_vote_stake against a bank, with the program loaded as a native builtin, and follows the pooled stake
+//! account through its activation epoch. The native runner of solana-program-test 1.10 can't resize accounts in a CPI,
+//! so the accounts create_vote_account would create are seeded as it leaves them.
+use borsh::BorshSerialize;
+use ingl::{
+    instruction::InstructionEnum,
+    processor::process_instruction,
+    state::{constants::*, InglVoteAccountData, RebalancingData},
+};
+use solana_program::{
+    borsh::try_from_slice_unchecked,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    rent::Rent,
+    stake::{
+        self,
+        state::{Authorized, Lockup, Meta, StakeState},
+    },
+    stake_history::StakeHistory,
+    system_program, sysvar,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &id()).0
+}
+
+fn program_account<T: BorshSerialize>(data: &T, space: usize) -> Account {
+    let mut account = Account::new(LAMPORTS_PER_SOL, space, &id());
+    data.serialize(&mut &mut account.data[..]).unwrap();
+    account
+}
+
+/// Seeds the vote account, its ingl vote data and its initialized pooled stake account, as create_vote_account leaves them
+/// for the first finalized validator selection proposal won by `validator`.
+fn program_test(validator: &Pubkey, vote_account: &Pubkey) -> ProgramTest {
+    let mut program_test = ProgramTest::new("ingl", id(), processor!(process_instruction));
+    program_test.add_account(*validator, Account::new(100 * LAMPORTS_PER_SOL, 0, &system_program::id()));
+
+    let authorized_withdrawer = pda(&[AUTHORIZED_WITHDRAWER_KEY.as_ref()]);
+    let vote_state = solana_vote_program::vote_state::create_account_with_authorized(
+        validator,
+        validator,
+        &authorized_withdrawer,
+        100,
+        Rent::default().minimum_balance(solana_vote_program::vote_state::VoteState::size_of()),
+    );
+    program_test.add_account(*vote_account, Account::from(vote_state));
+
+    let stake_space = std::mem::size_of::<StakeState>();
+    let stake_rent = Rent::default().minimum_balance(stake_space);
+    let pd_pool = pda(&[PD_POOL_KEY.as_ref()]);
+    let mut stake_account = Account::new(stake_rent + LAMPORTS_PER_SOL, stake_space, &stake::program::id());
+    let stake_state = StakeState::Initialized(Meta {
+        rent_exempt_reserve: stake_rent,
+        authorized: Authorized { staker: pd_pool, withdrawer: pd_pool },
+        lockup: Lockup::default(),
+    });
+    bincode::serialize_into(&mut stake_account.data[..], &stake_state).unwrap();
+    program_test.add_account(pda(&[STAKE_ACCOUNT_KEY.as_ref(), vote_account.as_ref()]), stake_account);
+
+    let ingl_vote_data = InglVoteAccountData {
+        validation_phrase: INGL_VOTE_ACCOUNT_DATA_VAL_PHRASE,
+        total_delegated: 0,
+        last_withdraw_epoch: 0,
+        dealloced: 0,
+        rebalancing_data: RebalancingData { pending_validator_rewards: 0, unclaimed_validator_rewards: 0, is_rebalancing_active: false },
+        validator_id: *validator,
+        last_total_staked: stake_rent + LAMPORTS_PER_SOL,
+        is_t_stake_initialized: false,
+        pending_delegation_total: 0,
+        vote_rewards: Vec::new(),
+        liquid_delegated: 0,
+        stake_activation_epoch: None,
+        active_stake: 0,
+        liquid_active_stake: 0,
+        pending_activations: Vec::new(),
+        rarity_bonuses: Vec::new(),
+        stake_tracking_initialized: true,
+    };
+    program_test.add_account(pda(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account.as_ref()]), program_account(&ingl_vote_data, 10_240));
+
+    program_test
+}
+
+async fn process(context: &mut ProgramTestContext, instruction: Instruction, payer: &Keypair) {
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&payer.pubkey()), &[payer], blockhash);
+    context.banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn vote_data(context: &mut ProgramTestContext, vote_account: &Pubkey) -> InglVoteAccountData {
+    let account = context
+        .banks_client
+        .get_account(pda(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account.as_ref()]))
+        .await
+        .unwrap()
+        .unwrap();
+    try_from_slice_unchecked(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn delegate_vote_stake_activates_the_pooled_stake() {
+    let validator = Keypair::new();
+    let vote_account = pda(&[VOTE_ACCOUNT_KEY.as_ref(), &0u32.to_be_bytes()]);
+    let mut context = program_test(&validator.pubkey(), &vote_account).start_with_context().await;
+
+    let stake_account = pda(&[STAKE_ACCOUNT_KEY.as_ref(), vote_account.as_ref()]);
+    let t_stake_account = pda(&[T_STAKE_ACCOUNT_KEY.as_ref(), vote_account.as_ref()]);
+    let pd_pool = pda(&[PD_POOL_KEY.as_ref()]);
+    assert_eq!(vote_data(&mut context, &vote_account).await.stake_activation_epoch, None);
+
+    let delegate_vote_stake = Instruction::new_with_borsh(
+        id(),
+        &InstructionEnum::DelegateVoteStake,
+        vec![
+            AccountMeta::new(validator.pubkey(), true),
+            AccountMeta::new_readonly(vote_account, false),
+            AccountMeta::new(pda(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account.as_ref()]), false),
+            AccountMeta::new(stake_account, false),
+            AccountMeta::new(t_stake_account, false),
+            AccountMeta::new_readonly(pd_pool, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+            AccountMeta::new_readonly(stake::config::id(), false),
+            AccountMeta::new_readonly(stake::program::id(), false),
+        ],
+    );
+    process(&mut context, delegate_vote_stake, &validator).await;
+
+    let delegation_epoch = context.banks_client.get_sysvar::<Clock>().await.unwrap().epoch;
+    assert_eq!(vote_data(&mut context, &vote_account).await.stake_activation_epoch, Some(delegation_epoch));
+
+    let stake_data = context.banks_client.get_account(stake_account).await.unwrap().unwrap();
+    let delegation = match bincode::deserialize::<StakeState>(&stake_data.data).unwrap() {
+        StakeState::Stake(_, stake) => stake.delegation,
+        _ => panic!("pooled stake account isn't delegated"),
+    };
+    assert_eq!(delegation.voter_pubkey, vote_account);
+    assert_eq!(delegation.activation_epoch, delegation_epoch);
+    let stake_history = context.banks_client.get_sysvar::<StakeHistory>().await.unwrap();
+    let status = delegation.stake_activating_and_deactivating(delegation_epoch, Some(&stake_history));
+    assert_eq!((status.effective, status.activating), (0, delegation.stake));
+
+    let activated_slot = context
+        .genesis_config()
+        .epoch_schedule
+        .get_first_slot_in_epoch(delegation_epoch + 2);
+    context.warp_to_slot(activated_slot).unwrap();
+
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    assert!(clock.epoch > delegation_epoch);
+    let stake_history = context.banks_client.get_sysvar::<StakeHistory>().await.unwrap();
+    let status = delegation.stake_activating_and_deactivating(clock.epoch, Some(&stake_history));
+    assert_eq!((status.effective, status.activating), (delegation.stake, 0));
+    // The recorded epoch is what reward snapshots count activation from, so it must not move once the stake is active.
+    assert_eq!(vote_data(&mut context, &vote_account).await.stake_activation_epoch, Some(delegation_epoch));
+}
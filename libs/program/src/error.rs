@@ -27,6 +27,24 @@ pub enum InglError{
 
     #[error("Account data validation failed")]
     InvalidValPhrase,
+
+    #[error("The gems sent can't be converted into the requested class")]
+    InvalidConversion,
+
+    #[error("The merkle proof sent doesn't match the committed root")]
+    InvalidProof,
+
+    #[error("The oracle account sent isn't the expected feed on this network")]
+    InvalidOracle,
+
+    #[error("The oracle hasn't reported within its allowed staleness")]
+    StaleOracle,
+
+    #[error("The oracle has no report close enough before the requested time")]
+    MissingOracleReport,
+
+    #[error("The proposal didn't reach a passing vote")]
+    ProposalNotPassed,
 }
 
 
@@ -61,6 +79,24 @@ impl InglError{
             Self::InvalidValPhrase => {
                 msg!("Error: keyword={:} Validation Phrase Found in the sent account is different from that expected", if let Some(key) = keyword{key} else {""});
             }
+            Self::InvalidConversion => {
+                msg!("Error: keyword={:?} The gems sent can't be converted into the requested class", keyword.unwrap_or_default());
+            }
+            Self::InvalidProof => {
+                msg!("Error: keyword={:?} The merkle proof sent doesn't match the committed root", keyword.unwrap_or_default());
+            }
+            Self::InvalidOracle => {
+                msg!("Error: keyword={:?} The oracle account sent isn't the expected feed on this network", keyword.unwrap_or_default());
+            }
+            Self::StaleOracle => {
+                msg!("Error: keyword={:?} The oracle hasn't reported within its allowed staleness", keyword.unwrap_or_default());
+            }
+            Self::MissingOracleReport => {
+                msg!("Error: keyword={:?} The oracle has no report close enough before the requested time", keyword.unwrap_or_default());
+            }
+            Self::ProposalNotPassed => {
+                msg!("Error: keyword={:?} The proposal didn't reach a passing vote", keyword.unwrap_or_default());
+            }
         }
         ProgramError::from(self)
     }
@@ -0,0 +1,131 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::state::{Class, FundsLocation, Rarity};
+
+/// First field of every event log, telling ingl events apart from other programs' `Program data:` lines.
+pub const EVENT_TAG: &[u8; 8] = b"ingl_evt";
+const LOG_PREFIX: &str = "Program data: ";
+
+/// Typed record of a state change, logged with sol_log_data as `Program data: <base64 tag> <base64 borsh event>`.
+/// Variants are only ever appended, so old logs keep decoding.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub enum InglEvent {
+    GemMinted { mint: Pubkey, owner: Pubkey, class: Class, numeration: u32, funds_location: FundsLocation },
+    RarityImprinted { mint: Pubkey, rarity: Rarity, rolls: u32 },
+    Allocated { mint: Pubkey, lamports: u64 },
+    Delegated { mint: Pubkey, vote_account: Pubkey, lamports: u64 },
+    Undelegated { mint: Pubkey, vote_account: Pubkey, lamports: u64 },
+    RewardsProcessed { vote_account: Pubkey, epoch: u64, total_reward: u64, total_stake: u64 },
+    RewardsClaimed { mint: Pubkey, owner: Pubkey, lamports: u64 },
+    ProposalFinalized { proposal: Pubkey, winner: Pubkey },
+    Redeemed { mint: Pubkey, owner: Pubkey, refund: u64, fee: u64 },
+    RebalanceStarted { vote_account: Pubkey, epoch: u64 },
+    RebalanceFinalized { vote_account: Pubkey, epoch: u64, total_staked: u64 },
+    GemBurned { mint: Pubkey, owner: Pubkey, class: Class }, // A gem burned to be merged or split. Its replacements log GemMinted.
+    Liquified { mint: Pubkey, owner: Pubkey, lamports: u64, liquid_tokens: u64 },
+    Unliquified { mint: Pubkey, owner: Pubkey, lamports: u64, liquid_tokens: u64 },
+    ValidatorVoteCast { proposal: Pubkey, mint: Pubkey, validator_index: u32, weight: u32 },
+    TreasuryVoteCast { proposal: Pubkey, mint: Pubkey, approve: bool, weight: u64 },
+    Fractionalized { mint: Pubkey, owner: Pubkey, vault: Pubkey, share_mint: Pubkey, shares: u64 },
+    TeamVestingClaimed { beneficiary: Pubkey, lamports: u64 },
+    Deallocated { mint: Pubkey, lamports: u64 },
+    Listed { mint: Pubkey, seller: Pubkey, price: u64 },
+    ListingCancelled { mint: Pubkey, seller: Pubkey },
+    Sold { mint: Pubkey, seller: Pubkey, buyer: Pubkey, price: u64, fee: u64 },
+    TreasuryProposalCreated { proposal: Pubkey, proposer: Pubkey, recipient: Pubkey, amount: u64 },
+    TreasuryProposalExecuted { proposal: Pubkey, recipient: Pubkey, amount: u64 },
+    VaultSharesDeposited { vault: Pubkey, owner: Pubkey, shares: u64 },
+    VaultSharesWithdrawn { vault: Pubkey, owner: Pubkey, shares: u64 },
+    VaultRewardsClaimed { vault: Pubkey, owner: Pubkey, lamports: u64 },
+    FractionalizedNftReclaimed { mint: Pubkey, owner: Pubkey, vault: Pubkey },
+    RarityBonusesSwept { vote_account: Pubkey, lamports: u64 },
+    LiquidRewardsRedeemed { owner: Pubkey, lamports: u64, liquid_tokens: u64 },
+    CompoundReserveWithdrawn { owner: Pubkey, lamports: u64 },
+}
+
+impl InglEvent {
+    pub fn emit(&self) {
+        sol_log_data(&[EVENT_TAG, &self.try_to_vec().unwrap()]);
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(data).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+
+    /// Decodes a transaction log line. Returns None for lines that aren't ingl events.
+    pub fn from_log(line: &str) -> Option<Self> {
+        let mut fields = line.strip_prefix(LOG_PREFIX)?.split(' ');
+        if base64::decode(fields.next()?).ok()? != EVENT_TAG {
+            return None;
+        }
+        Self::decode(&base64::decode(fields.next()?).ok()?).ok()
+    }
+
+    /// Decodes every ingl event in a transaction's log messages, in emission order.
+    pub fn from_logs<S: AsRef<str>>(logs: &[S]) -> Vec<Self> {
+        logs.iter().filter_map(|line| Self::from_log(line.as_ref())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_line(event: &InglEvent) -> String {
+        format!("{}{} {}", LOG_PREFIX, base64::encode(EVENT_TAG), base64::encode(event.try_to_vec().unwrap()))
+    }
+
+    #[test]
+    fn events_round_trip_through_their_logs() {
+        let mint = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        let sold = InglEvent::Sold { mint, seller, buyer, price: 5_000_000_000, fee: 50_000_000 };
+
+        let decoded = InglEvent::decode(&sold.try_to_vec().unwrap()).unwrap();
+        assert!(matches!(
+            decoded,
+            InglEvent::Sold { mint: m, seller: s, buyer: b, price: 5_000_000_000, fee: 50_000_000 } if m == mint && s == seller && b == buyer
+        ));
+
+        let decoded = InglEvent::from_log(&log_line(&sold)).unwrap();
+        assert!(matches!(decoded, InglEvent::Sold { mint: m, .. } if m == mint));
+    }
+
+    #[test]
+    fn early_variants_keep_their_encoding() {
+        let mint = Pubkey::new_unique();
+        let minted = InglEvent::GemMinted {
+            mint,
+            owner: Pubkey::new_unique(),
+            class: Class::Emerald,
+            numeration: 7,
+            funds_location: FundsLocation::PDPool,
+        };
+        // Appending variants must not shift the tags of the ones already on chain.
+        assert_eq!(minted.try_to_vec().unwrap()[0], 0);
+        assert_eq!(InglEvent::Deallocated { mint, lamports: 0 }.try_to_vec().unwrap()[0], 18);
+
+        let decoded = InglEvent::from_log(&log_line(&minted)).unwrap();
+        assert!(matches!(decoded, InglEvent::GemMinted { class: Class::Emerald, numeration: 7, funds_location: FundsLocation::PDPool, .. }));
+    }
+
+    #[test]
+    fn ignores_lines_that_are_not_ingl_events() {
+        let event = InglEvent::Deallocated { mint: Pubkey::new_unique(), lamports: 1_000_000_000 };
+        let payload = base64::encode(event.try_to_vec().unwrap());
+        let logs = vec![
+            "Program log: Instruction: Deallocate".to_string(),
+            format!("{}{} {}", LOG_PREFIX, base64::encode(b"othr_evt"), payload),
+            format!("{}{} {}", LOG_PREFIX, base64::encode(EVENT_TAG), base64::encode([0xff; 4])),
+            format!("{}{}", LOG_PREFIX, base64::encode(EVENT_TAG)),
+            log_line(&event),
+        ];
+
+        assert!(InglEvent::decode(&[]).is_err());
+        let events = InglEvent::from_logs(&logs);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], InglEvent::Deallocated { lamports: 1_000_000_000, .. }));
+    }
+}
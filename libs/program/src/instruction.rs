@@ -5,9 +5,9 @@ use solana_program::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::state::{VoteInit, Class, constants::vote_program, VoteState};
+use crate::state::{VoteInit, Class, Rarity, RandomnessSource, RedeemFeePoint, constants::vote_program, VoteState};
 
-#[derive(BorshSerialize, BorshDeserialize)]
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum InstructionEnum {
     MintNft(Class),
     MintNewCollection,
@@ -30,7 +30,55 @@ pub enum InstructionEnum {
     CloseProposal,
     InitRebalance,
     FinalizeRebalance,
-    InjectTestingData{num_nfts: u32}
+    InjectTestingData{num_nfts: u32},
+    InitLiquidStaking,
+    LiquifyNFT,
+    UnLiquifyNFT,
+    SetAutoDelegate{auto_delegate: bool},
+    AutoDelegateNFTs{num_vote_accounts: u8, num_nfts: u8},
+    DelegateVoteStake,
+    SetCompounding{compound_rewards: bool},
+    MintCompoundedNFT(Class),
+    WithdrawCompoundReserve,
+    MergeNFTs{class: Class, num_nfts: u8},
+    SplitNFT{class: Class, num_nfts: u8},
+    FractionalizeNFT,
+    DepositVaultShares{amount: u64},
+    WithdrawVaultShares{amount: u64},
+    HarvestVaultRewards,
+    ClaimVaultRewards,
+    ReclaimFractionalizedNFT,
+    InitProtocolConfig,
+    SetMarketplaceFee{fee_bps: u16},
+    ListNFT{price: u64},
+    CancelListing,
+    BuyNFT,
+    SetMintPhases{merkle_root: [u8; 32], presale_start: u32, public_mint_start: u32},
+    MintAllowlistNFT{class: Class, allocation: u32, proof: Vec<[u8; 32]>},
+    SetClassLimits{class: Class, max_supply: u32, mint_open: u32, mint_close: u32},
+    MintNFTs{classes: Vec<Class>},
+    InitMetadataRegistry,
+    SetMetadataUri{class: Class, rarity: Option<Rarity>, uri: String},
+    SetMetadataTemplates{name_prefix: String, symbol: String},
+    RefreshGemMetadata,
+    SetRandomnessSource{source: RandomnessSource, vrf_account: Pubkey},
+    SetRerollFee{fee: u64, treasury_bps: u16, rewards_vote_account: Pubkey},
+    RerollRarity,
+    SetRarityMultipliers{bonus_pool_bps: u16, reward_bonus_bps: [u16; 5], vote_bonus_bps: [u16; 5]},
+    SetRedeemFeeSchedule{schedule: Vec<RedeemFeePoint>},
+    QuoteRedeemFee,
+    CreateTreasuryProposal{recipient: Pubkey, amount: u64, memo: String},
+    VoteTreasuryProposal{approve: bool, num_nfts: u8},
+    ExecuteTreasuryProposal,
+    MigrateLegacyTreasury,
+    InitTeamVesting{beneficiary: Pubkey, start: u32, cliff: u32, duration: u32},
+    SetTeamBeneficiary{beneficiary: Pubkey},
+    ClaimTeamVesting,
+    RedeemLiquidRewards{lamports: u64},
+    SweepRarityBonuses,
+    SetLiquidAutoDelegate{auto_delegate: bool},
+    SetGemUriBase{uri_base: String},
+    SetGemStatistics{class_supply: [u32; 6], class_rarity_supply: [[u32; 5]; 6], class_allocated: [u64; 6], class_delegated: [u64; 6]},
 }
 
 
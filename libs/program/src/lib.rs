@@ -1,13 +1,20 @@
 pub mod state;
 pub mod error;
+pub mod events;
 pub mod instruction;
 pub mod nfts;
 pub mod processor;
+pub mod randomness;
+pub mod rewards;
 pub mod utils;
 
+#[cfg(not(feature = "no-entrypoint"))]
 use processor::process_instruction;
+#[cfg(not(feature = "no-entrypoint"))]
 use solana_program::entrypoint;
 
+// Off-chain users such as the indexer link the crate with `no-entrypoint`, so its entrypoint symbol isn't exported twice.
+#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
 
 
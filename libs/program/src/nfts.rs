@@ -1,4 +1,10 @@
-use crate::state::{Class, Rarity};
+use crate::state::{Class, GemAccountV0_0_1, MetadataRegistry, Rarity};
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey};
+
+pub const DEFAULT_NAME_PREFIX: &str = "Ingl ";
+pub const DEFAULT_SYMBOL: &str = "I-Gem";
+/// Longest path `gem_metadata_uri` appends to the gem uri base: "/<numeration>.json", up to u32::MAX.
+pub const MAX_GEM_URI_PATH_LENGTH: usize = 16;
 
 pub fn get_uri<'life>(class: Class, rarity: Option<Rarity>) -> &'life str {
     match class {
@@ -69,53 +75,194 @@ pub fn get_uri<'life>(class: Class, rarity: Option<Rarity>) -> &'life str {
     }
 }
 
+/// Position of a class and rarity in the registry's URI table. Unrevealed gems use the first slot of their class.
+pub fn registry_index(class: Class, rarity: &Option<Rarity>) -> usize {
+    class.index() * 6
+        + match rarity {
+            Some(rarity_type) => rarity_type.clone() as usize + 1,
+            None => 0,
+        }
+}
+
+/// The registry's URI for a gem, falling back to the built-in Arweave links when none is registered.
+pub fn resolve_uri(registry: Option<&MetadataRegistry>, class: Class, rarity: Option<Rarity>) -> String {
+    match registry.and_then(|registry| registry.uris.get(registry_index(class, &rarity))) {
+        Some(uri) if !uri.is_empty() => uri.clone(),
+        _ => String::from(get_uri(class, rarity)),
+    }
+}
 
+/// "<prefix><Class> #<numeration>", e.g. "Ingl Ruby #42".
+pub fn resolve_name(registry: Option<&MetadataRegistry>, class: Class, numeration: u32) -> String {
+    let prefix = match registry {
+        Some(registry) => registry.name_prefix.as_str(),
+        None => DEFAULT_NAME_PREFIX,
+    };
+    format!("{}{:?} #{}", prefix, class, numeration)
+}
+
+/// Symbol shared by every gem. The numeration is left to the name, as it wouldn't fit next to a symbol in MAX_SYMBOL_LENGTH.
+pub fn resolve_symbol(registry: Option<&MetadataRegistry>) -> String {
+    match registry {
+        Some(registry) => registry.symbol.clone(),
+        None => String::from(DEFAULT_SYMBOL),
+    }
+}
+
+/// The gem's own metadata JSON at "<base>/<numeration>.json" once a gem uri base is registered, served off chain with
+/// attributes following the gem's state. Until then, gems share the JSON of their class and rarity.
+pub fn gem_metadata_uri(registry: Option<&MetadataRegistry>, gem: &GemAccountV0_0_1) -> String {
+    match registry {
+        Some(registry) if !registry.gem_uri_base.is_empty() => {
+            format!("{}/{}.json", registry.gem_uri_base.trim_end_matches('/'), gem.numeration)
+        }
+        _ => resolve_uri(registry, gem.class, gem.rarity.clone()),
+    }
+}
+
+/// Hands a freshly minted gem over to its holder. A holder minting for themselves approves the mint authority as the
+/// token account's delegate and marks the primary sale through it, both signed by them. A crank minting for a compound
+/// reserve's owner can't sign for the holder, so it skips the approval, which nothing relies on, and the mint authority
+/// marks the primary sale as the metadata's update authority.
+pub fn gem_handover_instructions(
+    payer: &Pubkey,
+    holder: &Pubkey,
+    mint_authority: &Pubkey,
+    token_account: &Pubkey,
+    metadata: &Pubkey,
+) -> Result<Vec<Instruction>, ProgramError> {
+    if payer != holder {
+        return Ok(vec![mpl_token_metadata::instruction::update_metadata_accounts_v2(
+            mpl_token_metadata::id(),
+            *metadata,
+            *mint_authority,
+            None,
+            None,
+            Some(true),
+            None,
+        )]);
+    }
+    Ok(vec![
+        spl_token::instruction::approve(&spl_token::id(), token_account, mint_authority, holder, &[], 1)?,
+        mpl_token_metadata::instruction::update_primary_sale_happened_via_token(
+            mpl_token_metadata::id(),
+            *metadata,
+            *holder,
+            *token_account,
+        ),
+    ])
+}
 
 impl Class {
+    /// Exclusive upper bounds of the Mythic, Exalted, Rare and Uncommon ranges of a 0..10_000 rarity roll. Higher rolls are Common.
+    pub fn rarity_odds(self) -> [u64; 4] {
+        match self {
+            Class::Benitoite => [2000, 4000, 6000, 8000], // Rebalance this Later. Changed to create equal chances for testing purposes.
+            Class::Serendibite => [30, 180, 880, 3880],
+            Class::Emerald => [70, 270, 1070, 5000],
+            Class::Sapphire => [150, 650, 2000, 8000],
+            Class::Diamond => [200, 900, 3000, 8500],
+            Class::Ruby => [500, 1500, 5000, 9000],
+        }
+    }
+
     pub fn get_rarity(self, random_value: u64) -> Option<Rarity> {
-        Some(match self {
-            Class::Benitoite => match random_value { // Rebalance this Later. Changed to create equal chances for testing purposes.
-                8000..=u64::MAX => Rarity::Common,
-                6000..=7999 => Rarity::Uncommon,
-                4000..=5999 => Rarity::Rare,
-                2000..=3999 => Rarity::Exalted,
-                0..=1999 => Rarity::Mythic,
-            },
-            Class::Serendibite => match random_value {
-                3880..=u64::MAX => Rarity::Common,
-                880..=3879 => Rarity::Uncommon,
-                180..=879 => Rarity::Rare,
-                30..=179 => Rarity::Exalted,
-                0..=29 => Rarity::Mythic,
-            },
-            Class::Emerald => match random_value {
-                5000..=u64::MAX => Rarity::Common,
-                1070..=4999 => Rarity::Uncommon,
-                270..=1069 => Rarity::Rare,
-                70..=269 => Rarity::Exalted,
-                0..=69 => Rarity::Mythic,
-            },
-            Class::Sapphire => match random_value {
-                8000..=u64::MAX => Rarity::Common,
-                2000..=7999 => Rarity::Uncommon,
-                650..=1999 => Rarity::Rare,
-                150..=649 => Rarity::Exalted,
-                0..=149 => Rarity::Mythic,
-            },
-            Class::Diamond => match random_value {
-                8500..=u64::MAX => Rarity::Common,
-                3000..=8499 => Rarity::Uncommon,
-                900..=2999 => Rarity::Rare,
-                200..=899 => Rarity::Exalted,
-                0..=199 => Rarity::Mythic,
-            },
-            Class::Ruby => match random_value {
-                9000..=u64::MAX => Rarity::Common,
-                5000..=8999 => Rarity::Uncommon,
-                1500..=4999 => Rarity::Rare,
-                500..=1499 => Rarity::Exalted,
-                0..=499 => Rarity::Mythic,
-            },
-        })
+        Some(rarity_from_odds(random_value, &self.rarity_odds()))
+    }
+}
+
+pub fn rarity_from_odds(random_value: u64, odds: &[u64; 4]) -> Rarity {
+    if random_value < odds[0] {
+        Rarity::Mythic
+    } else if random_value < odds[1] {
+        Rarity::Exalted
+    } else if random_value < odds[2] {
+        Rarity::Rare
+    } else if random_value < odds[3] {
+        Rarity::Uncommon
+    } else {
+        Rarity::Common
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{FundsLocation, GemAccountVersions, RandomnessSource};
+
+    fn signers(instructions: &[Instruction]) -> Vec<Pubkey> {
+        instructions
+            .iter()
+            .flat_map(|instruction| instruction.accounts.iter())
+            .filter(|account| account.is_signer)
+            .map(|account| account.pubkey)
+            .collect()
+    }
+
+    #[test]
+    fn cranked_mints_need_no_signature_from_the_holder() {
+        let (payer, holder, mint_authority, token_account, metadata) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let cranked = gem_handover_instructions(&payer, &holder, &mint_authority, &token_account, &metadata).unwrap();
+        assert_eq!(signers(&cranked), vec![mint_authority]);
+        assert_eq!(cranked[0].program_id, mpl_token_metadata::id());
+
+        let own = gem_handover_instructions(&holder, &holder, &mint_authority, &token_account, &metadata).unwrap();
+        assert_eq!(own.len(), 2);
+        assert!(signers(&own).iter().all(|signer| *signer == holder));
+    }
+
+    fn registry(gem_uri_base: &str) -> MetadataRegistry {
+        MetadataRegistry {
+            validation_phrase: crate::state::constants::METADATA_REGISTRY_VAL_PHRASE,
+            name_prefix: String::from(DEFAULT_NAME_PREFIX),
+            symbol: String::from(DEFAULT_SYMBOL),
+            uris: Vec::new(),
+            gem_uri_base: String::from(gem_uri_base),
+        }
+    }
+
+    fn gem(numeration: u32) -> GemAccountV0_0_1 {
+        GemAccountV0_0_1 {
+            struct_id: GemAccountVersions::GemAccountV0_0_1,
+            validation_phrase: crate::state::constants::GEM_ACCOUNT_VAL_PHRASE,
+            date_created: 0,
+            class: Class::Ruby,
+            redeemable_date: 0,
+            numeration,
+            rarity: Some(Rarity::Rare),
+            funds_location: FundsLocation::PDPool,
+            rarity_seed_time: None,
+            date_allocated: None,
+            last_voted_proposal: None,
+            last_withdrawal_epoch: None,
+            last_delegation_epoch: None,
+            all_withdraws: Vec::new(),
+            all_votes: Vec::new(),
+            is_liquid: false,
+            auto_delegate: false,
+            compound_rewards: false,
+            randomness_source: RandomnessSource::SlotHash,
+            rarity_commit_slot: 0,
+            rarity_rolls: 0,
+            rarity_vrf_round: 0,
+        }
+    }
+
+    #[test]
+    fn gems_resolve_their_own_json_under_the_uri_base() {
+        let gem = gem(42);
+        assert_eq!(gem_metadata_uri(None, &gem), get_uri(Class::Ruby, Some(Rarity::Rare)));
+        assert_eq!(gem_metadata_uri(Some(&registry("")), &gem), get_uri(Class::Ruby, Some(Rarity::Rare)));
+        assert_eq!(gem_metadata_uri(Some(&registry("https://x/gems/")), &gem), "https://x/gems/42.json");
+        assert_eq!(gem_metadata_uri(Some(&registry("https://x/gems")), &gem), "https://x/gems/42.json");
+        assert!(format!("/{}.json", u32::MAX).len() <= MAX_GEM_URI_PATH_LENGTH);
+    }
+
+    #[test]
+    fn symbols_fit_token_metadata() {
+        assert!(resolve_symbol(None).len() <= mpl_token_metadata::state::MAX_SYMBOL_LENGTH);
+        assert!(resolve_name(None, Class::Serendibite, u32::MAX).len() <= mpl_token_metadata::state::MAX_NAME_LENGTH);
     }
 }
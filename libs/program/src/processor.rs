@@ -1,41 +1,40 @@
 use crate::{
     error::InglError,
+    events::InglEvent,
     instruction::{
         split, vote_create_account, vote_initialize_account, vote_withdraw, InstructionEnum,
     },
-    nfts,
+    nfts, randomness, rewards,
     state::{
-        constants::*, Class, FundsLocation, GemAccountV0_0_1, GemAccountVersions, GlobalGems,
+        constants::*, Class, ClassLimit, FundsLocation, GemAccountV0_0_1, GemAccountVersions, GlobalGems,
         InglVoteAccountData, ValidatorProposal, ValidatorVote, VoteInit, VoteRewards, RebalancingData,
+        LiquidStakeData, CompoundReserve, FractionalVault, VaultShareAccount, ProtocolConfig, Listing, AllowlistAllocation, MetadataRegistry, Rarity, RandomnessSource, RarityBonus, RedeemFeePoint,
+        TreasuryProposal, TreasuryVote, TeamVesting, split_redeem_fee, auto_delegation_target,
     },
-    utils::{assert_owned_by, assert_program_owned, assert_pubkeys_exactitude, assert_is_signer, assert_pda_input},
+    utils::{assert_owned_by, assert_program_owned, assert_pubkeys_exactitude, assert_is_signer, assert_pda_input, assert_config_authority, allowlist_leaf, verify_merkle_proof},
 };
-use std::str::FromStr;
 
 use anchor_lang::AnchorDeserialize;
 use borsh::BorshSerialize;
 use mpl_token_metadata::state::{Collection, Creator, DataV2, Metadata, PREFIX};
-use num_traits::Pow;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    borsh::try_from_slice_unchecked,
     clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
     pubkey::Pubkey,
     rent::Rent,
     system_instruction, system_program,
     sysvar::{self, Sysvar},
-    hash::hashv,
     stake::{state::{Authorized, Lockup, StakeState}, self},
+    stake_history::StakeHistory,
 };
 use solana_program::{native_token::LAMPORTS_PER_SOL, program_pack::Pack};
 use spl_associated_token_account::{get_associated_token_address, *};
 use spl_token::{error::TokenError, state::Account};
-use switchboard_v2::{
-    AggregatorHistoryBuffer, AggregatorHistoryRow, SWITCHBOARD_V2_DEVNET, SWITCHBOARD_V2_MAINNET,
-};
 
 pub fn process_instruction(
     program_id: &Pubkey,
@@ -63,6 +62,54 @@ pub fn process_instruction(
         InstructionEnum::InitRebalance => init_rebalance(program_id, accounts)?,
         InstructionEnum::FinalizeRebalance => finalize_rebalance(program_id, accounts)?,
         InstructionEnum::InjectTestingData{num_nfts} => inject_testing_data(program_id, accounts, num_nfts)?,
+        InstructionEnum::InitLiquidStaking => init_liquid_staking(program_id, accounts)?,
+        InstructionEnum::LiquifyNFT => liquify_nft(program_id, accounts)?,
+        InstructionEnum::UnLiquifyNFT => unliquify_nft(program_id, accounts)?,
+        InstructionEnum::SetAutoDelegate{auto_delegate} => set_auto_delegate(program_id, accounts, auto_delegate)?,
+        InstructionEnum::AutoDelegateNFTs{num_vote_accounts, num_nfts} => auto_delegate_nfts(program_id, accounts, num_vote_accounts, num_nfts)?,
+        InstructionEnum::DelegateVoteStake => delegate_vote_stake(program_id, accounts)?,
+        InstructionEnum::SetCompounding{compound_rewards} => set_compounding(program_id, accounts, compound_rewards)?,
+        InstructionEnum::MintCompoundedNFT(class) => mint_compounded_nft(program_id, accounts, class)?,
+        InstructionEnum::WithdrawCompoundReserve => withdraw_compound_reserve(program_id, accounts)?,
+        InstructionEnum::MergeNFTs{class, num_nfts} => merge_nfts(program_id, accounts, class, num_nfts)?,
+        InstructionEnum::SplitNFT{class, num_nfts} => split_nft(program_id, accounts, class, num_nfts)?,
+        InstructionEnum::FractionalizeNFT => fractionalize_nft(program_id, accounts)?,
+        InstructionEnum::DepositVaultShares{amount} => deposit_vault_shares(program_id, accounts, amount)?,
+        InstructionEnum::WithdrawVaultShares{amount} => withdraw_vault_shares(program_id, accounts, amount)?,
+        InstructionEnum::HarvestVaultRewards => harvest_vault_rewards(program_id, accounts)?,
+        InstructionEnum::ClaimVaultRewards => claim_vault_rewards(program_id, accounts)?,
+        InstructionEnum::ReclaimFractionalizedNFT => reclaim_fractionalized_nft(program_id, accounts)?,
+        InstructionEnum::InitProtocolConfig => init_protocol_config(program_id, accounts)?,
+        InstructionEnum::SetMarketplaceFee{fee_bps} => set_marketplace_fee(program_id, accounts, fee_bps)?,
+        InstructionEnum::ListNFT{price} => list_nft(program_id, accounts, price)?,
+        InstructionEnum::CancelListing => cancel_listing(program_id, accounts)?,
+        InstructionEnum::BuyNFT => buy_nft(program_id, accounts)?,
+        InstructionEnum::SetMintPhases{merkle_root, presale_start, public_mint_start} => set_mint_phases(program_id, accounts, merkle_root, presale_start, public_mint_start)?,
+        InstructionEnum::MintAllowlistNFT{class, allocation, proof} => mint_allowlist_nft(program_id, accounts, class, allocation, proof)?,
+        InstructionEnum::SetClassLimits{class, max_supply, mint_open, mint_close} => set_class_limits(program_id, accounts, class, max_supply, mint_open, mint_close)?,
+        InstructionEnum::MintNFTs{classes} => mint_nfts(program_id, accounts, classes)?,
+        InstructionEnum::InitMetadataRegistry => init_metadata_registry(program_id, accounts)?,
+        InstructionEnum::SetMetadataUri{class, rarity, uri} => set_metadata_uri(program_id, accounts, class, rarity, uri)?,
+        InstructionEnum::SetMetadataTemplates{name_prefix, symbol} => set_metadata_templates(program_id, accounts, name_prefix, symbol)?,
+        InstructionEnum::RefreshGemMetadata => refresh_gem_metadata(program_id, accounts)?,
+        InstructionEnum::SetRandomnessSource{source, vrf_account} => set_randomness_source(program_id, accounts, source, vrf_account)?,
+        InstructionEnum::SetRerollFee{fee, treasury_bps, rewards_vote_account} => set_reroll_fee(program_id, accounts, fee, treasury_bps, rewards_vote_account)?,
+        InstructionEnum::RerollRarity => reroll_rarity(program_id, accounts)?,
+        InstructionEnum::SetRarityMultipliers{bonus_pool_bps, reward_bonus_bps, vote_bonus_bps} => set_rarity_multipliers(program_id, accounts, bonus_pool_bps, reward_bonus_bps, vote_bonus_bps)?,
+        InstructionEnum::SetRedeemFeeSchedule{schedule} => set_redeem_fee_schedule(program_id, accounts, schedule)?,
+        InstructionEnum::QuoteRedeemFee => quote_redeem_fee(program_id, accounts)?,
+        InstructionEnum::CreateTreasuryProposal{recipient, amount, memo} => create_treasury_proposal(program_id, accounts, recipient, amount, memo)?,
+        InstructionEnum::VoteTreasuryProposal{approve, num_nfts} => vote_treasury_proposal(program_id, accounts, approve, num_nfts)?,
+        InstructionEnum::ExecuteTreasuryProposal => execute_treasury_proposal(program_id, accounts)?,
+        InstructionEnum::MigrateLegacyTreasury => migrate_legacy_treasury(program_id, accounts)?,
+        InstructionEnum::InitTeamVesting{beneficiary, start, cliff, duration} => init_team_vesting(program_id, accounts, beneficiary, start, cliff, duration)?,
+        InstructionEnum::SetTeamBeneficiary{beneficiary} => set_team_beneficiary(program_id, accounts, beneficiary)?,
+        InstructionEnum::ClaimTeamVesting => claim_team_vesting(program_id, accounts)?,
+        InstructionEnum::RedeemLiquidRewards{lamports} => redeem_liquid_rewards(program_id, accounts, lamports)?,
+        InstructionEnum::SweepRarityBonuses => sweep_rarity_bonuses(program_id, accounts)?,
+        InstructionEnum::SetLiquidAutoDelegate{auto_delegate} => set_liquid_auto_delegate(program_id, accounts, auto_delegate)?,
+        InstructionEnum::SetGemUriBase{uri_base} => set_gem_uri_base(program_id, accounts, uri_base)?,
+        InstructionEnum::SetGemStatistics{class_supply, class_rarity_supply, class_allocated, class_delegated} => set_gem_statistics(program_id, accounts, class_supply, class_rarity_supply, class_allocated, class_delegated)?,
         _ => Err(ProgramError::InvalidInstructionData)?,
     })
 }
@@ -97,6 +144,8 @@ pub fn finalize_proposal(_program_id:&Pubkey, accounts: &[AccountInfo]) -> Progr
     global_gem_account_data.validator_list.remove(index);
     global_gem_account_data.is_proposal_ongoing = false;
     global_gem_account_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+    InglEvent::ProposalFinalized { proposal: *proposal_account_info.key, winner: proposal_data.winner.unwrap() }.emit();
     Ok(())
 }
 
@@ -105,8 +154,11 @@ pub fn vote_validator_proposal(_program_id: &Pubkey, accounts: &[AccountInfo], n
     let account_info_iter = &mut accounts.iter();
     let payer_account_info = next_account_info(account_info_iter)?;
     let proposal_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
     
     assert_program_owned(proposal_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
     let mut proposal_data = ValidatorProposal::decode(proposal_account_info)?;
     if let Some(_) = proposal_data.date_finalized {
         Err(InglError::TooLate.utilize(Some("Proposal Voted Already Ended")))?
@@ -143,8 +195,10 @@ pub fn vote_validator_proposal(_program_id: &Pubkey, accounts: &[AccountInfo], n
 
         gem_account_data.last_voted_proposal = Some(*proposal_account_info.key);
         gem_account_data.all_votes.push(ValidatorVote{validation_phrase: VALIDATOR_VOTE_VAL_PHRASE, proposal_id: *proposal_account_info.key, validator_index: validator_index});
-        proposal_data.votes[validator_index as usize] = proposal_data.votes[validator_index as usize].checked_add((gem_account_data.class.get_class_lamports() / LAMPORTS_PER_SOL) as u32).unwrap();
+        let weight = protocol_config.vote_weight(gem_account_data.class, &gem_account_data.rarity);
+        proposal_data.votes[validator_index as usize] = proposal_data.votes[validator_index as usize].checked_add(weight).unwrap();
         gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+        InglEvent::ValidatorVoteCast { proposal: *proposal_account_info.key, mint: *mint_account_info.key, validator_index, weight }.emit();
     }
 
     proposal_data.serialize(&mut &mut proposal_account_info.data.borrow_mut()[..])?;
@@ -212,7 +266,7 @@ pub fn register_validator_id(program_id: &Pubkey, accounts: &[AccountInfo]) -> P
     let account_info_iter = &mut accounts.iter();
     let payer_account_info = next_account_info(account_info_iter)?;
     let global_gem_account_info = next_account_info(account_info_iter)?;
-    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let team_vault_account_info = next_account_info(account_info_iter)?;
     let validator_info = next_account_info(account_info_iter)?; //Remove this and change it back to payer only after hackathon.
     let dup_prevention_account = next_account_info(account_info_iter)?;
 
@@ -225,17 +279,17 @@ pub fn register_validator_id(program_id: &Pubkey, accounts: &[AccountInfo]) -> P
     )?;
 
     let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info );
-    let (_mint_authority_key, _mint_authority_bump) = assert_pda_input(&[INGL_MINT_AUTHORITY_KEY.as_ref()], mint_authority_account_info);
+    let (_team_vault_key, _team_vault_bump) = assert_pda_input(&[TEAM_VAULT_KEY.as_ref()], team_vault_account_info);
 
     invoke(
         &system_instruction::transfer(
             payer_account_info.key,
-            mint_authority_account_info.key,
+            team_vault_account_info.key,
             2 * LAMPORTS_PER_SOL,
         ),
         &[
             payer_account_info.clone(),
-            mint_authority_account_info.clone(),
+            team_vault_account_info.clone(),
         ],
     )?;
 
@@ -333,6 +387,13 @@ pub fn create_vote_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         is_t_stake_initialized: false,
         vote_rewards: Vec::new(),
         last_total_staked: LAMPORTS_PER_SOL + Rent::get()?.minimum_balance(std::mem::size_of::<StakeState>() as usize),
+        liquid_delegated: 0,
+        stake_activation_epoch: None,
+        active_stake: 0,
+        liquid_active_stake: 0,
+        pending_activations: Vec::new(),
+        rarity_bonuses: Vec::new(),
+        stake_tracking_initialized: true,
     };
 
     ingl_vote_data.serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
@@ -434,6 +495,9 @@ pub fn allocate_sol(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
     let global_gem_account_info = next_account_info(account_info_iter)?;
     let pd_pool_account_info = next_account_info(account_info_iter)?;
     let minting_pool_account_info = next_account_info(account_info_iter)?;
+    let metadata_account_info = next_account_info(account_info_iter)?;
+    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
 
     assert_program_owned(gem_account_data_info)?;
     assert_program_owned(global_gem_account_info)?;
@@ -491,9 +555,19 @@ pub fn allocate_sol(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
     gem_account_data.redeemable_date = clock.unix_timestamp as u32 + ALLOCATE_LOCK_TIME;
 
     global_gem_account_data.pd_pool_total += mint_cost;
+    global_gem_account_data.add_class_funds(gem_account_data.class, &FundsLocation::PDPool);
 
     gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
     global_gem_account_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+    InglEvent::Allocated { mint: *mint_account_info.key, lamports: mint_cost }.emit();
+
+    sync_gem_metadata(
+        mint_account_info,
+        metadata_account_info,
+        mint_authority_account_info,
+        metadata_registry_account_info,
+        &gem_account_data,
+    )?;
 
     Ok(())
 }
@@ -507,6 +581,9 @@ pub fn deallocate_sol(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Program
     let global_gem_account_info = next_account_info(account_info_iter)?;
     let pd_pool_account_info = next_account_info(account_info_iter)?;
     let minting_pool_account_info = next_account_info(account_info_iter)?;
+    let metadata_account_info = next_account_info(account_info_iter)?;
+    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
 
     assert_program_owned(gem_account_data_info)?;
     assert_program_owned(global_gem_account_info)?;
@@ -564,14 +641,68 @@ pub fn deallocate_sol(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Program
         Err(InglError::TooEarly.utilize(Some("Deallocating Earlier than allowed")))?;
     }
     global_gem_account_data.pd_pool_total -= mint_cost;
+    global_gem_account_data.remove_class_funds(gem_account_data.class, &FundsLocation::PDPool);
 
     gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
     global_gem_account_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+    InglEvent::Deallocated { mint: *mint_account_info.key, lamports: mint_cost }.emit();
+
+    sync_gem_metadata(
+        mint_account_info,
+        metadata_account_info,
+        mint_authority_account_info,
+        metadata_registry_account_info,
+        &gem_account_data,
+    )?;
 
     Ok(())
 }
 
+/// Where the lamports backing a newly minted gem come from.
+enum GemFunding {
+    /// Paid by the payer into the minting pool, during the presale when `presale` is set.
+    Payer { presale: bool },
+    /// Paid from a holder's compound reserve into the PD pool. The reserve and its owner, who receives the gem, follow the protocol config.
+    CompoundReserve,
+    /// Already held by burned gems of another class. The new gem carries over the template's location, locks and reward state.
+    Conversion(Box<GemAccountV0_0_1>),
+}
+
 pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) -> ProgramResult {
+    mint_gem(program_id, accounts, class, GemFunding::Payer { presale: false })
+}
+
+/// Mints a gem paid for by a holder's compound reserve, directly allocated to the PD pool.
+/// Anyone may crank it for the holder, paying the new accounts' rent, as long as it mints the most valuable class the reserve covers.
+pub fn mint_compounded_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) -> ProgramResult {
+    mint_gem(program_id, accounts, class, GemFunding::CompoundReserve)
+}
+
+/// Accounts shared by every gem minted in one instruction.
+struct GemMintAccounts<'a, 'b> {
+    payer: &'b AccountInfo<'a>,
+    owner: &'b AccountInfo<'a>, // Wallet receiving the gems, the payer unless a crank mints for a compound reserve's owner.
+    mint_authority: &'b AccountInfo<'a>,
+    spl_token_program: &'b AccountInfo<'a>,
+    sysvar_rent: &'b AccountInfo<'a>,
+    system_program: &'b AccountInfo<'a>,
+    ingl_edition: &'b AccountInfo<'a>,
+    ingl_collection_mint: &'b AccountInfo<'a>,
+    ingl_collection: &'b AccountInfo<'a>,
+    metadata_registry: &'b AccountInfo<'a>,
+}
+
+/// Accounts of a single gem: its NFT mint, the owner's token account, metadata, edition and gem account.
+struct GemNftAccounts<'a, 'b> {
+    mint: &'b AccountInfo<'a>,
+    associated_token: &'b AccountInfo<'a>,
+    metadata: &'b AccountInfo<'a>,
+    nft_edition: &'b AccountInfo<'a>,
+    gem: &'b AccountInfo<'a>,
+}
+
+/// The pool account must hold the gem's funds: the minting pool, the PD pool, or the vote account for conversions of delegated gems.
+fn mint_gem(program_id: &Pubkey, accounts: &[AccountInfo], class: Class, funding: GemFunding) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let payer_account_info = next_account_info(account_info_iter)?;
     let mint_account_info = next_account_info(account_info_iter)?;
@@ -588,8 +719,266 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
     let nft_edition_account_info = next_account_info(account_info_iter)?;
     let ingl_collection_mint_info = next_account_info(account_info_iter)?;
     let ingl_collection_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+
+    assert_program_owned(global_gem_account_info)?;
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+    let minting_pool_id = match &funding {
+        GemFunding::Payer { .. } => assert_pda_input(&[INGL_MINTING_POOL_KEY.as_ref()], minting_pool_account_info).0,
+        GemFunding::CompoundReserve => assert_pda_input(&[PD_POOL_KEY.as_ref()], minting_pool_account_info).0,
+        GemFunding::Conversion(template) => match &template.funds_location {
+            FundsLocation::MintingPool => assert_pda_input(&[INGL_MINTING_POOL_KEY.as_ref()], minting_pool_account_info).0,
+            FundsLocation::PDPool => assert_pda_input(&[PD_POOL_KEY.as_ref()], minting_pool_account_info).0,
+            FundsLocation::VoteAccount { vote_account_id } => {
+                assert_pubkeys_exactitude(vote_account_id, minting_pool_account_info.key).expect("Error: @vote account in funds location");
+                *vote_account_id
+            }
+        },
+    };
+
+    let clock = Clock::get()?;
+    // Getting timestamp
+    let current_timestamp = clock.unix_timestamp as u32;
+
+    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+
+    // The class's cap and mint window bound every mint, while the mint phases only gate gems paid by the payer.
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    let class_minted = global_gem_data.class_minted[class.index()];
+    match funding {
+        GemFunding::Payer { presale } => protocol_config.assert_mint_allowed(class, class_minted, presale, current_timestamp)?,
+        _ => protocol_config.assert_class_mint_allowed(class, class_minted, current_timestamp)?,
+    }
+
+    let mint_cost = class.clone().get_class_lamports();
+    global_gem_data.counter += 1;
+    global_gem_data.class_minted[class.index()] = global_gem_data.class_minted[class.index()].checked_add(1).unwrap();
+    global_gem_data.add_gem(class);
+    match &funding {
+        GemFunding::Conversion(template) => global_gem_data.add_class_funds(class, &template.funds_location),
+        GemFunding::CompoundReserve => global_gem_data.add_class_funds(class, &FundsLocation::PDPool),
+        GemFunding::Payer { .. } => {}
+    }
+
+    let compounded = matches!(funding, GemFunding::CompoundReserve);
+    let mut owner_account_info = payer_account_info;
+    if let GemFunding::Conversion(_) = funding {
+        // The burned gems' lamports stay where they are, so nothing is raised or moved.
+    } else if compounded {
+        global_gem_data.total_raised += mint_cost;
+        let compound_reserve_account_info = next_account_info(account_info_iter)?;
+        let reserve_owner_account_info = next_account_info(account_info_iter)?;
+        let (_compound_reserve_key, _compound_reserve_bump) = assert_pda_input(&[COMPOUND_RESERVE_KEY.as_ref(), reserve_owner_account_info.key.as_ref()], compound_reserve_account_info);
+        assert_is_signer(payer_account_info)?;
+
+        let mut compound_reserve_data = CompoundReserve::decode(compound_reserve_account_info)?;
+        assert_pubkeys_exactitude(&compound_reserve_data.owner, reserve_owner_account_info.key).expect("Error: @compound reserve owner");
+        if payer_account_info.key != reserve_owner_account_info.key
+            && Class::largest_affordable(compound_reserve_data.balance).map(Class::index) != Some(class.index())
+        {
+            Err(InglError::InvalidConversion.utilize(Some("cranked compound mints must be of the most valuable class the reserve covers")))?
+        }
+        owner_account_info = reserve_owner_account_info;
+        compound_reserve_data.balance = compound_reserve_data
+            .balance
+            .checked_sub(mint_cost)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        compound_reserve_data.gems_minted = compound_reserve_data.gems_minted.checked_add(1).unwrap();
+
+        // The reserve is owned by the program, so its lamports are moved directly.
+        **compound_reserve_account_info.lamports.borrow_mut() = compound_reserve_account_info
+            .lamports()
+            .checked_sub(mint_cost)
+            .unwrap();
+        **minting_pool_account_info.lamports.borrow_mut() = minting_pool_account_info
+            .lamports()
+            .checked_add(mint_cost)
+            .unwrap();
+        global_gem_data.pd_pool_total = global_gem_data.pd_pool_total.checked_add(mint_cost).unwrap();
+        compound_reserve_data.serialize(&mut &mut compound_reserve_account_info.data.borrow_mut()[..])?;
+    } else {
+        global_gem_data.total_raised += mint_cost;
+        //tranfer token from one account to an other
+        invoke(
+            &system_instruction::transfer(payer_account_info.key, &minting_pool_id, mint_cost),
+            &[
+                payer_account_info.clone(),
+                minting_pool_account_info.clone(),
+            ],
+        )?;
+    }
+    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+    let gem_account_data = match funding {
+        GemFunding::Conversion(template) => GemAccountV0_0_1 {
+            struct_id: GemAccountVersions::GemAccountV0_0_1,
+            validation_phrase: GEM_ACCOUNT_VAL_PHRASE,
+            numeration: global_gem_data.counter,
+            rarity: None,
+            class,
+            all_withdraws: Vec::new(),
+            all_votes: Vec::new(),
+            is_liquid: false,
+            ..*template
+        },
+        _ => new_gem_data(class, global_gem_data.counter, current_timestamp, compounded),
+    };
+
+    create_gem_nft(
+        program_id,
+        &GemMintAccounts {
+            payer: payer_account_info,
+            owner: owner_account_info,
+            mint_authority: mint_authority_account_info,
+            spl_token_program: spl_token_program_account_info,
+            sysvar_rent: sysvar_rent_account_info,
+            system_program: system_program_account_info,
+            ingl_edition: ingl_edition_account_info,
+            ingl_collection_mint: ingl_collection_mint_info,
+            ingl_collection: ingl_collection_account_info,
+            metadata_registry: metadata_registry_account_info,
+        },
+        &GemNftAccounts {
+            mint: mint_account_info,
+            associated_token: associated_token_account_info,
+            metadata: metadata_account_info,
+            nft_edition: nft_edition_account_info,
+            gem: gem_account_info,
+        },
+        gem_account_data,
+    )
+}
+
+/// Mints several gems paid by the payer, with a single minting pool transfer and a single global state update.
+/// Accounts: payer, mint authority, spl token program, rent sysvar, system program, minting pool, global gem,
+/// collection edition, collection mint, collection metadata, metadata registry, protocol config, then per gem: mint, associated token account, metadata, edition and gem account.
+/// Every gem costs about as much compute as a single mint_nft, so clients should request a compute budget of roughly 240k units per gem.
+pub fn mint_nfts(program_id: &Pubkey, accounts: &[AccountInfo], classes: Vec<Class>) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+    let sysvar_rent_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let minting_pool_account_info = next_account_info(account_info_iter)?;
+    let global_gem_account_info = next_account_info(account_info_iter)?;
+    let ingl_edition_account_info = next_account_info(account_info_iter)?;
+    let ingl_collection_mint_info = next_account_info(account_info_iter)?;
+    let ingl_collection_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
 
+    if classes.is_empty() || classes.len() > MAX_BATCH_MINT {
+        Err(InglError::BeyondBounds.utilize(Some("batch mint size")))?
+    }
     assert_program_owned(global_gem_account_info)?;
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+    let (minting_pool_id, _minting_pool_bump) = assert_pda_input(&[INGL_MINTING_POOL_KEY.as_ref()], minting_pool_account_info);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+
+    let current_timestamp = Clock::get()?.unix_timestamp as u32;
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+
+    let mut new_gems = Vec::with_capacity(classes.len());
+    let mut mint_cost: u64 = 0;
+    for class in classes {
+        protocol_config.assert_mint_allowed(class, global_gem_data.class_minted[class.index()], false, current_timestamp)?;
+        global_gem_data.counter += 1;
+        global_gem_data.class_minted[class.index()] = global_gem_data.class_minted[class.index()].checked_add(1).unwrap();
+        global_gem_data.add_gem(class);
+        mint_cost = mint_cost.checked_add(class.get_class_lamports()).unwrap();
+        new_gems.push(new_gem_data(class, global_gem_data.counter, current_timestamp, false));
+    }
+    global_gem_data.total_raised = global_gem_data.total_raised.checked_add(mint_cost).unwrap();
+
+    invoke(
+        &system_instruction::transfer(payer_account_info.key, &minting_pool_id, mint_cost),
+        &[
+            payer_account_info.clone(),
+            minting_pool_account_info.clone(),
+        ],
+    )?;
+    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+    let shared = GemMintAccounts {
+        payer: payer_account_info,
+        owner: payer_account_info,
+        mint_authority: mint_authority_account_info,
+        spl_token_program: spl_token_program_account_info,
+        sysvar_rent: sysvar_rent_account_info,
+        system_program: system_program_account_info,
+        ingl_edition: ingl_edition_account_info,
+        ingl_collection_mint: ingl_collection_mint_info,
+        ingl_collection: ingl_collection_account_info,
+        metadata_registry: metadata_registry_account_info,
+    };
+    for gem_account_data in new_gems {
+        let gem = GemNftAccounts {
+            mint: next_account_info(account_info_iter)?,
+            associated_token: next_account_info(account_info_iter)?,
+            metadata: next_account_info(account_info_iter)?,
+            nft_edition: next_account_info(account_info_iter)?,
+            gem: next_account_info(account_info_iter)?,
+        };
+        create_gem_nft(program_id, &shared, &gem, gem_account_data)?;
+    }
+
+    Ok(())
+}
+
+/// Gem state of a freshly paid gem, sitting in the minting pool or, when compounded, already allocated to the PD pool.
+fn new_gem_data(class: Class, numeration: u32, now: u32, compounded: bool) -> GemAccountV0_0_1 {
+    GemAccountV0_0_1 {
+        struct_id: GemAccountVersions::GemAccountV0_0_1,
+        validation_phrase: GEM_ACCOUNT_VAL_PHRASE,
+        date_created: now,
+        redeemable_date: if compounded { now + ALLOCATE_LOCK_TIME } else { now },
+        numeration,
+        rarity: None,
+        funds_location: if compounded { FundsLocation::PDPool } else { FundsLocation::MintingPool },
+        rarity_seed_time: None,
+        date_allocated: if compounded { Some(now) } else { None },
+        class,
+        last_voted_proposal: None,
+        last_withdrawal_epoch: None,
+        last_delegation_epoch: None,
+        all_withdraws: Vec::new(),
+        all_votes: Vec::new(),
+        is_liquid: false,
+        auto_delegate: false,
+        compound_rewards: false,
+        randomness_source: RandomnessSource::PriceFeeds,
+        rarity_commit_slot: 0,
+        rarity_rolls: 0,
+        rarity_vrf_round: 0,
+    }
+}
+
+/// Creates a gem's account, NFT mint, metadata and master edition, verified in the ingl collection.
+fn create_gem_nft<'a>(
+    program_id: &Pubkey,
+    shared: &GemMintAccounts<'a, '_>,
+    gem: &GemNftAccounts<'a, '_>,
+    gem_account_data: GemAccountV0_0_1,
+) -> ProgramResult {
+    let payer_account_info = shared.payer;
+    let mint_authority_account_info = shared.mint_authority;
+    let spl_token_program_account_info = shared.spl_token_program;
+    let sysvar_rent_account_info = shared.sysvar_rent;
+    let system_program_account_info = shared.system_program;
+    let ingl_edition_account_info = shared.ingl_edition;
+    let ingl_collection_mint_info = shared.ingl_collection_mint;
+    let ingl_collection_account_info = shared.ingl_collection;
+    let mint_account_info = gem.mint;
+    let associated_token_account_info = gem.associated_token;
+    let metadata_account_info = gem.metadata;
+    let nft_edition_account_info = gem.nft_edition;
+    let gem_account_info = gem.gem;
+    let metadata_registry = load_metadata_registry(shared.metadata_registry)?;
+
     assert_owned_by(ingl_edition_account_info, &metaplex::id())?;
     assert_owned_by(ingl_collection_account_info, &metaplex::id())?;
     assert_owned_by(ingl_collection_mint_info, &spl_program::id())?;
@@ -597,16 +986,9 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
     assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
     assert_pubkeys_exactitude(sysvar_rent_account_info.key, &sysvar::rent::id())?;
 
-    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
-    let (minting_pool_id, _minting_pool_bump) = assert_pda_input(&[INGL_MINTING_POOL_KEY.as_ref()], minting_pool_account_info);
     let (mint_authority_key, mint_authority_bump) = assert_pda_input(&[INGL_MINT_AUTHORITY_KEY.as_ref()], mint_authority_account_info);
     let (ingl_nft_collection_key, _ingl_nft_bump) = assert_pda_input(&[INGL_NFT_COLLECTION_KEY.as_ref()], ingl_collection_mint_info);
 
-    
-    let clock = Clock::get()?;
-    // Getting timestamp
-    let current_timestamp = clock.unix_timestamp as u32;
-
     let (gem_account_pubkey, gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_info);
     let space = 500;
     let rent_lamports = Rent::get()?.minimum_balance(space);
@@ -627,19 +1009,15 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
         ]],
     )?;
 
-    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
-
     let space = 82;
     let rent_lamports = Rent::get()?.minimum_balance(space);
 
-
     assert_pubkeys_exactitude(
-        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        &get_associated_token_address(shared.owner.key, mint_account_info.key),
         associated_token_account_info.key,
     )
     .expect("Error: @associated_token_account_info");
 
-
     let mpl_token_metadata_id = mpl_token_metadata::id();
     let metadata_seeds = &[
         PREFIX.as_bytes(),
@@ -653,20 +1031,6 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
     assert_pubkeys_exactitude(&nft_metadata_key, metadata_account_info.key)
         .expect("Error: @meta_data_account_info");
 
-    let mint_cost = class.clone().get_class_lamports();
-    global_gem_data.counter += 1;
-    global_gem_data.total_raised += mint_cost;
-
-    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
-    //tranfer token from one account to an other
-    invoke(
-        &system_instruction::transfer(payer_account_info.key, &minting_pool_id, mint_cost),
-        &[
-            payer_account_info.clone(),
-            minting_pool_account_info.clone(),
-        ],
-    )?;
-
     //create the mint account
     invoke(
         &system_instruction::create_account(
@@ -693,13 +1057,13 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
     invoke(
         &spl_associated_token_account::instruction::create_associated_token_account(
             payer_account_info.key,
-            payer_account_info.key,
+            shared.owner.key,
             mint_account_info.key,
         ),
         &[
             payer_account_info.clone(),
             associated_token_account_info.clone(),
-            payer_account_info.clone(),
+            shared.owner.clone(),
             mint_account_info.clone(),
             system_program_account_info.clone(),
             spl_token_program_account_info.clone(),
@@ -753,9 +1117,9 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
             *mint_authority_account_info.key,
             *payer_account_info.key,
             *mint_authority_account_info.key,
-            String::from("Ingl Gem #") + &global_gem_data.counter.to_string(),
-            String::from("I-Gem#") + &global_gem_data.counter.to_string(),
-            String::from(nfts::get_uri(class, None)),
+            nfts::resolve_name(metadata_registry.as_ref(), gem_account_data.class, gem_account_data.numeration),
+            nfts::resolve_symbol(metadata_registry.as_ref()),
+            nfts::gem_metadata_uri(metadata_registry.as_ref(), &gem_account_data),
             Some(creators),
             300,
             true,
@@ -852,22 +1216,6 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
         &[&[INGL_MINT_AUTHORITY_KEY.as_ref(), &[mint_authority_bump]]],
     )?;
 
-    invoke(
-        &spl_token::instruction::approve(
-            &spl_token::id(),
-            associated_token_account_info.key,
-            &mint_authority_key,
-            payer_account_info.key,
-            &[],
-            1,
-        )?,
-        &[
-            associated_token_account_info.clone(),
-            mint_authority_account_info.clone(),
-            payer_account_info.clone(),
-        ],
-    )?;
-
     let (edition_key, _edition_bump) = Pubkey::find_program_address(
         &[
             b"metadata",
@@ -880,39 +1228,35 @@ pub fn mint_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class) ->
     assert_pubkeys_exactitude(&edition_key, ingl_edition_account_info.key)
         .expect("Error: @edition_account_info");
 
-    // msg!("updating update_primary_sale_happened_via_token");
-    invoke(
-        &mpl_token_metadata::instruction::update_primary_sale_happened_via_token(
-            mpl_token_metadata::id(),
-            nft_metadata_key,
-            *payer_account_info.key,
-            *associated_token_account_info.key,
-        ),
-        &[
-            metadata_account_info.clone(),
-            payer_account_info.clone(),
-            associated_token_account_info.clone(),
-        ],
-    )?;
+    // msg!("handing the gem over to its holder");
+    for instruction in nfts::gem_handover_instructions(
+        payer_account_info.key,
+        shared.owner.key,
+        &mint_authority_key,
+        associated_token_account_info.key,
+        &nft_metadata_key,
+    )? {
+        invoke_signed(
+            &instruction,
+            &[
+                associated_token_account_info.clone(),
+                metadata_account_info.clone(),
+                mint_authority_account_info.clone(),
+                payer_account_info.clone(),
+            ],
+            &[&[INGL_MINT_AUTHORITY_KEY.as_ref(), &[mint_authority_bump]]],
+        )?;
+    }
 
-    let gem_account_data = GemAccountV0_0_1 {
-        struct_id: GemAccountVersions::GemAccountV0_0_1,
-        validation_phrase: GEM_ACCOUNT_VAL_PHRASE,
-        date_created: current_timestamp,
-        redeemable_date: current_timestamp,
-        numeration: global_gem_data.counter,
-        rarity: None,
-        funds_location: FundsLocation::MintingPool,
-        rarity_seed_time: None,
-        date_allocated: None,
-        class: class,
-        last_voted_proposal: None,
-        last_withdrawal_epoch: None,
-        last_delegation_epoch: None,
-        all_withdraws: Vec::new(),
-        all_votes: Vec::new(),
-    };
     gem_account_data.serialize(&mut &mut gem_account_info.data.borrow_mut()[..])?;
+    InglEvent::GemMinted {
+        mint: *mint_account_info.key,
+        owner: *shared.owner.key,
+        class: gem_account_data.class,
+        numeration: gem_account_data.numeration,
+        funds_location: gem_account_data.funds_location,
+    }
+    .emit();
     Ok(())
 }
 
@@ -997,6 +1341,12 @@ pub fn mint_collection(program_id: &Pubkey, accounts: &[AccountInfo]) -> Program
         pending_delegation_total: 0,
         validator_list: Vec::new(),
         dealloced_total: 0,
+        class_minted: [0; 6],
+        class_supply: [0; 6],
+        class_rarity_supply: [[0; 5]; 6],
+        class_allocated: [0; 6],
+        class_delegated: [0; 6],
+        treasury_proposal_numeration: 0,
     };
     global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
 
@@ -1178,6 +1528,8 @@ pub fn init_rarity_imprint(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Pr
     let associated_token_account_info = next_account_info(account_info_iter)?;
     let freeze_authority_account_info = next_account_info(account_info_iter)?;
     let nft_edition_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+    // The accounts read by the configured randomness source's commitment follow, see randomness::commit_seed.
 
     assert_is_signer(payer_account_info).unwrap();
     assert_program_owned(gem_account_info)?;
@@ -1209,8 +1561,10 @@ pub fn init_rarity_imprint(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Pr
         Err(TokenError::AccountFrozen)?
     }
 
-    gem_data.rarity_seed_time =
-        Some(Clock::get()?.unix_timestamp as u32 + PRICE_TIME_INTERVAL as u32);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+
+    randomness::commit_seed(account_info_iter, &mut gem_data, &protocol_config, &Clock::get()?)?;
     gem_data.serialize(&mut &mut gem_account_info.data.borrow_mut()[..])?;
 
 
@@ -1256,32 +1610,15 @@ pub fn imprint_rarity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
     let freeze_authority_account_info = next_account_info(account_info_iter)?;
     let metadata_account_info = next_account_info(account_info_iter)?;
     let nft_edition_account_info = next_account_info(account_info_iter)?;
-
-    let btc_feed_account_info = next_account_info(account_info_iter)?;
-    let sol_feed_account_info = next_account_info(account_info_iter)?;
-    let eth_feed_account_info = next_account_info(account_info_iter)?;
-    let bnb_feed_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+    let global_gem_account_info = next_account_info(account_info_iter)?;
+    // The accounts read by the gem's randomness source follow, see randomness::draw_seed.
 
     assert_program_owned(gem_account_info)?;
     assert_owned_by(mint_account_info, &spl_program::id())?;
     assert_owned_by(metadata_account_info, &mpl_token_metadata::id())?;
     assert_owned_by(associated_token_account_info, &spl_program::id())?;
-    assert_owned_by(btc_feed_account_info, &SWITCHBOARD_V2_DEVNET).or(assert_owned_by(
-        btc_feed_account_info,
-        &SWITCHBOARD_V2_MAINNET,
-    ))?;
-    assert_owned_by(sol_feed_account_info, &SWITCHBOARD_V2_DEVNET).or(assert_owned_by(
-        sol_feed_account_info,
-        &SWITCHBOARD_V2_MAINNET,
-    ))?;
-    assert_owned_by(eth_feed_account_info, &SWITCHBOARD_V2_DEVNET).or(assert_owned_by(
-        eth_feed_account_info,
-        &SWITCHBOARD_V2_MAINNET,
-    ))?;
-    assert_owned_by(bnb_feed_account_info, &SWITCHBOARD_V2_DEVNET).or(assert_owned_by(
-        bnb_feed_account_info,
-        &SWITCHBOARD_V2_MAINNET,
-    ))?;
 
     assert_pubkeys_exactitude(
         &get_associated_token_address(payer_account_info.key, mint_account_info.key),
@@ -1302,27 +1639,21 @@ pub fn imprint_rarity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
     let (mint_authority_key, mint_authority_bump) =  assert_pda_input(&[INGL_MINT_AUTHORITY_KEY.as_ref()], freeze_authority_account_info);
 
     let (_gem_pubkey, _gem_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_info);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
 
-    assert_pubkeys_exactitude(
-        &Pubkey::from_str(BTC_FEED_PUBLIC_KEY).unwrap(),
-        btc_feed_account_info.key,
-    )
-    .expect("btc_history_account_info_owner");
-    assert_pubkeys_exactitude(
-        &Pubkey::from_str(SOL_FEED_PUBLIC_KEY).unwrap(),
-        sol_feed_account_info.key,
-    )
-    .expect("sol_price_account_info_owner");
-    assert_pubkeys_exactitude(
-        &Pubkey::from_str(ETH_FEED_PUBLIC_KEY).unwrap(),
-        eth_feed_account_info.key,
-    )
-    .expect("eth_price_account_info_owner");
-    assert_pubkeys_exactitude(
-        &Pubkey::from_str(BNB_FEED_PUBLIC_KEY).unwrap(),
-        bnb_feed_account_info.key,
-    )
-    .expect("bnb_price_account_info_owner");
+    let mut gem_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(
+        &gem_account_info.data.borrow(),
+    )?)?;
+    let now = Clock::get()?;
+    if (now.unix_timestamp as u32) < gem_data.rarity_seed_time.unwrap() {
+        Err(InglError::TooEarly.utilize(Some("imprint_rarity")))?
+    }
+    if gem_data.rarity.is_some() {
+        Err(ProgramError::InvalidAccountData)?
+    }
+
+    let seed = randomness::draw_seed(account_info_iter, &gem_data, &protocol_config, mint_account_info.key, program_id)?;
 
     let mpl_token_metadata_id = mpl_token_metadata::id();
     let (nft_edition_key, _nft_edition_bump) = Pubkey::find_program_address(
@@ -1354,110 +1685,27 @@ pub fn imprint_rarity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
         &[&[INGL_MINT_AUTHORITY_KEY.as_ref(), &[mint_authority_bump]]],
     )?;
 
-    let mut gem_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(
-        &gem_account_info.data.borrow(),
-    )?)?;
-    let now = Clock::get()?;
-    msg!(
-        "now: {}, seed_time: {}",
-        now.unix_timestamp,
-        gem_data.rarity_seed_time.unwrap()
-    );
-    if (now.unix_timestamp as u32) < gem_data.rarity_seed_time.unwrap() {
-        Err(InglError::TooEarly.utilize(Some("imprint_rarity")))?
-    }
-    if let Some(_) = gem_data.rarity {
-        Err(ProgramError::InvalidAccountData)?
-    }
-
-    let btc_history = AggregatorHistoryBuffer::new(btc_feed_account_info)?;
-    let AggregatorHistoryRow {
-        value: btc_value,
-        timestamp: _,
-    } = btc_history
-        .lower_bound(gem_data.rarity_seed_time.unwrap() as i64)
-        .unwrap();
-    let btc_price = btc_value.mantissa * 10.pow(btc_value.scale) as i128;
-
-    let sol_history = AggregatorHistoryBuffer::new(sol_feed_account_info)?;
-    let AggregatorHistoryRow {
-        value: sol_value,
-        timestamp: _,
-    } = sol_history
-        .lower_bound(gem_data.rarity_seed_time.unwrap() as i64)
-        .unwrap();
-    let sol_price = sol_value.mantissa * 10.pow(sol_value.scale) as i128;
-
-    let eth_history = AggregatorHistoryBuffer::new(eth_feed_account_info)?;
-    let AggregatorHistoryRow {
-        value: eth_value,
-        timestamp: _,
-    } = eth_history
-        .lower_bound(gem_data.rarity_seed_time.unwrap() as i64)
-        .unwrap();
-    let eth_price = eth_value.mantissa * 10.pow(eth_value.scale) as i128;
-
-    let bnb_history = AggregatorHistoryBuffer::new(bnb_feed_account_info)?;
-    let AggregatorHistoryRow {
-        value: bnb_value,
-        timestamp: _,
-    } = bnb_history
-        .lower_bound(gem_data.rarity_seed_time.unwrap() as i64)
-        .unwrap();
-    let bnb_price = bnb_value.mantissa * 10.pow(bnb_value.scale) as i128;
-
-    let rarity_hash_string = hashv(&[&btc_price.to_be_bytes(), &sol_price.to_be_bytes(), &eth_price.to_be_bytes(), &bnb_price.to_be_bytes(), &mint_account_info.key.to_bytes(), &program_id.to_bytes()]);
-    let rarity_hash_bytes = rarity_hash_string.to_bytes();
-
-    let mut byte_sum: u64 = 0;
-    for byte in rarity_hash_bytes {
-        byte_sum = byte_sum + (byte as u64).pow(2);
-    }
-    
-    let random_value = byte_sum*10%10000;
-    msg!("Bytes product: {:?}", random_value);
+    let random_value = randomness::rarity_roll(seed);
     gem_data.rarity = gem_data.class.get_rarity(random_value);
 
-    let mpl_token_metadata_id = mpl_token_metadata::id();
-    let metadata_seeds = &[
-        PREFIX.as_bytes(),
-        mpl_token_metadata_id.as_ref(),
-        mint_account_info.key.as_ref(),
-    ];
-
-    let (nft_metadata_key, _nft_metadata_bump) =
-        Pubkey::find_program_address(metadata_seeds, &mpl_token_metadata::id());
-
-    assert_pubkeys_exactitude(&nft_metadata_key, metadata_account_info.key)
-        .expect("Error: @meta_data_account_info");
-
-    let gem_metadata = Metadata::deserialize(&mut &metadata_account_info.data.borrow()[..])?;
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+    if let Some(rarity) = &gem_data.rarity {
+        global_gem_data.add_rarity(gem_data.class, rarity);
+    }
+    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
 
-    invoke_signed(
-        &mpl_token_metadata::instruction::update_metadata_accounts_v2(
-            mpl_token_metadata_id,
-            *metadata_account_info.key,
-            *freeze_authority_account_info.key,
-            Some(*freeze_authority_account_info.key),
-            Some(DataV2 {
-                uri: String::from(nfts::get_uri(gem_data.class, gem_data.rarity.clone())),
-                uses: gem_metadata.uses,
-                name: gem_metadata.data.name,
-                symbol: gem_metadata.data.symbol,
-                collection: gem_metadata.collection,
-                creators: gem_metadata.data.creators,
-                seller_fee_basis_points: gem_metadata.data.seller_fee_basis_points,
-            }),
-            Some(gem_metadata.primary_sale_happened),
-            Some(gem_metadata.is_mutable),
-        ),
-        &[
-            metadata_account_info.clone(),
-            freeze_authority_account_info.clone(),
-        ],
-        &[&[INGL_MINT_AUTHORITY_KEY.as_ref(), &[mint_authority_bump]]],
+    sync_gem_metadata(
+        mint_account_info,
+        metadata_account_info,
+        freeze_authority_account_info,
+        metadata_registry_account_info,
+        &gem_data,
     )?;
     gem_data.serialize(&mut &mut gem_account_info.data.borrow_mut()[..])?;
+    if let Some(rarity) = gem_data.rarity {
+        InglEvent::RarityImprinted { mint: *mint_account_info.key, rarity, rolls: gem_data.rarity_rolls }.emit();
+    }
     Ok(())
 }
 
@@ -1474,9 +1722,13 @@ pub fn redeem_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
     let ingl_collection_account_info = next_account_info(account_info_iter)?;
     let spl_token_program_account_info = next_account_info(account_info_iter)?;
     let program_treasury_account_info = next_account_info(account_info_iter)?;
+    let global_gem_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+    let team_vault_account_info = next_account_info(account_info_iter)?;
 
     assert_is_signer(payer_account_info).unwrap();
     assert_program_owned(gem_account_info)?;
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
     assert_owned_by(mint_account_info, &spl_program::id())?;
     assert_owned_by(associated_token_account_info, &spl_program::id())?;
     assert_pubkeys_exactitude(
@@ -1485,7 +1737,7 @@ pub fn redeem_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
     )
     .expect("associated_token_account_info");
 
-    let (mint_authority_key, _mint_authority_bump) = assert_pda_input(&[INGL_MINT_AUTHORITY_KEY.as_ref()], mint_authority_account_info);
+    let (_mint_authority_key, _mint_authority_bump) = assert_pda_input(&[INGL_MINT_AUTHORITY_KEY.as_ref()], mint_authority_account_info);
 
     let (minting_pool_id, minting_pool_bump) = assert_pda_input(&[INGL_MINTING_POOL_KEY.as_ref()], minting_pool_account_info);
 
@@ -1548,53 +1800,47 @@ pub fn redeem_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
         Err(InglError::TooEarly.utilize(Some("redeem_nft")))?;
     }
 
-    let mut redeem_fees: u64 = 0;
-    if let Some(val) = gem_data.rarity_seed_time{
-        let spent_time =
-            ((now.checked_sub(val)).unwrap() as f64) / ((60 * 60 * 24 * 365) as f64);
-        msg!("Now: {}, rarity_seed_time: {}, spent_time: {}",now, val, spent_time);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    let redeem_fees = protocol_config.redeem_fee(gem_data.class.get_class_lamports(), now.saturating_sub(gem_data.date_created));
+    if redeem_fees > 0 {
+        let (program_treasury_id, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], program_treasury_account_info);
 
-        if spent_time < 1.0 {
-            redeem_fees = redeem_fees.checked_add((((1.0 - spent_time.pow(2) as f64).sqrt() * FEE_MULTIPLYER as f64 / 100.0) * (gem_data.class.get_class_lamports() as f64)) as u64).ok_or(InglError::BeyondBounds).unwrap();
-            
-            let (program_treasury_id, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], program_treasury_account_info);
-
-            let treasury_funds = (redeem_fees as f64 * TREASURY_FEE_MULTIPLYER as f64 / 100.0) as u64;
-            let mint_authority_funds = redeem_fees.checked_sub(treasury_funds).ok_or(InglError::BeyondBounds).unwrap();
-
-                invoke_signed(
-                    &system_instruction::transfer(
-                        &minting_pool_id,
-                        &program_treasury_id,
-                        treasury_funds,
-                    ),
-                    &[
-                        minting_pool_account_info.clone(),
-                        program_treasury_account_info.clone(),
-                    ],
-                    &[&[INGL_MINTING_POOL_KEY.as_ref(), &[minting_pool_bump]]],
-                )?;
-                invoke_signed(
-                    &system_instruction::transfer(
-                        &minting_pool_id,
-                        &mint_authority_key,
-                        mint_authority_funds,
-                    ),
-                    &[
-                        minting_pool_account_info.clone(),
-                        mint_authority_account_info.clone(),
-                    ],
-                    &[&[INGL_MINTING_POOL_KEY.as_ref(), &[minting_pool_bump]]],
-                )?;
-            }
+        let (treasury_funds, team_funds) = split_redeem_fee(redeem_fees, TREASURY_FEE_MULTIPLYER);
+        let (team_vault_key, _team_vault_bump) = assert_pda_input(&[TEAM_VAULT_KEY.as_ref()], team_vault_account_info);
+
+        invoke_signed(
+            &system_instruction::transfer(
+                &minting_pool_id,
+                &program_treasury_id,
+                treasury_funds,
+            ),
+            &[
+                minting_pool_account_info.clone(),
+                program_treasury_account_info.clone(),
+            ],
+            &[&[INGL_MINTING_POOL_KEY.as_ref(), &[minting_pool_bump]]],
+        )?;
+        invoke_signed(
+            &system_instruction::transfer(
+                &minting_pool_id,
+                &team_vault_key,
+                team_funds,
+            ),
+            &[
+                minting_pool_account_info.clone(),
+                team_vault_account_info.clone(),
+            ],
+            &[&[INGL_MINTING_POOL_KEY.as_ref(), &[minting_pool_bump]]],
+        )?;
     }
 
-    msg!("Redeem_fees: {:?} lamports: {:?}",redeem_fees, gem_data.class.get_class_lamports());
+    let refund = gem_data.class.get_class_lamports().checked_sub(redeem_fees).ok_or(InglError::BeyondBounds).unwrap();
     invoke_signed(
         &system_instruction::transfer(
             &minting_pool_id,
             payer_account_info.key,
-            gem_data.class.get_class_lamports().checked_sub(redeem_fees).ok_or(InglError::BeyondBounds).unwrap(),
+            refund,
         ),
         &[
             minting_pool_account_info.clone(),
@@ -1634,6 +1880,11 @@ pub fn redeem_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResul
     let mut payer_gem_data = gem_account_info.data.borrow_mut();
     payer_gem_data.fill(0);
 
+    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+    global_gem_data.remove_gem(gem_data.class, &gem_data.rarity);
+    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+    InglEvent::Redeemed { mint: *mint_account_info.key, owner: *payer_account_info.key, refund, fee: redeem_fees }.emit();
     Ok(())
 }
 
@@ -1648,6 +1899,9 @@ pub fn delegate_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
     let global_gem_account_info = next_account_info(account_info_iter)?;
     let sysvar_clock_info = next_account_info(account_info_iter)?;
     let stake_config_program_info = next_account_info(account_info_iter)?;
+    let metadata_account_info = next_account_info(account_info_iter)?;
+    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
 
     assert_pubkeys_exactitude(sysvar_clock_info.key, &sysvar::clock::id())
         .expect("sysvar clock info");
@@ -1683,6 +1937,42 @@ pub fn delegate_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
     )?;
     let mut global_gem_account_data = GlobalGems::decode(global_gem_account_info)?;
 
+    delegate_gem(
+        &mut gem_account_data,
+        &mut ingl_vote_account_data,
+        &mut global_gem_account_data,
+        vote_account_info.key,
+    )?;
+
+    global_gem_account_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+    ingl_vote_account_data
+        .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    InglEvent::Delegated {
+        mint: *mint_account_info.key,
+        vote_account: *vote_account_info.key,
+        lamports: gem_account_data.class.get_class_lamports(),
+    }
+    .emit();
+
+    sync_gem_metadata(
+        mint_account_info,
+        metadata_account_info,
+        mint_authority_account_info,
+        metadata_registry_account_info,
+        &gem_account_data,
+    )?;
+
+    Ok(())
+}
+
+/// Moves a PD pool gem's stake accounting to the given vote account. Shared by delegate_nft and the auto delegation crank.
+fn delegate_gem(
+    gem_account_data: &mut GemAccountV0_0_1,
+    ingl_vote_account_data: &mut InglVoteAccountData,
+    global_gem_account_data: &mut GlobalGems,
+    vote_account_key: &Pubkey,
+) -> ProgramResult {
     global_gem_account_data.pd_pool_total = global_gem_account_data
         .pd_pool_total
         .checked_sub(gem_account_data.class.get_class_lamports())
@@ -1691,11 +1981,13 @@ pub fn delegate_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
         .delegated_total
         .checked_add(gem_account_data.class.get_class_lamports())
         .unwrap();
+    global_gem_account_data.remove_class_funds(gem_account_data.class, &FundsLocation::PDPool);
+    global_gem_account_data.add_class_funds(gem_account_data.class, &FundsLocation::VoteAccount { vote_account_id: *vote_account_key });
 
     match gem_account_data.funds_location {
         FundsLocation::PDPool => {
             gem_account_data.funds_location = FundsLocation::VoteAccount {
-                vote_account_id: *vote_account_info.key,
+                vote_account_id: *vote_account_key,
             };
             gem_account_data.last_delegation_epoch = Some(Clock::get()?.epoch);
         }
@@ -1735,11 +2027,20 @@ pub fn delegate_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramRe
         .checked_add(gem_account_data.class.get_class_lamports())
         .unwrap();
 
-    global_gem_account_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
-    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
-    ingl_vote_account_data
-        .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
-
+    let liquid_lamports = if gem_account_data.is_liquid {
+        gem_account_data.class.get_class_lamports()
+    } else {
+        0
+    };
+    ingl_vote_account_data.liquid_delegated = ingl_vote_account_data
+        .liquid_delegated
+        .checked_add(liquid_lamports)
+        .unwrap();
+    ingl_vote_account_data.add_pending_activation(
+        gem_account_data.last_delegation_epoch.unwrap(),
+        gem_account_data.class.get_class_lamports(),
+        liquid_lamports,
+    );
     Ok(())
 }
 
@@ -1756,8 +2057,13 @@ pub fn undelegate_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
     let validator_account_info = next_account_info(account_info_iter)?;
     let system_program_account_info = next_account_info(account_info_iter)?;
     let authorized_withdrawer_info = next_account_info(account_info_iter)?;
+    let sysvar_stake_history_info = next_account_info(account_info_iter)?;
+    let metadata_account_info = next_account_info(account_info_iter)?;
+    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
 
     assert_is_signer(payer_account_info).unwrap();
+    assert_pubkeys_exactitude(sysvar_stake_history_info.key, &sysvar::stake_history::id())?;
 
     let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()],gem_account_data_info);
     assert_pubkeys_exactitude(
@@ -1794,6 +2100,8 @@ pub fn undelegate_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
         .delegated_total
         .checked_sub(gem_account_data.class.get_class_lamports())
         .unwrap();
+    global_gem_account_data.remove_class_funds(gem_account_data.class, &gem_account_data.funds_location);
+    global_gem_account_data.add_class_funds(gem_account_data.class, &FundsLocation::PDPool);
     ingl_vote_account_data.total_delegated = ingl_vote_account_data
         .total_delegated
         .checked_sub(gem_account_data.class.get_class_lamports())
@@ -1821,25 +2129,52 @@ pub fn undelegate_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
             .unwrap();
     }
 
+    let delegation_epoch = gem_account_data.last_delegation_epoch.unwrap();
+    if ingl_vote_account_data.is_stake_active(delegation_epoch) {
+        // The gem earns through the previous epoch, so that epoch's rewards must be processed before it leaves.
+        if let Some(last_rewards) = ingl_vote_account_data.vote_rewards.last() {
+            if last_rewards.epoch_number < Clock::get()?.epoch {
+                Err(InglError::TooEarly.utilize(Some("Process this epoch's rewards before undelegating")))?
+            }
+        }
+        ingl_vote_account_data.active_stake = ingl_vote_account_data
+            .active_stake
+            .saturating_sub(gem_account_data.class.get_class_lamports());
+    } else {
+        ingl_vote_account_data.remove_pending_activation(
+            delegation_epoch,
+            gem_account_data.class.get_class_lamports(),
+            0,
+        );
+    }
 
-    
     //TODO: The testing for this isn't done yet, so please do not deploy this version of the code until it is thoroughly tested.
-    let new_accounts = &[ 
+    let mut new_accounts = vec![ 
         payer_account_info.clone(),
         vote_account_info.clone(),
         validator_account_info.clone(),
         ingl_vote_data_account_info.clone(),
         authorized_withdrawer_info.clone(),
+        sysvar_stake_history_info.clone(),
 
         associated_token_account_info.clone(),
         mint_account_info.clone(),
         gem_account_data_info.clone(),
-
-        system_program_account_info.clone()
     ];
+    if gem_account_data.compound_rewards {
+        // Compounding gems send their final rewards to the holder's compound reserve, passed as the last account.
+        // Holders without a reserve, such as the buyer of a compounding gem, are paid directly.
+        if let Some(compound_reserve_account_info) = account_info_iter.next() {
+            new_accounts.push(compound_reserve_account_info.clone());
+        }
+    }
+    new_accounts.push(system_program_account_info.clone());
+    // nft_withdraw records the gem's rarity bonus claims on the vote data, so it must read the stake changes above and not be overwritten after.
+    ingl_vote_account_data
+        .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
     //TODO: Please do not deploy this version of the code until it is thoroughly tested. else the undelegate functionality might not work properly.
     // IF ALREADY DEPLOYED, PLEASE COMMENT THE LINE BELOW AND REDEPLOY. 
-    nft_withdraw(program_id, new_accounts, 1)?;
+    nft_withdraw(program_id, &new_accounts, 1)?;
 
     match gem_account_data.funds_location {
         FundsLocation::VoteAccount { vote_account_id } => {
@@ -1851,9 +2186,21 @@ pub fn undelegate_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
     }
 
     global_gem_account_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
-    ingl_vote_account_data
-        .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
     gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+    InglEvent::Undelegated {
+        mint: *mint_account_info.key,
+        vote_account: *vote_account_info.key,
+        lamports: gem_account_data.class.get_class_lamports(),
+    }
+    .emit();
+
+    sync_gem_metadata(
+        mint_account_info,
+        metadata_account_info,
+        mint_authority_account_info,
+        metadata_registry_account_info,
+        &gem_account_data,
+    )?;
 
     Ok(())
 }
@@ -1865,12 +2212,21 @@ pub fn process_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
     let vote_account_info = next_account_info(account_info_iter)?;
     let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
     let authorized_withdrawer_info = next_account_info(account_info_iter)?;
-    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let team_vault_account_info = next_account_info(account_info_iter)?;
     let treasury_account_info = next_account_info(account_info_iter)?;
+    let liquid_stake_data_account_info = next_account_info(account_info_iter)?;
+    let liquid_reserve_account_info = next_account_info(account_info_iter)?;
+    let sysvar_stake_history_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
 
-    let (_mint_authority_key, _mint_authority_bump) = assert_pda_input(&[INGL_MINT_AUTHORITY_KEY.as_ref()], mint_authority_account_info);
+    assert_pubkeys_exactitude(sysvar_stake_history_info.key, &sysvar::stake_history::id())?;
+    let (_team_vault_key, _team_vault_bump) = assert_pda_input(&[TEAM_VAULT_KEY.as_ref()], team_vault_account_info);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    let (_liquid_stake_data_key, _liquid_stake_data_bump) = assert_pda_input(&[LIQUID_STAKE_DATA_KEY.as_ref()], liquid_stake_data_account_info);
+    let (_liquid_reserve_key, _liquid_reserve_bump) = assert_pda_input(&[LIQUID_RESERVE_KEY.as_ref()], liquid_reserve_account_info);
 
-    let (_treasury_key, _treasury_bump) = assert_pda_input(&[TREASURY_ACCOUNT_KEY.as_ref()], treasury_account_info);
+    let (_treasury_key, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], treasury_account_info);
 
     let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(),vote_account_info.key.as_ref(),], ingl_vote_data_account_info);
     assert_program_owned(ingl_vote_data_account_info)?;
@@ -1884,7 +2240,7 @@ pub fn process_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
         .lamports()
         .checked_sub(Rent::get()?.minimum_balance(vote_account_info.data_len()))
         .unwrap();
-    let one_percent: u64 = lamports.checked_div(100).unwrap();
+    let reward_split = rewards::REWARD_SHARES.split(lamports, protocol_config.rarity_bonus_pool_bps);
 
     invoke_signed(
         &vote_withdraw(
@@ -1911,12 +2267,12 @@ pub fn process_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
     invoke_signed(
         &system_instruction::transfer(
             authorized_withdrawer_info.key,
-            mint_authority_account_info.key,
-            one_percent.checked_mul(TEAM_SHARE).unwrap(),
+            team_vault_account_info.key,
+            reward_split.team,
         ),
         &[
             authorized_withdrawer_info.clone(),
-            mint_authority_account_info.clone(),
+            team_vault_account_info.clone(),
         ],
         &[&[
             AUTHORIZED_WITHDRAWER_KEY.as_ref(),
@@ -1928,7 +2284,7 @@ pub fn process_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
         &system_instruction::transfer(
             authorized_withdrawer_info.key,
             validator_info.key,
-            one_percent.checked_mul(VALIDATOR_ID_SHARE).unwrap(),
+            reward_split.validator_id,
         ),
         &[authorized_withdrawer_info.clone(), validator_info.clone()],
         &[&[
@@ -1937,11 +2293,34 @@ pub fn process_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
         ]],
     )?;
 
+    // The snapshot only counts the stake that was fully active during the epoch being rewarded.
+    let reward_epoch = Clock::get()?.epoch.saturating_sub(1);
+    let stake_history = StakeHistory::from_account_info(sysvar_stake_history_info)?;
+    let mut still_pending = Vec::new();
+    for cohort in ingl_vote_account_data.pending_activations.iter() {
+        match rewards::first_active_epoch(cohort.delegation_epoch, &stake_history) {
+            Some(epoch) if epoch <= reward_epoch => {
+                ingl_vote_account_data.active_stake = ingl_vote_account_data.active_stake.checked_add(cohort.lamports).unwrap();
+                ingl_vote_account_data.liquid_active_stake = ingl_vote_account_data.liquid_active_stake.checked_add(cohort.liquid_lamports).unwrap();
+            }
+            _ => still_pending.push(*cohort),
+        }
+    }
+    ingl_vote_account_data.pending_activations = still_pending;
+
+    // The claimable part of the rarity bonus pool stays with the authorized withdrawer, next to the NFTs share it tops up.
+    // The rest, such as the liquid gems' share, is returned to the treasury along with its own share.
+    let rarity_bonus_pool = rewards::claimable_rarity_bonus_pool(
+        reward_split.rarity_bonus_pool,
+        ingl_vote_account_data.active_stake,
+        ingl_vote_account_data.liquid_active_stake,
+        &protocol_config.rarity_reward_bonus_bps,
+    );
     invoke_signed(
         &system_instruction::transfer(
             authorized_withdrawer_info.key,
             treasury_account_info.key,
-            one_percent.checked_mul(TREASURY_SHARE).unwrap(),
+            reward_split.treasury + reward_split.rarity_bonus_pool - rarity_bonus_pool,
         ),
         &[
             authorized_withdrawer_info.clone(),
@@ -1953,20 +2332,62 @@ pub fn process_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Progra
         ]],
     )?;
 
+    // The NFTs share of the liquid gems is moved to the liquid reserve, raising the liquid token's exchange rate.
+    if ingl_vote_account_data.liquid_active_stake > 0 && ingl_vote_account_data.active_stake > 0 {
+        let liquid_rewards = (reward_split.nfts as u128
+            * ingl_vote_account_data.liquid_active_stake as u128
+            / ingl_vote_account_data.active_stake as u128) as u64;
+
+        let mut liquid_stake_data = LiquidStakeData::decode(liquid_stake_data_account_info)?;
+        invoke_signed(
+            &system_instruction::transfer(
+                authorized_withdrawer_info.key,
+                liquid_reserve_account_info.key,
+                liquid_rewards,
+            ),
+            &[
+                authorized_withdrawer_info.clone(),
+                liquid_reserve_account_info.clone(),
+            ],
+            &[&[
+                AUTHORIZED_WITHDRAWER_KEY.as_ref(),
+                &[authorized_withdrawer_bump],
+            ]],
+        )?;
+        liquid_stake_data.accrued_rewards = liquid_stake_data.accrued_rewards.checked_add(liquid_rewards).unwrap();
+        liquid_stake_data.serialize(&mut &mut liquid_stake_data_account_info.data.borrow_mut()[..])?;
+    }
+
     ingl_vote_account_data.vote_rewards.push(VoteRewards {
         validation_phrase: VOTE_REWARDS_VAL_PHRASE,
         epoch_number: Clock::get()?.epoch,
-        total_stake: ingl_vote_account_data.total_delegated,
+        total_stake: ingl_vote_account_data.active_stake,
         total_reward: lamports,
     });
+    if rarity_bonus_pool > 0 {
+        ingl_vote_account_data.rarity_bonuses.push(RarityBonus {
+            epoch_number: Clock::get()?.epoch,
+            pool: rarity_bonus_pool,
+            total_stake: ingl_vote_account_data.active_stake - ingl_vote_account_data.liquid_active_stake,
+            bonus_bps: protocol_config.rarity_reward_bonus_bps,
+            claimed: 0,
+        });
+    }
     ingl_vote_account_data.last_withdraw_epoch = Clock::get()?.epoch;
 
     ingl_vote_account_data
         .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    InglEvent::RewardsProcessed {
+        vote_account: *vote_account_info.key,
+        epoch: Clock::get()?.epoch,
+        total_reward: lamports,
+        total_stake: ingl_vote_account_data.active_stake,
+    }
+    .emit();
     Ok(())
 }
 
-pub fn nft_withdraw(_program_id: &Pubkey, accounts: &[AccountInfo], cnt: usize) -> ProgramResult {
+pub fn nft_withdraw(program_id: &Pubkey, accounts: &[AccountInfo], cnt: usize) -> ProgramResult {
     // msg!("cnt: {:?}, Accounts: {:?}",cnt,  accounts);
     let account_info_iter = &mut accounts.iter();
     let payer_account_info = next_account_info(account_info_iter)?;
@@ -1974,10 +2395,14 @@ pub fn nft_withdraw(_program_id: &Pubkey, accounts: &[AccountInfo], cnt: usize)
     let validator_info = next_account_info(account_info_iter)?;
     let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
     let authorized_withdrawer_info = next_account_info(account_info_iter)?;
+    let sysvar_stake_history_info = next_account_info(account_info_iter)?;
+
+    assert_pubkeys_exactitude(sysvar_stake_history_info.key, &sysvar::stake_history::id())?;
+    let stake_history = StakeHistory::from_account_info(sysvar_stake_history_info)?;
 
     let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(),vote_account_info.key.as_ref(),],ingl_vote_data_account_info);
     assert_program_owned(ingl_vote_data_account_info)?;
-    let ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+    let mut ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
 
     let (_authorized_withdrawer, authorized_withdrawer_bump) = assert_pda_input(&[AUTHORIZED_WITHDRAWER_KEY.as_ref()], authorized_withdrawer_info);
 
@@ -1986,7 +2411,17 @@ pub fn nft_withdraw(_program_id: &Pubkey, accounts: &[AccountInfo], cnt: usize)
 
     assert_is_signer(payer_account_info)
         .expect("Payer must be Signer, couldn't find its signature");
+
+    // Compounding follows the gem's current holder. A holder without a compound reserve, for instance after an SPL transfer
+    // of a compounding gem, is paid directly and the gem stops compounding until its holder opts in again.
+    // The reserve is only credited once it checks out as the payer's own, so any other trailing account is ignored.
+    let compound_reserve_account_info = accounts
+        .get(6 + 3 * cnt)
+        .filter(|account| is_compound_reserve_of(program_id, account, payer_account_info.key));
+    let holder_compounds = compound_reserve_account_info.is_some();
+
     let mut general_rewards: u64 = 0;
+    let mut compounded_rewards: u64 = 0;
     for _ in 0..cnt {
         let associated_token_account_info = next_account_info(account_info_iter)?;
         let mint_account_info = next_account_info(account_info_iter)?;
@@ -2019,25 +2454,18 @@ pub fn nft_withdraw(_program_id: &Pubkey, accounts: &[AccountInfo], cnt: usize)
         }
         
         
-        let interested_epoch = if let Some(_) = gem_account_data.last_withdrawal_epoch {
-            gem_account_data.last_withdrawal_epoch.unwrap().max(gem_account_data.last_delegation_epoch.unwrap())
+        let total_reward = settle_gem_rewards(&mut gem_account_data, &mut ingl_vote_account_data, &stake_history);
+        if gem_account_data.compound_rewards && !holder_compounds {
+            gem_account_data.compound_rewards = false;
         }
-        else {
-            gem_account_data.last_delegation_epoch.unwrap()
-        };
-        let interested_index =1 + ingl_vote_account_data.vote_rewards.iter().position(|x| x.epoch_number == interested_epoch).expect("couldn't find the last withdrawal epoch. One Can only undelegate the epoch after that which they delegated.");
-        let mut total_reward: u64 = 0;
-        for i in interested_index..ingl_vote_account_data.vote_rewards.len() {
-            let epoch_reward = ingl_vote_account_data.vote_rewards[i];
-            // msg!("epoch_reward: {:?}", epoch_reward);
-            total_reward = total_reward.checked_add((gem_account_data.class.get_class_lamports() as f64 * NFTS_SHARE as f64 * epoch_reward.total_reward as f64 / (100.0 * epoch_reward.total_stake as f64))as u64).unwrap(); //unsafe Get back to this Cyrial
+        if gem_account_data.compound_rewards {
+            compounded_rewards = compounded_rewards.checked_add(total_reward).unwrap();
+        } else {
+            general_rewards = general_rewards.checked_add(total_reward).unwrap();
         }
-        gem_account_data.last_withdrawal_epoch = Some(Clock::get()?.epoch);
-        gem_account_data.all_withdraws.push(total_reward);
-        general_rewards = general_rewards.checked_add(total_reward).unwrap();
         gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+        InglEvent::RewardsClaimed { mint: *mint_account_info.key, owner: *payer_account_info.key, lamports: total_reward }.emit();
     }
-    msg!("right before Invocation");
     invoke_signed(
         &system_instruction::transfer(
             authorized_withdrawer_info.key,
@@ -2054,6 +2482,72 @@ pub fn nft_withdraw(_program_id: &Pubkey, accounts: &[AccountInfo], cnt: usize)
         ]],
     )?;
 
+    if let Some(compound_reserve_account_info) = compound_reserve_account_info.filter(|_| compounded_rewards > 0) {
+        credit_compound_reserve(
+            payer_account_info,
+            compound_reserve_account_info,
+            authorized_withdrawer_info,
+            authorized_withdrawer_bump,
+            compounded_rewards,
+        )?;
+    }
+    ingl_vote_account_data.serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Computes a delegated gem's unclaimed rewards and marks them as withdrawn, counting its rarity bonuses against their pools.
+fn settle_gem_rewards(
+    gem_account_data: &mut GemAccountV0_0_1,
+    ingl_vote_account_data: &mut InglVoteAccountData,
+    stake_history: &StakeHistory,
+) -> u64 {
+    let total_reward = rewards::gem_claimable_rewards(gem_account_data, ingl_vote_account_data, stake_history);
+    rewards::record_rarity_bonus_claims(gem_account_data, ingl_vote_account_data, stake_history);
+    if let Some(last_rewards) = ingl_vote_account_data.vote_rewards.last() {
+        gem_account_data.last_withdrawal_epoch = Some(last_rewards.epoch_number);
+    }
+    gem_account_data.all_withdraws.push(total_reward);
+    total_reward
+}
+
+/// Whether `account` is `owner`'s compound reserve: their reserve PDA, held by the program and recording them as its owner.
+fn is_compound_reserve_of(program_id: &Pubkey, account: &AccountInfo, owner: &Pubkey) -> bool {
+    let (compound_reserve_key, _compound_reserve_bump) = Pubkey::find_program_address(&[COMPOUND_RESERVE_KEY.as_ref(), owner.as_ref()], program_id);
+    account.key == &compound_reserve_key
+        && account.owner == program_id
+        && CompoundReserve::decode(account).is_ok_and(|compound_reserve| &compound_reserve.owner == owner)
+}
+
+/// Moves `amount` of rewards from the authorized withdrawer into `owner`'s compound reserve.
+fn credit_compound_reserve<'a>(
+    owner_account_info: &AccountInfo<'a>,
+    compound_reserve_account_info: &AccountInfo<'a>,
+    authorized_withdrawer_info: &AccountInfo<'a>,
+    authorized_withdrawer_bump: u8,
+    amount: u64,
+) -> ProgramResult {
+    let (_compound_reserve_key, _compound_reserve_bump) = assert_pda_input(&[COMPOUND_RESERVE_KEY.as_ref(), owner_account_info.key.as_ref()], compound_reserve_account_info);
+    let mut compound_reserve_data = CompoundReserve::decode(compound_reserve_account_info)?;
+    assert_pubkeys_exactitude(&compound_reserve_data.owner, owner_account_info.key).expect("Error: @compound reserve owner");
+
+    invoke_signed(
+        &system_instruction::transfer(
+            authorized_withdrawer_info.key,
+            compound_reserve_account_info.key,
+            amount,
+        ),
+        &[
+            authorized_withdrawer_info.clone(),
+            compound_reserve_account_info.clone(),
+        ],
+        &[&[
+            AUTHORIZED_WITHDRAWER_KEY.as_ref(),
+            &[authorized_withdrawer_bump],
+        ]],
+    )?;
+    compound_reserve_data.balance = compound_reserve_data.balance.checked_add(amount).unwrap();
+    compound_reserve_data.serialize(&mut &mut compound_reserve_account_info.data.borrow_mut()[..])?;
     Ok(())
 }
 
@@ -2297,6 +2791,7 @@ pub fn init_rebalance(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Program
     ingl_vote_account_data
         .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
     global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+    InglEvent::RebalanceStarted { vote_account: *vote_account_info.key, epoch: Clock::get()?.epoch }.emit();
     Ok(())
 }
 
@@ -2394,6 +2889,12 @@ pub fn finalize_rebalance(_program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
     ingl_vote_account_data.rebalancing_data.is_rebalancing_active = false;
     ingl_vote_account_data
         .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    InglEvent::RebalanceFinalized {
+        vote_account: *vote_account_info.key,
+        epoch: Clock::get()?.epoch,
+        total_staked: ingl_vote_account_data.last_total_staked,
+    }
+    .emit();
     Ok(())
 }
 
@@ -2439,4 +2940,2814 @@ pub fn inject_testing_data(_program_id: &Pubkey, accounts: &[AccountInfo], num_m
     ingl_vote_account_data.serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
 
     Ok(())
-}
\ No newline at end of file
+}
+pub fn init_liquid_staking(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let liquid_mint_account_info = next_account_info(account_info_iter)?;
+    let liquid_mint_authority_account_info = next_account_info(account_info_iter)?;
+    let liquid_stake_data_account_info = next_account_info(account_info_iter)?;
+    let sysvar_rent_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+    let liquid_reserve_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+    assert_pubkeys_exactitude(sysvar_rent_account_info.key, &sysvar::rent::id())?;
+
+    let (liquid_mint_key, liquid_mint_bump) = assert_pda_input(&[LIQUID_MINT_KEY.as_ref()], liquid_mint_account_info);
+    let (liquid_mint_authority_key, _liquid_mint_authority_bump) = assert_pda_input(&[LIQUID_MINT_AUTHORITY_KEY.as_ref()], liquid_mint_authority_account_info);
+    let (liquid_stake_data_key, liquid_stake_data_bump) = assert_pda_input(&[LIQUID_STAKE_DATA_KEY.as_ref()], liquid_stake_data_account_info);
+    let (liquid_reserve_key, _liquid_reserve_bump) = assert_pda_input(&[LIQUID_RESERVE_KEY.as_ref()], liquid_reserve_account_info);
+
+    let space = 82;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account_info.key,
+            &liquid_mint_key,
+            rent_lamports,
+            space as u64,
+            spl_token_program_account_info.key,
+        ),
+        &[payer_account_info.clone(), liquid_mint_account_info.clone()],
+        &[&[LIQUID_MINT_KEY.as_ref(), &[liquid_mint_bump]]],
+    )?;
+
+    invoke(
+        &spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &liquid_mint_key,
+            &liquid_mint_authority_key,
+            None,
+            LIQUID_MINT_DECIMALS,
+        )?,
+        &[liquid_mint_account_info.clone(), sysvar_rent_account_info.clone()],
+    )?;
+
+    let space = 100;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account_info.key,
+            &liquid_stake_data_key,
+            rent_lamports,
+            space as u64,
+            program_id,
+        ),
+        &[payer_account_info.clone(), liquid_stake_data_account_info.clone()],
+        &[&[LIQUID_STAKE_DATA_KEY.as_ref(), &[liquid_stake_data_bump]]],
+    )?;
+
+    let liquid_stake_data = LiquidStakeData {
+        validation_phrase: LIQUID_STAKE_DATA_VAL_PHRASE,
+        total_gem_lamports: 0,
+        accrued_rewards: 0,
+        total_supply: 0,
+        auto_delegate: false,
+    };
+    liquid_stake_data.serialize(&mut &mut liquid_stake_data_account_info.data.borrow_mut()[..])?;
+
+    // The reserve is kept rent exempt outside of accrued_rewards, so any amount of rewards can be credited to it or redeemed from it.
+    let reserve_rent = Rent::get()?.minimum_balance(0).saturating_sub(liquid_reserve_account_info.lamports());
+    if reserve_rent > 0 {
+        invoke(
+            &system_instruction::transfer(payer_account_info.key, &liquid_reserve_key, reserve_rent),
+            &[payer_account_info.clone(), liquid_reserve_account_info.clone()],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Burns liquid tokens against the rewards accrued by the liquid reserve, paying `lamports` to the holder at the current exchange rate.
+pub fn redeem_liquid_rewards(_program_id: &Pubkey, accounts: &[AccountInfo], lamports: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let liquid_mint_account_info = next_account_info(account_info_iter)?;
+    let liquid_associated_token_account_info = next_account_info(account_info_iter)?;
+    let liquid_stake_data_account_info = next_account_info(account_info_iter)?;
+    let liquid_reserve_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_owned_by(liquid_associated_token_account_info, &spl_program::id())?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let (liquid_mint_key, _liquid_mint_bump) = assert_pda_input(&[LIQUID_MINT_KEY.as_ref()], liquid_mint_account_info);
+    let (_liquid_stake_data_key, _liquid_stake_data_bump) = assert_pda_input(&[LIQUID_STAKE_DATA_KEY.as_ref()], liquid_stake_data_account_info);
+    let (liquid_reserve_key, liquid_reserve_bump) = assert_pda_input(&[LIQUID_RESERVE_KEY.as_ref()], liquid_reserve_account_info);
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, &liquid_mint_key),
+        liquid_associated_token_account_info.key,
+    )
+    .expect("Error: @liquid_associated_token_address");
+
+    let mut liquid_stake_data = LiquidStakeData::decode(liquid_stake_data_account_info)?;
+    if lamports == 0 || lamports > liquid_stake_data.accrued_rewards {
+        Err(InglError::BeyondBounds.utilize(Some("liquid rewards to redeem")))?
+    }
+    let liquid_tokens = liquid_stake_data.tokens_to_burn(lamports);
+
+    invoke(
+        &spl_token::instruction::burn(
+            &spl_token::id(),
+            liquid_associated_token_account_info.key,
+            liquid_mint_account_info.key,
+            payer_account_info.key,
+            &[],
+            liquid_tokens,
+        )?,
+        &[
+            liquid_associated_token_account_info.clone(),
+            liquid_mint_account_info.clone(),
+            payer_account_info.clone(),
+        ],
+    )?;
+    invoke_signed(
+        &system_instruction::transfer(&liquid_reserve_key, payer_account_info.key, lamports),
+        &[liquid_reserve_account_info.clone(), payer_account_info.clone()],
+        &[&[LIQUID_RESERVE_KEY.as_ref(), &[liquid_reserve_bump]]],
+    )?;
+
+    liquid_stake_data.accrued_rewards = liquid_stake_data.accrued_rewards.checked_sub(lamports).unwrap();
+    liquid_stake_data.total_supply = liquid_stake_data.total_supply.checked_sub(liquid_tokens).unwrap();
+    liquid_stake_data.serialize(&mut &mut liquid_stake_data_account_info.data.borrow_mut()[..])?;
+    InglEvent::LiquidRewardsRedeemed { owner: *payer_account_info.key, lamports, liquid_tokens }.emit();
+
+    Ok(())
+}
+
+pub fn liquify_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let escrow_associated_token_account_info = next_account_info(account_info_iter)?;
+    let liquid_mint_authority_account_info = next_account_info(account_info_iter)?;
+    let liquid_mint_account_info = next_account_info(account_info_iter)?;
+    let liquid_associated_token_account_info = next_account_info(account_info_iter)?;
+    let liquid_stake_data_account_info = next_account_info(account_info_iter)?;
+    let liquid_reserve_account_info = next_account_info(account_info_iter)?;
+    let treasury_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_data_info)?;
+    assert_owned_by(mint_account_info, &spl_program::id())?;
+    assert_owned_by(associated_token_account_info, &spl_program::id())?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    let (liquid_mint_authority_key, liquid_mint_authority_bump) = assert_pda_input(&[LIQUID_MINT_AUTHORITY_KEY.as_ref()], liquid_mint_authority_account_info);
+    let (liquid_mint_key, _liquid_mint_bump) = assert_pda_input(&[LIQUID_MINT_KEY.as_ref()], liquid_mint_account_info);
+    let (_liquid_stake_data_key, _liquid_stake_data_bump) = assert_pda_input(&[LIQUID_STAKE_DATA_KEY.as_ref()], liquid_stake_data_account_info);
+    let (liquid_reserve_key, liquid_reserve_bump) = assert_pda_input(&[LIQUID_RESERVE_KEY.as_ref()], liquid_reserve_account_info);
+    let (_treasury_key, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], treasury_account_info);
+
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&liquid_mint_authority_key, mint_account_info.key),
+        escrow_associated_token_account_info.key,
+    )
+    .expect("Error: @escrow_associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, &liquid_mint_key),
+        liquid_associated_token_account_info.key,
+    )
+    .expect("Error: @liquid_associated_token_address");
+
+    let associated_token_address_data =
+        Account::unpack(&associated_token_account_info.data.borrow())?;
+    if associated_token_address_data.amount != 1 {
+        Err(ProgramError::InsufficientFunds)?
+    }
+
+    let mut gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+    let class_lamports = gem_account_data.class.get_class_lamports();
+
+    match gem_account_data.funds_location {
+        FundsLocation::PDPool => {}
+        FundsLocation::VoteAccount { vote_account_id } => {
+            let vote_account_info = next_account_info(account_info_iter)?;
+            let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+            assert_pubkeys_exactitude(&vote_account_id, vote_account_info.key)
+                .expect("Error: @vote_account_info in funds location");
+            let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+            let mut ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+
+            // Rewards earned before liquifying belong to the holder, and would otherwise become unclaimable.
+            if let Some(last_rewards) = ingl_vote_account_data.vote_rewards.last() {
+                let last_claimed_epoch = gem_account_data
+                    .last_withdrawal_epoch
+                    .unwrap_or(0)
+                    .max(gem_account_data.last_delegation_epoch.unwrap());
+                if last_claimed_epoch < last_rewards.epoch_number {
+                    Err(InglError::TooEarly.utilize(Some("Claim the gem's pending rewards before liquifying")))?
+                }
+            }
+            ingl_vote_account_data.liquid_delegated = ingl_vote_account_data
+                .liquid_delegated
+                .checked_add(class_lamports)
+                .unwrap();
+            let delegation_epoch = gem_account_data.last_delegation_epoch.unwrap();
+            if ingl_vote_account_data.is_stake_active(delegation_epoch) {
+                ingl_vote_account_data.liquid_active_stake = ingl_vote_account_data
+                    .liquid_active_stake
+                    .checked_add(class_lamports)
+                    .unwrap();
+            } else {
+                ingl_vote_account_data.remove_pending_activation(delegation_epoch, class_lamports, 0);
+                ingl_vote_account_data.add_pending_activation(delegation_epoch, class_lamports, class_lamports);
+            }
+            ingl_vote_account_data
+                .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+        }
+        _ => Err(InglError::InvalidFundsLocation.utilize(Some("gem's funds location.")))?,
+    }
+
+    if escrow_associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                &liquid_mint_authority_key,
+                mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                escrow_associated_token_account_info.clone(),
+                liquid_mint_authority_account_info.clone(),
+                mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    invoke(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            associated_token_account_info.key,
+            escrow_associated_token_account_info.key,
+            payer_account_info.key,
+            &[],
+            1,
+        )?,
+        &[
+            associated_token_account_info.clone(),
+            escrow_associated_token_account_info.clone(),
+            payer_account_info.clone(),
+        ],
+    )?;
+
+    let mut liquid_stake_data = LiquidStakeData::decode(liquid_stake_data_account_info)?;
+    let orphaned_rewards = liquid_stake_data.orphaned_rewards();
+    if orphaned_rewards > 0 {
+        invoke_signed(
+            &system_instruction::transfer(&liquid_reserve_key, treasury_account_info.key, orphaned_rewards),
+            &[liquid_reserve_account_info.clone(), treasury_account_info.clone()],
+            &[&[LIQUID_RESERVE_KEY.as_ref(), &[liquid_reserve_bump]]],
+        )?;
+        liquid_stake_data.accrued_rewards = 0;
+    }
+    let liquid_tokens = liquid_stake_data.lamports_to_tokens(class_lamports);
+
+    if liquid_associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                payer_account_info.key,
+                liquid_mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                liquid_associated_token_account_info.clone(),
+                payer_account_info.clone(),
+                liquid_mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            &spl_token::id(),
+            liquid_mint_account_info.key,
+            liquid_associated_token_account_info.key,
+            &liquid_mint_authority_key,
+            &[],
+            liquid_tokens,
+        )?,
+        &[
+            liquid_mint_account_info.clone(),
+            liquid_associated_token_account_info.clone(),
+            liquid_mint_authority_account_info.clone(),
+        ],
+        &[&[LIQUID_MINT_AUTHORITY_KEY.as_ref(), &[liquid_mint_authority_bump]]],
+    )?;
+
+    liquid_stake_data.total_gem_lamports = liquid_stake_data.total_gem_lamports.checked_add(class_lamports).unwrap();
+    liquid_stake_data.total_supply = liquid_stake_data.total_supply.checked_add(liquid_tokens).unwrap();
+    gem_account_data.is_liquid = true;
+
+    liquid_stake_data.serialize(&mut &mut liquid_stake_data_account_info.data.borrow_mut()[..])?;
+    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+    InglEvent::Liquified { mint: *mint_account_info.key, owner: *payer_account_info.key, lamports: class_lamports, liquid_tokens }.emit();
+
+    Ok(())
+}
+
+pub fn unliquify_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let escrow_associated_token_account_info = next_account_info(account_info_iter)?;
+    let liquid_mint_authority_account_info = next_account_info(account_info_iter)?;
+    let liquid_mint_account_info = next_account_info(account_info_iter)?;
+    let liquid_associated_token_account_info = next_account_info(account_info_iter)?;
+    let liquid_stake_data_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_data_info)?;
+    assert_owned_by(mint_account_info, &spl_program::id())?;
+    assert_owned_by(escrow_associated_token_account_info, &spl_program::id())?;
+    assert_owned_by(liquid_associated_token_account_info, &spl_program::id())?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    let (liquid_mint_authority_key, liquid_mint_authority_bump) = assert_pda_input(&[LIQUID_MINT_AUTHORITY_KEY.as_ref()], liquid_mint_authority_account_info);
+    let (liquid_mint_key, _liquid_mint_bump) = assert_pda_input(&[LIQUID_MINT_KEY.as_ref()], liquid_mint_account_info);
+    let (_liquid_stake_data_key, _liquid_stake_data_bump) = assert_pda_input(&[LIQUID_STAKE_DATA_KEY.as_ref()], liquid_stake_data_account_info);
+
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&liquid_mint_authority_key, mint_account_info.key),
+        escrow_associated_token_account_info.key,
+    )
+    .expect("Error: @escrow_associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, &liquid_mint_key),
+        liquid_associated_token_account_info.key,
+    )
+    .expect("Error: @liquid_associated_token_address");
+
+    let escrow_token_account_data =
+        Account::unpack(&escrow_associated_token_account_info.data.borrow())?;
+    if escrow_token_account_data.amount != 1 {
+        Err(ProgramError::InsufficientFunds)?
+    }
+
+    let mut gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+    if !gem_account_data.is_liquid {
+        Err(InglError::InvalidFundsLocation.utilize(Some("gem is not liquid")))?
+    }
+    let class_lamports = gem_account_data.class.get_class_lamports();
+
+    if let FundsLocation::VoteAccount { vote_account_id } = gem_account_data.funds_location {
+        let vote_account_info = next_account_info(account_info_iter)?;
+        let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+        assert_pubkeys_exactitude(&vote_account_id, vote_account_info.key)
+            .expect("Error: @vote_account_info in funds location");
+        let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+        let mut ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+
+        // Rewards processed while the gem was liquid were paid to the liquid reserve.
+        if let Some(last_rewards) = ingl_vote_account_data.vote_rewards.last() {
+            gem_account_data.last_withdrawal_epoch = Some(last_rewards.epoch_number);
+        }
+        ingl_vote_account_data.liquid_delegated = ingl_vote_account_data
+            .liquid_delegated
+            .checked_sub(class_lamports)
+            .unwrap();
+        let delegation_epoch = gem_account_data.last_delegation_epoch.unwrap();
+        if ingl_vote_account_data.is_stake_active(delegation_epoch) {
+            ingl_vote_account_data.liquid_active_stake = ingl_vote_account_data
+                .liquid_active_stake
+                .saturating_sub(class_lamports);
+        } else {
+            ingl_vote_account_data.remove_pending_activation(delegation_epoch, class_lamports, class_lamports);
+            ingl_vote_account_data.add_pending_activation(delegation_epoch, class_lamports, 0);
+        }
+        ingl_vote_account_data
+            .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    }
+
+    let mut liquid_stake_data = LiquidStakeData::decode(liquid_stake_data_account_info)?;
+    let liquid_tokens = liquid_stake_data.tokens_to_burn(class_lamports);
+
+    invoke(
+        &spl_token::instruction::burn(
+            &spl_token::id(),
+            liquid_associated_token_account_info.key,
+            liquid_mint_account_info.key,
+            payer_account_info.key,
+            &[],
+            liquid_tokens,
+        )?,
+        &[
+            liquid_associated_token_account_info.clone(),
+            liquid_mint_account_info.clone(),
+            payer_account_info.clone(),
+        ],
+    )?;
+
+    if associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                payer_account_info.key,
+                mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                associated_token_account_info.clone(),
+                payer_account_info.clone(),
+                mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            escrow_associated_token_account_info.key,
+            associated_token_account_info.key,
+            &liquid_mint_authority_key,
+            &[],
+            1,
+        )?,
+        &[
+            escrow_associated_token_account_info.clone(),
+            associated_token_account_info.clone(),
+            liquid_mint_authority_account_info.clone(),
+        ],
+        &[&[LIQUID_MINT_AUTHORITY_KEY.as_ref(), &[liquid_mint_authority_bump]]],
+    )?;
+    invoke_signed(
+        &spl_token::instruction::close_account(
+            &spl_token::id(),
+            escrow_associated_token_account_info.key,
+            payer_account_info.key,
+            &liquid_mint_authority_key,
+            &[],
+        )?,
+        &[
+            escrow_associated_token_account_info.clone(),
+            payer_account_info.clone(),
+            liquid_mint_authority_account_info.clone(),
+        ],
+        &[&[LIQUID_MINT_AUTHORITY_KEY.as_ref(), &[liquid_mint_authority_bump]]],
+    )?;
+
+    liquid_stake_data.total_gem_lamports = liquid_stake_data.total_gem_lamports.checked_sub(class_lamports).unwrap();
+    liquid_stake_data.total_supply = liquid_stake_data.total_supply.checked_sub(liquid_tokens).unwrap();
+    gem_account_data.is_liquid = false;
+
+    liquid_stake_data.serialize(&mut &mut liquid_stake_data_account_info.data.borrow_mut()[..])?;
+    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+    InglEvent::Unliquified { mint: *mint_account_info.key, owner: *payer_account_info.key, lamports: class_lamports, liquid_tokens }.emit();
+
+    Ok(())
+}
+
+pub fn set_auto_delegate(_program_id: &Pubkey, accounts: &[AccountInfo], auto_delegate: bool) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_data_info)?;
+    assert_owned_by(mint_account_info, &spl_program::id())?;
+    assert_owned_by(associated_token_account_info, &spl_program::id())?;
+
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    let associated_token_address_data =
+        Account::unpack(&associated_token_account_info.data.borrow())?;
+    if associated_token_address_data.amount != 1 {
+        Err(ProgramError::InsufficientFunds)?
+    }
+
+    let mut gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+    gem_account_data.auto_delegate = auto_delegate;
+    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Lets the auto delegation crank delegate liquid gems, whose escrowed holders can't delegate them or opt in themselves.
+pub fn set_liquid_auto_delegate(_program_id: &Pubkey, accounts: &[AccountInfo], auto_delegate: bool) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let liquid_stake_data_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_liquid_stake_data_key, _liquid_stake_data_bump) = assert_pda_input(&[LIQUID_STAKE_DATA_KEY.as_ref()], liquid_stake_data_account_info);
+
+    let mut liquid_stake_data = LiquidStakeData::decode(liquid_stake_data_account_info)?;
+    liquid_stake_data.auto_delegate = auto_delegate;
+    liquid_stake_data.serialize(&mut &mut liquid_stake_data_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Permissionless crank delegating opted in PD pool gems, each to the sent vote account with the most remaining capacity.
+/// Liquid gems are only eligible while the config authority has enabled their auto delegation.
+pub fn auto_delegate_nfts(_program_id: &Pubkey, accounts: &[AccountInfo], num_vote_accounts: u8, num_nfts: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let _payer_account_info = next_account_info(account_info_iter)?;
+    let global_gem_account_info = next_account_info(account_info_iter)?;
+    let liquid_stake_data_account_info = next_account_info(account_info_iter)?;
+    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+    assert_program_owned(global_gem_account_info)?;
+    let mut global_gem_account_data = GlobalGems::decode(global_gem_account_info)?;
+    let (_liquid_stake_data_key, _liquid_stake_data_bump) = assert_pda_input(&[LIQUID_STAKE_DATA_KEY.as_ref()], liquid_stake_data_account_info);
+    let liquid_stake_data = LiquidStakeData::decode(liquid_stake_data_account_info)?;
+
+    let mut vote_accounts: Vec<(&Pubkey, &AccountInfo, InglVoteAccountData)> = Vec::new();
+    for _ in 0..num_vote_accounts {
+        let vote_account_info = next_account_info(account_info_iter)?;
+        let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+
+        let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+        if vote_accounts.iter().any(|(key, _, _)| *key == vote_account_info.key) {
+            Err(InglError::AddressMismatch.utilize(Some("duplicate vote account")))?
+        }
+        let ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+        vote_accounts.push((vote_account_info.key, ingl_vote_data_account_info, ingl_vote_account_data));
+    }
+
+    for _ in 0..num_nfts {
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let gem_account_data_info = next_account_info(account_info_iter)?;
+        let metadata_account_info = next_account_info(account_info_iter)?;
+
+        let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+        assert_program_owned(gem_account_data_info)?;
+        assert_owned_by(mint_account_info, &spl_program::id())?;
+
+        let mut gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+            GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+        )?;
+        let opted_in = gem_account_data.auto_delegate || (gem_account_data.is_liquid && liquid_stake_data.auto_delegate);
+        if !opted_in {
+            Err(InglError::InvalidFundsLocation.utilize(Some("gem did not opt in to auto delegation")))?
+        }
+
+        let class_lamports = gem_account_data.class.get_class_lamports();
+        let chosen_index = auto_delegation_target(vote_accounts.iter().map(|(_, _, data)| data), class_lamports);
+
+        let (vote_account_key, _, ingl_vote_account_data) = match chosen_index {
+            Some(index) => &mut vote_accounts[index],
+            None => Err(InglError::BeyondBounds.utilize(Some("No vote account can take this gem's stake")))?,
+        };
+        delegate_gem(
+            &mut gem_account_data,
+            ingl_vote_account_data,
+            &mut global_gem_account_data,
+            vote_account_key,
+        )?;
+        gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+        InglEvent::Delegated { mint: *mint_account_info.key, vote_account: **vote_account_key, lamports: class_lamports }.emit();
+        sync_gem_metadata(
+            mint_account_info,
+            metadata_account_info,
+            mint_authority_account_info,
+            metadata_registry_account_info,
+            &gem_account_data,
+        )?;
+    }
+
+    for (_, ingl_vote_data_account_info, ingl_vote_account_data) in vote_accounts.iter() {
+        ingl_vote_account_data
+            .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    }
+    global_gem_account_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Delegates the pooled stake account to its vote account, once after creation and again whenever it has been deactivated.
+/// Also delegates an initialized temporary stake account, so that finalize_rebalance can merge it once active.
+pub fn delegate_vote_stake(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let _payer_account_info = next_account_info(account_info_iter)?;
+    let vote_account_info = next_account_info(account_info_iter)?;
+    let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+    let stake_account_info = next_account_info(account_info_iter)?;
+    let t_stake_account_info = next_account_info(account_info_iter)?;
+    let pd_pool_account_info = next_account_info(account_info_iter)?;
+    let sysvar_clock_info = next_account_info(account_info_iter)?;
+    let sysvar_stake_history_info = next_account_info(account_info_iter)?;
+    let sysvar_stake_config_info = next_account_info(account_info_iter)?;
+
+    assert_pubkeys_exactitude(sysvar_clock_info.key, &sysvar::clock::id())?;
+    assert_pubkeys_exactitude(sysvar_stake_history_info.key, &sysvar::stake_history::id())?;
+    assert_pubkeys_exactitude(
+        sysvar_stake_config_info.key,
+        &solana_program::stake::config::id(),
+    )?;
+
+    let (pd_pool_pubkey, pd_pool_bump) = assert_pda_input(&[PD_POOL_KEY.as_ref()], pd_pool_account_info);
+    let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+    let (_expected_stake_key, _expected_stake_bump) = assert_pda_input(&[STAKE_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], stake_account_info);
+    let (_expected_t_stake_key, _expected_t_stake_bump) = assert_pda_input(&[T_STAKE_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], t_stake_account_info);
+    assert_owned_by(stake_account_info, &stake::program::id())?;
+
+    assert_program_owned(ingl_vote_data_account_info)?;
+    let mut ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+
+    let stake_needs_delegation = match try_from_slice_unchecked::<StakeState>(&stake_account_info.data.borrow())? {
+        StakeState::Initialized(_) => true,
+        StakeState::Stake(_, stake) => stake.delegation.deactivation_epoch != u64::MAX,
+        _ => Err(ProgramError::InvalidAccountData)?,
+    };
+    let t_stake_needs_delegation = ingl_vote_account_data.is_t_stake_initialized
+        && t_stake_account_info.owner == &stake::program::id()
+        && matches!(
+            try_from_slice_unchecked::<StakeState>(&t_stake_account_info.data.borrow())?,
+            StakeState::Initialized(_)
+        );
+
+    if !stake_needs_delegation && !t_stake_needs_delegation {
+        Err(InglError::TooLate.utilize(Some("Stake is already delegated to the vote account")))?
+    }
+
+    if stake_needs_delegation {
+        invoke_signed(
+            &solana_program::stake::instruction::delegate_stake(
+                stake_account_info.key,
+                &pd_pool_pubkey,
+                vote_account_info.key,
+            ),
+            &[
+                stake_account_info.clone(),
+                vote_account_info.clone(),
+                sysvar_clock_info.clone(),
+                sysvar_stake_history_info.clone(),
+                sysvar_stake_config_info.clone(),
+                pd_pool_account_info.clone(),
+            ],
+            &[&[PD_POOL_KEY.as_ref(), &[pd_pool_bump]]],
+        )?;
+        ingl_vote_account_data.stake_activation_epoch = Some(Clock::get()?.epoch);
+    }
+
+    if t_stake_needs_delegation {
+        invoke_signed(
+            &solana_program::stake::instruction::delegate_stake(
+                t_stake_account_info.key,
+                &pd_pool_pubkey,
+                vote_account_info.key,
+            ),
+            &[
+                t_stake_account_info.clone(),
+                vote_account_info.clone(),
+                sysvar_clock_info.clone(),
+                sysvar_stake_history_info.clone(),
+                sysvar_stake_config_info.clone(),
+                pd_pool_account_info.clone(),
+            ],
+            &[&[PD_POOL_KEY.as_ref(), &[pd_pool_bump]]],
+        )?;
+    }
+
+    ingl_vote_account_data
+        .serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+pub fn set_compounding(program_id: &Pubkey, accounts: &[AccountInfo], compound_rewards: bool) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let compound_reserve_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_data_info)?;
+    assert_owned_by(mint_account_info, &spl_program::id())?;
+    assert_owned_by(associated_token_account_info, &spl_program::id())?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    let (compound_reserve_key, compound_reserve_bump) = assert_pda_input(&[COMPOUND_RESERVE_KEY.as_ref(), payer_account_info.key.as_ref()], compound_reserve_account_info);
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    let associated_token_address_data =
+        Account::unpack(&associated_token_account_info.data.borrow())?;
+    if associated_token_address_data.amount != 1 {
+        Err(ProgramError::InsufficientFunds)?
+    }
+
+    if compound_rewards && compound_reserve_account_info.data_is_empty() {
+        let space = 48;
+        let rent_lamports = Rent::get()?.minimum_balance(space);
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_account_info.key,
+                &compound_reserve_key,
+                rent_lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer_account_info.clone(), compound_reserve_account_info.clone()],
+            &[&[COMPOUND_RESERVE_KEY.as_ref(), payer_account_info.key.as_ref(), &[compound_reserve_bump]]],
+        )?;
+
+        let compound_reserve_data = CompoundReserve {
+            validation_phrase: COMPOUND_RESERVE_VAL_PHRASE,
+            owner: *payer_account_info.key,
+            balance: 0,
+            gems_minted: 0,
+        };
+        compound_reserve_data.serialize(&mut &mut compound_reserve_account_info.data.borrow_mut()[..])?;
+    }
+
+    let mut gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+    gem_account_data.compound_rewards = compound_rewards;
+    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn withdraw_compound_reserve(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let compound_reserve_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    let (_compound_reserve_key, _compound_reserve_bump) = assert_pda_input(&[COMPOUND_RESERVE_KEY.as_ref(), payer_account_info.key.as_ref()], compound_reserve_account_info);
+    let mut compound_reserve_data = CompoundReserve::decode(compound_reserve_account_info)?;
+    assert_pubkeys_exactitude(&compound_reserve_data.owner, payer_account_info.key).expect("Error: @compound reserve owner");
+
+    let amount = compound_reserve_data.balance;
+    **compound_reserve_account_info.lamports.borrow_mut() = compound_reserve_account_info
+        .lamports()
+        .checked_sub(amount)
+        .unwrap();
+    **payer_account_info.lamports.borrow_mut() = payer_account_info
+        .lamports()
+        .checked_add(amount)
+        .unwrap();
+    compound_reserve_data.balance = 0;
+    compound_reserve_data.serialize(&mut &mut compound_reserve_account_info.data.borrow_mut()[..])?;
+    InglEvent::CompoundReserveWithdrawn { owner: *payer_account_info.key, lamports: amount }.emit();
+
+    Ok(())
+}
+
+/// Number of accounts `mint_gem` reads for a single gem, up to the protocol config. A compounded mint follows them with the compound reserve and its owner.
+pub const GEM_MINT_ACCOUNTS_LEN: usize = 17;
+/// Number of accounts read per burned gem in a class conversion: mint, associated token account, gem, metadata and edition.
+pub const GEM_BURN_ACCOUNTS_LEN: usize = 5;
+
+/// Burns gems of one class whose lamports add up to `class` and mints a single gem of `class` in their place.
+/// Accounts are those of `mint_nft` for the new gem, followed by `num_nfts` burn groups.
+pub fn merge_nfts(program_id: &Pubkey, accounts: &[AccountInfo], class: Class, num_nfts: u8) -> ProgramResult {
+    if accounts.len() < GEM_MINT_ACCOUNTS_LEN + GEM_BURN_ACCOUNTS_LEN * num_nfts as usize {
+        Err(ProgramError::NotEnoughAccountKeys)?
+    }
+    let (mint_accounts, burn_accounts) = accounts.split_at(GEM_MINT_ACCOUNTS_LEN);
+    let payer_account_info = &mint_accounts[0];
+    let spl_token_program_account_info = &mint_accounts[4];
+    let ingl_collection_account_info = &mint_accounts[14];
+
+    let mut template: Option<GemAccountV0_0_1> = None;
+    let mut burned = Vec::with_capacity(num_nfts as usize);
+    let mut merged_lamports: u64 = 0;
+    for group in burn_accounts.chunks(GEM_BURN_ACCOUNTS_LEN).take(num_nfts as usize) {
+        let gem_data = burn_converted_gem(program_id, payer_account_info, spl_token_program_account_info, ingl_collection_account_info, group)?;
+        merged_lamports = merged_lamports.checked_add(gem_data.class.get_class_lamports()).unwrap();
+        match template.as_mut() {
+            None => template = Some(gem_data.conversion_template()),
+            Some(template) => template.merge(&gem_data)?,
+        }
+        burned.push(gem_data);
+    }
+    record_burned_gems(&mint_accounts[9], &burned)?;
+
+    let template = template.ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if template.class.get_class_lamports() >= class.get_class_lamports() || merged_lamports != class.get_class_lamports() {
+        Err(InglError::InvalidConversion.utilize(Some("Merged gems must add up to exactly one gem of a higher class")))?
+    }
+
+    mint_gem(program_id, mint_accounts, class, GemFunding::Conversion(Box::new(template)))
+}
+
+/// Burns one gem and mints `num_nfts` gems of the lower `class` whose lamports add up to it.
+/// Accounts are the burn group of the split gem, followed by `num_nfts` sets of `mint_nft` accounts.
+pub fn split_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class, num_nfts: u8) -> ProgramResult {
+    if num_nfts < 2 || accounts.len() < GEM_BURN_ACCOUNTS_LEN + GEM_MINT_ACCOUNTS_LEN * num_nfts as usize {
+        Err(ProgramError::NotEnoughAccountKeys)?
+    }
+    let (burn_accounts, mint_accounts) = accounts.split_at(GEM_BURN_ACCOUNTS_LEN);
+    let payer_account_info = &mint_accounts[0];
+    let spl_token_program_account_info = &mint_accounts[4];
+    let ingl_collection_account_info = &mint_accounts[14];
+
+    let gem_data = burn_converted_gem(program_id, payer_account_info, spl_token_program_account_info, ingl_collection_account_info, burn_accounts)?;
+    if class.get_class_lamports().checked_mul(num_nfts as u64).unwrap() != gem_data.class.get_class_lamports() {
+        Err(InglError::InvalidConversion.utilize(Some("Split gems must add up to exactly the split gem's class")))?
+    }
+    record_burned_gems(&mint_accounts[9], std::slice::from_ref(&gem_data))?;
+
+    for new_gem_accounts in mint_accounts.chunks(GEM_MINT_ACCOUNTS_LEN).take(num_nfts as usize) {
+        assert_pubkeys_exactitude(new_gem_accounts[0].key, payer_account_info.key).expect("Error: @payer of split gem");
+        mint_gem(program_id, new_gem_accounts, class, GemFunding::Conversion(Box::new(gem_data.conversion_template())))?;
+    }
+
+    Ok(())
+}
+
+/// Removes burned gems from the per-class statistics. The gems minted in their place are added back by mint_gem.
+fn record_burned_gems(global_gem_account_info: &AccountInfo, burned: &[GemAccountV0_0_1]) -> ProgramResult {
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+    for gem_data in burned {
+        global_gem_data.remove_gem(gem_data.class, &gem_data.rarity);
+        global_gem_data.remove_class_funds(gem_data.class, &gem_data.funds_location);
+    }
+    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// Burns a gem's NFT held by the payer and closes its gem account, returning the gem's data.
+fn burn_converted_gem<'a>(
+    program_id: &Pubkey,
+    payer_account_info: &AccountInfo<'a>,
+    spl_token_program_account_info: &AccountInfo<'a>,
+    ingl_collection_account_info: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+) -> Result<GemAccountV0_0_1, ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let gem_account_info = next_account_info(account_info_iter)?;
+    let metadata_account_info = next_account_info(account_info_iter)?;
+    let edition_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_info)?;
+    assert_owned_by(mint_account_info, &spl_program::id())?;
+    assert_owned_by(associated_token_account_info, &spl_program::id())?;
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    let associated_token_account_data =
+        Account::unpack(&associated_token_account_info.data.borrow())?;
+    if associated_token_account_data.amount != 1 {
+        Err(ProgramError::InsufficientFunds)?
+    }
+
+    let (_gem_pubkey, _gem_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_info);
+    let gem_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(
+        &gem_account_info.data.borrow(),
+    )?)?;
+    if gem_data.is_liquid {
+        Err(InglError::InvalidConversion.utilize(Some("Liquid gems can't be converted")))?
+    }
+
+    let mpl_token_metadata_id = mpl_token_metadata::id();
+    let (edition_key, _edition_bump) = Pubkey::find_program_address(
+        &[
+            b"metadata",
+            mpl_token_metadata_id.as_ref(),
+            mint_account_info.key.as_ref(),
+            b"edition",
+        ],
+        &mpl_token_metadata_id,
+    );
+    assert_pubkeys_exactitude(&edition_key, edition_account_info.key)
+        .expect("Error: @edition_account_info");
+
+    let (nft_metadata_key, _nft_metadata_bump) = Pubkey::find_program_address(
+        &[
+            PREFIX.as_ref(),
+            mpl_token_metadata_id.as_ref(),
+            mint_account_info.key.as_ref(),
+        ],
+        &mpl_token_metadata_id,
+    );
+    assert_pubkeys_exactitude(&nft_metadata_key, metadata_account_info.key)
+        .expect("Error: @meta_data_account_info");
+
+    let (ingl_nft_collection_key, _ingl_nft_bump) = Pubkey::find_program_address(&[INGL_NFT_COLLECTION_KEY.as_ref()], program_id);
+    let (collection_metadata_key, _collection_metadata_bump) = Pubkey::find_program_address(
+        &[
+            PREFIX.as_ref(),
+            mpl_token_metadata_id.as_ref(),
+            ingl_nft_collection_key.as_ref(),
+        ],
+        &mpl_token_metadata_id,
+    );
+    assert_pubkeys_exactitude(&collection_metadata_key, ingl_collection_account_info.key)
+        .expect("Error: @collection_metadata_info");
+
+    invoke(
+        &mpl_token_metadata::instruction::burn_nft(
+            mpl_token_metadata_id,
+            nft_metadata_key,
+            *payer_account_info.key,
+            *mint_account_info.key,
+            *associated_token_account_info.key,
+            edition_key,
+            spl_token::id(),
+            Some(collection_metadata_key),
+        ),
+        &[
+            metadata_account_info.clone(),
+            payer_account_info.clone(),
+            mint_account_info.clone(),
+            associated_token_account_info.clone(),
+            edition_account_info.clone(),
+            spl_token_program_account_info.clone(),
+            ingl_collection_account_info.clone(),
+        ],
+    )?;
+
+    let dest_starting_lamports = payer_account_info.lamports();
+    **payer_account_info.lamports.borrow_mut() = dest_starting_lamports
+        .checked_add(gem_account_info.lamports())
+        .unwrap();
+    **gem_account_info.lamports.borrow_mut() = 0;
+    gem_account_info.data.borrow_mut().fill(0);
+    InglEvent::GemBurned { mint: *mint_account_info.key, owner: *payer_account_info.key, class: gem_data.class }.emit();
+
+    Ok(gem_data)
+}
+
+pub fn fractionalize_nft(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let escrow_associated_token_account_info = next_account_info(account_info_iter)?;
+    let share_mint_account_info = next_account_info(account_info_iter)?;
+    let share_associated_token_account_info = next_account_info(account_info_iter)?;
+    let sysvar_rent_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_data_info)?;
+    assert_owned_by(mint_account_info, &spl_program::id())?;
+    assert_owned_by(associated_token_account_info, &spl_program::id())?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+    assert_pubkeys_exactitude(sysvar_rent_account_info.key, &sysvar::rent::id())?;
+
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    let (vault_key, vault_bump) = assert_pda_input(&[FRACTIONAL_VAULT_KEY.as_ref(), mint_account_info.key.as_ref()], vault_account_info);
+    let (share_mint_key, share_mint_bump) = assert_pda_input(&[VAULT_SHARE_MINT_KEY.as_ref(), mint_account_info.key.as_ref()], share_mint_account_info);
+
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&vault_key, mint_account_info.key),
+        escrow_associated_token_account_info.key,
+    )
+    .expect("Error: @escrow_associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, &share_mint_key),
+        share_associated_token_account_info.key,
+    )
+    .expect("Error: @share_associated_token_address");
+
+    let associated_token_address_data =
+        Account::unpack(&associated_token_account_info.data.borrow())?;
+    if associated_token_address_data.amount != 1 {
+        Err(ProgramError::InsufficientFunds)?
+    }
+
+    let gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+    if let FundsLocation::VoteAccount { vote_account_id } = gem_account_data.funds_location {
+        let vote_account_info = next_account_info(account_info_iter)?;
+        let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+        assert_pubkeys_exactitude(&vote_account_id, vote_account_info.key)
+            .expect("Error: @vote_account_info in funds location");
+        let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+        let ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+
+        // Rewards earned before fractionalizing belong to the holder, not to the share holders.
+        if let Some(last_rewards) = ingl_vote_account_data.vote_rewards.last() {
+            let last_claimed_epoch = gem_account_data
+                .last_withdrawal_epoch
+                .unwrap_or(0)
+                .max(gem_account_data.last_delegation_epoch.unwrap());
+            if last_claimed_epoch < last_rewards.epoch_number {
+                Err(InglError::TooEarly.utilize(Some("Claim the gem's pending rewards before fractionalizing")))?
+            }
+        }
+    }
+
+    let space = 108;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account_info.key,
+            &vault_key,
+            rent_lamports,
+            space as u64,
+            program_id,
+        ),
+        &[payer_account_info.clone(), vault_account_info.clone()],
+        &[&[FRACTIONAL_VAULT_KEY.as_ref(), mint_account_info.key.as_ref(), &[vault_bump]]],
+    )?;
+
+    let space = 82;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account_info.key,
+            &share_mint_key,
+            rent_lamports,
+            space as u64,
+            spl_token_program_account_info.key,
+        ),
+        &[payer_account_info.clone(), share_mint_account_info.clone()],
+        &[&[VAULT_SHARE_MINT_KEY.as_ref(), mint_account_info.key.as_ref(), &[share_mint_bump]]],
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &share_mint_key,
+            &vault_key,
+            None,
+            VAULT_SHARE_DECIMALS,
+        )?,
+        &[share_mint_account_info.clone(), sysvar_rent_account_info.clone()],
+    )?;
+
+    if escrow_associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                &vault_key,
+                mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                escrow_associated_token_account_info.clone(),
+                vault_account_info.clone(),
+                mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    invoke(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            associated_token_account_info.key,
+            escrow_associated_token_account_info.key,
+            payer_account_info.key,
+            &[],
+            1,
+        )?,
+        &[
+            associated_token_account_info.clone(),
+            escrow_associated_token_account_info.clone(),
+            payer_account_info.clone(),
+        ],
+    )?;
+
+    if share_associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                payer_account_info.key,
+                share_mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                share_associated_token_account_info.clone(),
+                payer_account_info.clone(),
+                share_mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            &spl_token::id(),
+            share_mint_account_info.key,
+            share_associated_token_account_info.key,
+            &vault_key,
+            &[],
+            VAULT_SHARES_SUPPLY,
+        )?,
+        &[
+            share_mint_account_info.clone(),
+            share_associated_token_account_info.clone(),
+            vault_account_info.clone(),
+        ],
+        &[&[FRACTIONAL_VAULT_KEY.as_ref(), mint_account_info.key.as_ref(), &[vault_bump]]],
+    )?;
+
+    let vault_data = FractionalVault {
+        validation_phrase: FRACTIONAL_VAULT_VAL_PHRASE,
+        gem_mint: *mint_account_info.key,
+        share_mint: share_mint_key,
+        deposited_shares: 0,
+        rewards_per_share: 0,
+        undistributed_rewards: 0,
+    };
+    vault_data.serialize(&mut &mut vault_account_info.data.borrow_mut()[..])?;
+    InglEvent::Fractionalized {
+        mint: *mint_account_info.key,
+        owner: *payer_account_info.key,
+        vault: vault_key,
+        share_mint: share_mint_key,
+        shares: VAULT_SHARES_SUPPLY,
+    }
+    .emit();
+
+    Ok(())
+}
+
+/// Pays a share holder vault rewards held by the vault account.
+fn pay_vault_rewards(
+    vault_account_info: &AccountInfo,
+    owner_account_info: &AccountInfo,
+    pending: u64,
+) -> ProgramResult {
+    if pending > 0 {
+        **vault_account_info.lamports.borrow_mut() = vault_account_info
+            .lamports()
+            .checked_sub(pending)
+            .unwrap();
+        **owner_account_info.lamports.borrow_mut() = owner_account_info
+            .lamports()
+            .checked_add(pending)
+            .unwrap();
+        InglEvent::VaultRewardsClaimed { vault: *vault_account_info.key, owner: *owner_account_info.key, lamports: pending }.emit();
+    }
+    Ok(())
+}
+
+pub fn deposit_vault_shares(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let share_account_info = next_account_info(account_info_iter)?;
+    let share_associated_token_account_info = next_account_info(account_info_iter)?;
+    let share_escrow_account_info = next_account_info(account_info_iter)?;
+    let share_mint_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let mut vault_data = FractionalVault::decode(vault_account_info)?;
+    let (vault_key, _vault_bump) = assert_pda_input(&[FRACTIONAL_VAULT_KEY.as_ref(), vault_data.gem_mint.as_ref()], vault_account_info);
+    let (share_account_key, share_account_bump) = assert_pda_input(&[VAULT_SHARE_ACCOUNT_KEY.as_ref(), vault_key.as_ref(), payer_account_info.key.as_ref()], share_account_info);
+    assert_pubkeys_exactitude(&vault_data.share_mint, share_mint_account_info.key).expect("Error: @share_mint_account_info");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, &vault_data.share_mint),
+        share_associated_token_account_info.key,
+    )
+    .expect("Error: @share_associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&vault_key, &vault_data.share_mint),
+        share_escrow_account_info.key,
+    )
+    .expect("Error: @share_escrow_associated_token_address");
+
+    if share_account_info.data_is_empty() {
+        let space = 92;
+        let rent_lamports = Rent::get()?.minimum_balance(space);
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_account_info.key,
+                &share_account_key,
+                rent_lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer_account_info.clone(), share_account_info.clone()],
+            &[&[VAULT_SHARE_ACCOUNT_KEY.as_ref(), vault_key.as_ref(), payer_account_info.key.as_ref(), &[share_account_bump]]],
+        )?;
+        let share_account_data = VaultShareAccount {
+            validation_phrase: VAULT_SHARE_ACCOUNT_VAL_PHRASE,
+            vault: vault_key,
+            owner: *payer_account_info.key,
+            shares: 0,
+            reward_debt: 0,
+        };
+        share_account_data.serialize(&mut &mut share_account_info.data.borrow_mut()[..])?;
+    }
+    if share_escrow_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                &vault_key,
+                share_mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                share_escrow_account_info.clone(),
+                vault_account_info.clone(),
+                share_mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+
+    // Shares coming out of the wallet bring along their part of the rewards pooled for wallet-held shares.
+    let mut share_account_data = VaultShareAccount::decode(share_account_info)?;
+    let pending = share_account_data.pending_rewards(&vault_data).checked_add(vault_data.take_held_rewards(amount)).unwrap();
+    pay_vault_rewards(vault_account_info, payer_account_info, pending)?;
+
+    invoke(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            share_associated_token_account_info.key,
+            share_escrow_account_info.key,
+            payer_account_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            share_associated_token_account_info.clone(),
+            share_escrow_account_info.clone(),
+            payer_account_info.clone(),
+        ],
+    )?;
+
+    share_account_data.shares = share_account_data.shares.checked_add(amount).unwrap();
+    vault_data.deposited_shares = vault_data.deposited_shares.checked_add(amount).unwrap();
+    share_account_data.reward_debt = vault_data.accrued(share_account_data.shares);
+
+    vault_data.serialize(&mut &mut vault_account_info.data.borrow_mut()[..])?;
+    share_account_data.serialize(&mut &mut share_account_info.data.borrow_mut()[..])?;
+    InglEvent::VaultSharesDeposited { vault: vault_key, owner: *payer_account_info.key, shares: amount }.emit();
+
+    Ok(())
+}
+
+pub fn withdraw_vault_shares(_program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let share_account_info = next_account_info(account_info_iter)?;
+    let share_associated_token_account_info = next_account_info(account_info_iter)?;
+    let share_escrow_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let mut vault_data = FractionalVault::decode(vault_account_info)?;
+    let (vault_key, vault_bump) = assert_pda_input(&[FRACTIONAL_VAULT_KEY.as_ref(), vault_data.gem_mint.as_ref()], vault_account_info);
+    let (_share_account_key, _share_account_bump) = assert_pda_input(&[VAULT_SHARE_ACCOUNT_KEY.as_ref(), vault_key.as_ref(), payer_account_info.key.as_ref()], share_account_info);
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, &vault_data.share_mint),
+        share_associated_token_account_info.key,
+    )
+    .expect("Error: @share_associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&vault_key, &vault_data.share_mint),
+        share_escrow_account_info.key,
+    )
+    .expect("Error: @share_escrow_associated_token_address");
+
+    let mut share_account_data = VaultShareAccount::decode(share_account_info)?;
+    if amount > share_account_data.shares {
+        Err(ProgramError::InsufficientFunds)?
+    }
+    pay_vault_rewards(vault_account_info, payer_account_info, share_account_data.pending_rewards(&vault_data))?;
+
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            share_escrow_account_info.key,
+            share_associated_token_account_info.key,
+            &vault_key,
+            &[],
+            amount,
+        )?,
+        &[
+            share_escrow_account_info.clone(),
+            share_associated_token_account_info.clone(),
+            vault_account_info.clone(),
+        ],
+        &[&[FRACTIONAL_VAULT_KEY.as_ref(), vault_data.gem_mint.as_ref(), &[vault_bump]]],
+    )?;
+
+    share_account_data.shares = share_account_data.shares.checked_sub(amount).unwrap();
+    vault_data.deposited_shares = vault_data.deposited_shares.checked_sub(amount).unwrap();
+    share_account_data.reward_debt = vault_data.accrued(share_account_data.shares);
+    vault_data.serialize(&mut &mut vault_account_info.data.borrow_mut()[..])?;
+
+    if share_account_data.shares == 0 {
+        let dest_starting_lamports = payer_account_info.lamports();
+        **payer_account_info.lamports.borrow_mut() = dest_starting_lamports
+            .checked_add(share_account_info.lamports())
+            .unwrap();
+        **share_account_info.lamports.borrow_mut() = 0;
+        share_account_info.data.borrow_mut().fill(0);
+    } else {
+        share_account_data.serialize(&mut &mut share_account_info.data.borrow_mut()[..])?;
+    }
+    InglEvent::VaultSharesWithdrawn { vault: vault_key, owner: *payer_account_info.key, shares: amount }.emit();
+
+    Ok(())
+}
+
+pub fn harvest_vault_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let vote_account_info = next_account_info(account_info_iter)?;
+    let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+    let authorized_withdrawer_info = next_account_info(account_info_iter)?;
+    let sysvar_stake_history_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_program_owned(gem_account_data_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(sysvar_stake_history_info.key, &sysvar::stake_history::id())?;
+    let stake_history = StakeHistory::from_account_info(sysvar_stake_history_info)?;
+
+    let mut vault_data = FractionalVault::decode(vault_account_info)?;
+    let (vault_key, _vault_bump) = assert_pda_input(&[FRACTIONAL_VAULT_KEY.as_ref(), mint_account_info.key.as_ref()], vault_account_info);
+    assert_pubkeys_exactitude(&vault_data.gem_mint, mint_account_info.key).expect("Error: @vault gem mint");
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+    let mut ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+    let (_authorized_withdrawer, authorized_withdrawer_bump) = assert_pda_input(&[AUTHORIZED_WITHDRAWER_KEY.as_ref()], authorized_withdrawer_info);
+
+    let mut gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+    if let FundsLocation::VoteAccount { vote_account_id } = gem_account_data.funds_location {
+        assert_pubkeys_exactitude(&vote_account_id, vote_account_info.key)
+            .expect("Error: @vote_account_info in funds location");
+    } else {
+        Err(InglError::InvalidFundsLocation.utilize(Some("Gem's fund location")))?
+    }
+
+    let total_reward = settle_gem_rewards(&mut gem_account_data, &mut ingl_vote_account_data, &stake_history);
+    InglEvent::RewardsClaimed { mint: *mint_account_info.key, owner: vault_key, lamports: total_reward }.emit();
+    invoke_signed(
+        &system_instruction::transfer(
+            authorized_withdrawer_info.key,
+            &vault_key,
+            total_reward,
+        ),
+        &[
+            authorized_withdrawer_info.clone(),
+            vault_account_info.clone(),
+        ],
+        &[&[
+            AUTHORIZED_WITHDRAWER_KEY.as_ref(),
+            &[authorized_withdrawer_bump],
+        ]],
+    )?;
+    vault_data.distribute(total_reward);
+
+    vault_data.serialize(&mut &mut vault_account_info.data.borrow_mut()[..])?;
+    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+    ingl_vote_account_data.serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn claim_vault_rewards(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let share_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    let vault_data = FractionalVault::decode(vault_account_info)?;
+    let (vault_key, _vault_bump) = assert_pda_input(&[FRACTIONAL_VAULT_KEY.as_ref(), vault_data.gem_mint.as_ref()], vault_account_info);
+    let (_share_account_key, _share_account_bump) = assert_pda_input(&[VAULT_SHARE_ACCOUNT_KEY.as_ref(), vault_key.as_ref(), payer_account_info.key.as_ref()], share_account_info);
+
+    let mut share_account_data = VaultShareAccount::decode(share_account_info)?;
+    pay_vault_rewards(vault_account_info, payer_account_info, share_account_data.pending_rewards(&vault_data))?;
+    share_account_data.reward_debt = vault_data.accrued(share_account_data.shares);
+    share_account_data.serialize(&mut &mut share_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn reclaim_fractionalized_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let vault_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let escrow_associated_token_account_info = next_account_info(account_info_iter)?;
+    let share_mint_account_info = next_account_info(account_info_iter)?;
+    let share_associated_token_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let vault_data = FractionalVault::decode(vault_account_info)?;
+    let (vault_key, vault_bump) = assert_pda_input(&[FRACTIONAL_VAULT_KEY.as_ref(), mint_account_info.key.as_ref()], vault_account_info);
+    assert_pubkeys_exactitude(&vault_data.share_mint, share_mint_account_info.key).expect("Error: @share_mint_account_info");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&vault_key, mint_account_info.key),
+        escrow_associated_token_account_info.key,
+    )
+    .expect("Error: @escrow_associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, &vault_data.share_mint),
+        share_associated_token_account_info.key,
+    )
+    .expect("Error: @share_associated_token_address");
+
+    // Deposited shares still have rewards to settle, so every share must be back in the reclaimer's wallet.
+    let share_associated_token_data =
+        Account::unpack(&share_associated_token_account_info.data.borrow())?;
+    if vault_data.deposited_shares != 0 || share_associated_token_data.amount != VAULT_SHARES_SUPPLY {
+        Err(ProgramError::InsufficientFunds)?
+    }
+
+    invoke(
+        &spl_token::instruction::burn(
+            &spl_token::id(),
+            share_associated_token_account_info.key,
+            share_mint_account_info.key,
+            payer_account_info.key,
+            &[],
+            VAULT_SHARES_SUPPLY,
+        )?,
+        &[
+            share_associated_token_account_info.clone(),
+            share_mint_account_info.clone(),
+            payer_account_info.clone(),
+        ],
+    )?;
+
+    if associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                payer_account_info.key,
+                mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                associated_token_account_info.clone(),
+                payer_account_info.clone(),
+                mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            escrow_associated_token_account_info.key,
+            associated_token_account_info.key,
+            &vault_key,
+            &[],
+            1,
+        )?,
+        &[
+            escrow_associated_token_account_info.clone(),
+            associated_token_account_info.clone(),
+            vault_account_info.clone(),
+        ],
+        &[&[FRACTIONAL_VAULT_KEY.as_ref(), mint_account_info.key.as_ref(), &[vault_bump]]],
+    )?;
+    invoke_signed(
+        &spl_token::instruction::close_account(
+            &spl_token::id(),
+            escrow_associated_token_account_info.key,
+            payer_account_info.key,
+            &vault_key,
+            &[],
+        )?,
+        &[
+            escrow_associated_token_account_info.clone(),
+            payer_account_info.clone(),
+            vault_account_info.clone(),
+        ],
+        &[&[FRACTIONAL_VAULT_KEY.as_ref(), mint_account_info.key.as_ref(), &[vault_bump]]],
+    )?;
+
+    // Closing the vault also hands over the rewards pooled for wallet-held shares, all of which the reclaimer holds.
+    let dest_starting_lamports = payer_account_info.lamports();
+    **payer_account_info.lamports.borrow_mut() = dest_starting_lamports
+        .checked_add(vault_account_info.lamports())
+        .unwrap();
+    **vault_account_info.lamports.borrow_mut() = 0;
+    vault_account_info.data.borrow_mut().fill(0);
+    InglEvent::FractionalizedNftReclaimed { mint: *mint_account_info.key, owner: *payer_account_info.key, vault: vault_key }.emit();
+
+    Ok(())
+}
+
+pub fn init_protocol_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    let (protocol_config_key, protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+
+    let space = 1000;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account_info.key,
+            &protocol_config_key,
+            rent_lamports,
+            space as u64,
+            program_id,
+        ),
+        &[payer_account_info.clone(), protocol_config_account_info.clone()],
+        &[&[PROTOCOL_CONFIG_KEY.as_ref(), &[protocol_config_bump]]],
+    )?;
+
+    let protocol_config = ProtocolConfig {
+        validation_phrase: PROTOCOL_CONFIG_VAL_PHRASE,
+        marketplace_fee_bps: 0,
+        presale_merkle_root: [0; 32],
+        presale_start: 0,
+        public_mint_start: 0,
+        class_limits: [ClassLimit::default(); 6],
+        randomness_source: RandomnessSource::PriceFeeds,
+        vrf_account: Pubkey::default(),
+        reroll_fee: 0,
+        reroll_treasury_bps: 0,
+        rarity_bonus_pool_bps: 0,
+        rarity_reward_bonus_bps: [0; 5],
+        rarity_vote_bonus_bps: [0; 5],
+        redeem_fee_schedule: Vec::new(),
+        reroll_rewards_vote_account: Pubkey::default(),
+    };
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn set_marketplace_fee(_program_id: &Pubkey, accounts: &[AccountInfo], fee_bps: u16) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    if fee_bps as u64 > BASIS_POINTS {
+        Err(InglError::BeyondBounds.utilize(Some("marketplace fee")))?
+    }
+
+    let mut protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    protocol_config.marketplace_fee_bps = fee_bps;
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn list_nft(program_id: &Pubkey, accounts: &[AccountInfo], price: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let listing_account_info = next_account_info(account_info_iter)?;
+    let escrow_associated_token_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_data_info)?;
+    assert_owned_by(mint_account_info, &spl_program::id())?;
+    assert_owned_by(associated_token_account_info, &spl_program::id())?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    let (listing_key, listing_bump) = assert_pda_input(&[LISTING_KEY.as_ref(), mint_account_info.key.as_ref()], listing_account_info);
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&listing_key, mint_account_info.key),
+        escrow_associated_token_account_info.key,
+    )
+    .expect("Error: @escrow_associated_token_address");
+
+    let associated_token_address_data =
+        Account::unpack(&associated_token_account_info.data.borrow())?;
+    if associated_token_address_data.amount != 1 {
+        Err(ProgramError::InsufficientFunds)?
+    }
+    let _gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+
+    let space = 80;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account_info.key,
+            &listing_key,
+            rent_lamports,
+            space as u64,
+            program_id,
+        ),
+        &[payer_account_info.clone(), listing_account_info.clone()],
+        &[&[LISTING_KEY.as_ref(), mint_account_info.key.as_ref(), &[listing_bump]]],
+    )?;
+
+    if escrow_associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                &listing_key,
+                mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                escrow_associated_token_account_info.clone(),
+                listing_account_info.clone(),
+                mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    invoke(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            associated_token_account_info.key,
+            escrow_associated_token_account_info.key,
+            payer_account_info.key,
+            &[],
+            1,
+        )?,
+        &[
+            associated_token_account_info.clone(),
+            escrow_associated_token_account_info.clone(),
+            payer_account_info.clone(),
+        ],
+    )?;
+
+    let listing_data = Listing {
+        validation_phrase: LISTING_VAL_PHRASE,
+        seller: *payer_account_info.key,
+        gem_mint: *mint_account_info.key,
+        price,
+        listed_at: Clock::get()?.unix_timestamp as u32,
+    };
+    listing_data.serialize(&mut &mut listing_account_info.data.borrow_mut()[..])?;
+    InglEvent::Listed { mint: *mint_account_info.key, seller: *payer_account_info.key, price }.emit();
+
+    Ok(())
+}
+
+/// Sends the escrowed gem NFT to `receiver`, then closes the escrow and listing accounts, refunding their rent to the seller.
+fn release_listing<'a>(
+    seller_account_info: &AccountInfo<'a>,
+    listing_account_info: &AccountInfo<'a>,
+    mint_account_info: &AccountInfo<'a>,
+    escrow_associated_token_account_info: &AccountInfo<'a>,
+    receiver_associated_token_account_info: &AccountInfo<'a>,
+) -> ProgramResult {
+    let (listing_key, listing_bump) = assert_pda_input(&[LISTING_KEY.as_ref(), mint_account_info.key.as_ref()], listing_account_info);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            &spl_token::id(),
+            escrow_associated_token_account_info.key,
+            receiver_associated_token_account_info.key,
+            &listing_key,
+            &[],
+            1,
+        )?,
+        &[
+            escrow_associated_token_account_info.clone(),
+            receiver_associated_token_account_info.clone(),
+            listing_account_info.clone(),
+        ],
+        &[&[LISTING_KEY.as_ref(), mint_account_info.key.as_ref(), &[listing_bump]]],
+    )?;
+    invoke_signed(
+        &spl_token::instruction::close_account(
+            &spl_token::id(),
+            escrow_associated_token_account_info.key,
+            seller_account_info.key,
+            &listing_key,
+            &[],
+        )?,
+        &[
+            escrow_associated_token_account_info.clone(),
+            seller_account_info.clone(),
+            listing_account_info.clone(),
+        ],
+        &[&[LISTING_KEY.as_ref(), mint_account_info.key.as_ref(), &[listing_bump]]],
+    )?;
+
+    let dest_starting_lamports = seller_account_info.lamports();
+    **seller_account_info.lamports.borrow_mut() = dest_starting_lamports
+        .checked_add(listing_account_info.lamports())
+        .unwrap();
+    **listing_account_info.lamports.borrow_mut() = 0;
+    listing_account_info.data.borrow_mut().fill(0);
+
+    Ok(())
+}
+
+pub fn cancel_listing(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let listing_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let escrow_associated_token_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+    let (listing_key, _listing_bump) = assert_pda_input(&[LISTING_KEY.as_ref(), mint_account_info.key.as_ref()], listing_account_info);
+    let listing_data = Listing::decode(listing_account_info)?;
+    assert_pubkeys_exactitude(&listing_data.seller, payer_account_info.key).expect("Error: @listing seller");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&listing_key, mint_account_info.key),
+        escrow_associated_token_account_info.key,
+    )
+    .expect("Error: @escrow_associated_token_address");
+
+    release_listing(
+        payer_account_info,
+        listing_account_info,
+        mint_account_info,
+        escrow_associated_token_account_info,
+        associated_token_account_info,
+    )?;
+    InglEvent::ListingCancelled { mint: *mint_account_info.key, seller: *payer_account_info.key }.emit();
+
+    Ok(())
+}
+
+pub fn buy_nft(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let seller_account_info = next_account_info(account_info_iter)?;
+    let listing_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_data_info = next_account_info(account_info_iter)?;
+    let associated_token_account_info = next_account_info(account_info_iter)?;
+    let escrow_associated_token_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+    let program_treasury_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let spl_token_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_data_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    assert_pubkeys_exactitude(&spl_token::id(), spl_token_program_account_info.key).expect("Error: @spl_token_program_account_info");
+
+    let (listing_key, _listing_bump) = assert_pda_input(&[LISTING_KEY.as_ref(), mint_account_info.key.as_ref()], listing_account_info);
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let (program_treasury_id, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], program_treasury_account_info);
+
+    let listing_data = Listing::decode(listing_account_info)?;
+    assert_pubkeys_exactitude(&listing_data.seller, seller_account_info.key).expect("Error: @listing seller");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+        associated_token_account_info.key,
+    )
+    .expect("Error: @associated_token_address");
+    assert_pubkeys_exactitude(
+        &get_associated_token_address(&listing_key, mint_account_info.key),
+        escrow_associated_token_account_info.key,
+    )
+    .expect("Error: @escrow_associated_token_address");
+
+    let mut gem_account_data: GemAccountV0_0_1 = GemAccountV0_0_1::validate(
+        GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?,
+    )?;
+
+    // Rewards accrued up to the sale belong to the seller.
+    if let FundsLocation::VoteAccount { vote_account_id } = gem_account_data.funds_location {
+        let vote_account_info = next_account_info(account_info_iter)?;
+        let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+        let authorized_withdrawer_info = next_account_info(account_info_iter)?;
+        let sysvar_stake_history_info = next_account_info(account_info_iter)?;
+
+        assert_pubkeys_exactitude(&vote_account_id, vote_account_info.key)
+            .expect("Error: @vote_account_info in funds location");
+        assert_pubkeys_exactitude(sysvar_stake_history_info.key, &sysvar::stake_history::id())?;
+        let stake_history = StakeHistory::from_account_info(sysvar_stake_history_info)?;
+        let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+        let mut ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+        let (_authorized_withdrawer, authorized_withdrawer_bump) = assert_pda_input(&[AUTHORIZED_WITHDRAWER_KEY.as_ref()], authorized_withdrawer_info);
+
+        let total_reward = settle_gem_rewards(&mut gem_account_data, &mut ingl_vote_account_data, &stake_history);
+        InglEvent::RewardsClaimed { mint: *mint_account_info.key, owner: *seller_account_info.key, lamports: total_reward }.emit();
+        invoke_signed(
+            &system_instruction::transfer(
+                authorized_withdrawer_info.key,
+                seller_account_info.key,
+                total_reward,
+            ),
+            &[
+                authorized_withdrawer_info.clone(),
+                seller_account_info.clone(),
+            ],
+            &[&[
+                AUTHORIZED_WITHDRAWER_KEY.as_ref(),
+                &[authorized_withdrawer_bump],
+            ]],
+        )?;
+        ingl_vote_account_data.serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    }
+
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    let protocol_fee = protocol_config.marketplace_fee(listing_data.price);
+    invoke(
+        &system_instruction::transfer(payer_account_info.key, &program_treasury_id, protocol_fee),
+        &[
+            payer_account_info.clone(),
+            program_treasury_account_info.clone(),
+        ],
+    )?;
+    invoke(
+        &system_instruction::transfer(
+            payer_account_info.key,
+            seller_account_info.key,
+            listing_data.price.checked_sub(protocol_fee).unwrap(),
+        ),
+        &[
+            payer_account_info.clone(),
+            seller_account_info.clone(),
+        ],
+    )?;
+
+    if associated_token_account_info.data_is_empty() {
+        invoke(
+            &spl_associated_token_account::instruction::create_associated_token_account(
+                payer_account_info.key,
+                payer_account_info.key,
+                mint_account_info.key,
+            ),
+            &[
+                payer_account_info.clone(),
+                associated_token_account_info.clone(),
+                payer_account_info.clone(),
+                mint_account_info.clone(),
+                system_program_account_info.clone(),
+                spl_token_program_account_info.clone(),
+            ],
+        )?;
+    }
+    release_listing(
+        seller_account_info,
+        listing_account_info,
+        mint_account_info,
+        escrow_associated_token_account_info,
+        associated_token_account_info,
+    )?;
+
+    // The seller's compound reserve doesn't follow the gem.
+    gem_account_data.compound_rewards = false;
+    gem_account_data.serialize(&mut &mut gem_account_data_info.data.borrow_mut()[..])?;
+    InglEvent::Sold {
+        mint: *mint_account_info.key,
+        seller: *seller_account_info.key,
+        buyer: *payer_account_info.key,
+        price: listing_data.price,
+        fee: protocol_fee,
+    }
+    .emit();
+
+    Ok(())
+}
+
+pub fn set_mint_phases(_program_id: &Pubkey, accounts: &[AccountInfo], merkle_root: [u8; 32], presale_start: u32, public_mint_start: u32) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    if presale_start > public_mint_start {
+        Err(InglError::BeyondBounds.utilize(Some("presale must start before public mint")))?
+    }
+
+    let mut protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    protocol_config.presale_merkle_root = merkle_root;
+    protocol_config.presale_start = presale_start;
+    protocol_config.public_mint_start = public_mint_start;
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Mints a gem during the presale. Accounts are those of `mint_nft`, followed by the payer's allowlist allocation PDA.
+pub fn mint_allowlist_nft(program_id: &Pubkey, accounts: &[AccountInfo], class: Class, allocation: u32, proof: Vec<[u8; 32]>) -> ProgramResult {
+    if accounts.len() < GEM_MINT_ACCOUNTS_LEN + 1 {
+        Err(ProgramError::NotEnoughAccountKeys)?
+    }
+    let payer_account_info = &accounts[0];
+    let system_program_account_info = &accounts[6];
+    let protocol_config_account_info = &accounts[GEM_MINT_ACCOUNTS_LEN - 1];
+    let allocation_account_info = &accounts[GEM_MINT_ACCOUNTS_LEN];
+
+    assert_is_signer(payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    let root = protocol_config.presale_merkle_root;
+
+    if !verify_merkle_proof(allowlist_leaf(payer_account_info.key, allocation), &proof, &root) {
+        Err(InglError::InvalidProof.utilize(Some("allowlist")))?
+    }
+
+    let (allocation_key, allocation_bump) = assert_pda_input(&[ALLOWLIST_ALLOCATION_KEY.as_ref(), root.as_ref(), payer_account_info.key.as_ref()], allocation_account_info);
+    if allocation_account_info.data_is_empty() {
+        let space = 40;
+        let rent_lamports = Rent::get()?.minimum_balance(space);
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_account_info.key,
+                &allocation_key,
+                rent_lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer_account_info.clone(), allocation_account_info.clone(), system_program_account_info.clone()],
+            &[&[ALLOWLIST_ALLOCATION_KEY.as_ref(), root.as_ref(), payer_account_info.key.as_ref(), &[allocation_bump]]],
+        )?;
+        let allocation_data = AllowlistAllocation {
+            validation_phrase: ALLOWLIST_ALLOCATION_VAL_PHRASE,
+            wallet: *payer_account_info.key,
+            minted: 0,
+        };
+        allocation_data.serialize(&mut &mut allocation_account_info.data.borrow_mut()[..])?;
+    }
+
+    let mut allocation_data = AllowlistAllocation::decode(allocation_account_info)?;
+    allocation_data.consume(allocation)?;
+    allocation_data.serialize(&mut &mut allocation_account_info.data.borrow_mut()[..])?;
+
+    mint_gem(program_id, accounts, class, GemFunding::Payer { presale: true })
+}
+
+pub fn set_class_limits(_program_id: &Pubkey, accounts: &[AccountInfo], class: Class, max_supply: u32, mint_open: u32, mint_close: u32) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    if mint_close != 0 && mint_close <= mint_open {
+        Err(InglError::BeyondBounds.utilize(Some("class mint window must close after it opens")))?
+    }
+
+    let mut protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    protocol_config.class_limits[class.index()] = ClassLimit {
+        max_supply,
+        mint_open,
+        mint_close,
+    };
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// The metadata registry, or None while it hasn't been initialized.
+fn load_metadata_registry(metadata_registry_account_info: &AccountInfo) -> Result<Option<MetadataRegistry>, ProgramError> {
+    let (_metadata_registry_key, _metadata_registry_bump) = assert_pda_input(&[METADATA_REGISTRY_KEY.as_ref()], metadata_registry_account_info);
+    if metadata_registry_account_info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(MetadataRegistry::decode(metadata_registry_account_info)?))
+}
+
+pub fn init_metadata_registry(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    let (metadata_registry_key, metadata_registry_bump) = assert_pda_input(&[METADATA_REGISTRY_KEY.as_ref()], metadata_registry_account_info);
+
+    // Room for a maximum length uri for every class and rarity.
+    let space = 7500;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account_info.key,
+            &metadata_registry_key,
+            rent_lamports,
+            space as u64,
+            program_id,
+        ),
+        &[payer_account_info.clone(), metadata_registry_account_info.clone()],
+        &[&[METADATA_REGISTRY_KEY.as_ref(), &[metadata_registry_bump]]],
+    )?;
+
+    let metadata_registry = MetadataRegistry {
+        validation_phrase: METADATA_REGISTRY_VAL_PHRASE,
+        name_prefix: String::from(nfts::DEFAULT_NAME_PREFIX),
+        symbol: String::from(nfts::DEFAULT_SYMBOL),
+        uris: vec![String::new(); 36],
+        gem_uri_base: String::new(),
+    };
+    metadata_registry.serialize(&mut &mut metadata_registry_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn set_metadata_uri(_program_id: &Pubkey, accounts: &[AccountInfo], class: Class, rarity: Option<Rarity>, uri: String) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_metadata_registry_key, _metadata_registry_bump) = assert_pda_input(&[METADATA_REGISTRY_KEY.as_ref()], metadata_registry_account_info);
+    if uri.len() > mpl_token_metadata::state::MAX_URI_LENGTH {
+        Err(InglError::BeyondBounds.utilize(Some("metadata uri length")))?
+    }
+
+    let mut metadata_registry = MetadataRegistry::decode(metadata_registry_account_info)?;
+    metadata_registry.uris[nfts::registry_index(class, &rarity)] = uri;
+    metadata_registry.serialize(&mut &mut metadata_registry_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Points gems at their own metadata JSON under `uri_base`. Gems pick it up the next time their metadata is synced.
+pub fn set_gem_uri_base(_program_id: &Pubkey, accounts: &[AccountInfo], uri_base: String) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_metadata_registry_key, _metadata_registry_bump) = assert_pda_input(&[METADATA_REGISTRY_KEY.as_ref()], metadata_registry_account_info);
+    if uri_base.len() + nfts::MAX_GEM_URI_PATH_LENGTH > mpl_token_metadata::state::MAX_URI_LENGTH {
+        Err(InglError::BeyondBounds.utilize(Some("gem uri base length")))?
+    }
+
+    let mut metadata_registry = MetadataRegistry::decode(metadata_registry_account_info)?;
+    metadata_registry.gem_uri_base = uri_base;
+    metadata_registry.serialize(&mut &mut metadata_registry_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn set_metadata_templates(_program_id: &Pubkey, accounts: &[AccountInfo], name_prefix: String, symbol: String) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_metadata_registry_key, _metadata_registry_bump) = assert_pda_input(&[METADATA_REGISTRY_KEY.as_ref()], metadata_registry_account_info);
+    // Leave room in names for the longest class name and the gem's numeration, up to u32::MAX. Symbols are used as they are.
+    if name_prefix.len() + "Serendibite #".len() + 10 > mpl_token_metadata::state::MAX_NAME_LENGTH || symbol.len() > mpl_token_metadata::state::MAX_SYMBOL_LENGTH {
+        Err(InglError::BeyondBounds.utilize(Some("metadata template length")))?
+    }
+
+    let mut metadata_registry = MetadataRegistry::decode(metadata_registry_account_info)?;
+    metadata_registry.name_prefix = name_prefix;
+    metadata_registry.symbol = symbol;
+    metadata_registry.serialize(&mut &mut metadata_registry_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Rewrites a gem's name, symbol and uri from the registry and the gem's state. Anyone can crank this after a registry change.
+pub fn refresh_gem_metadata(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_info = next_account_info(account_info_iter)?;
+    let metadata_account_info = next_account_info(account_info_iter)?;
+    let mint_authority_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_info)?;
+    assert_owned_by(metadata_account_info, &mpl_token_metadata::id())?;
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_info);
+
+    let gem_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(
+        &gem_account_info.data.borrow(),
+    )?)?;
+    sync_gem_metadata(
+        mint_account_info,
+        metadata_account_info,
+        mint_authority_account_info,
+        metadata_registry_account_info,
+        &gem_data,
+    )
+}
+
+/// Rewrites a gem's name, symbol and uri from the metadata registry and the gem's current state.
+fn sync_gem_metadata<'a>(
+    mint_account_info: &AccountInfo<'a>,
+    metadata_account_info: &AccountInfo<'a>,
+    mint_authority_account_info: &AccountInfo<'a>,
+    metadata_registry_account_info: &AccountInfo<'a>,
+    gem_data: &GemAccountV0_0_1,
+) -> ProgramResult {
+    assert_owned_by(metadata_account_info, &mpl_token_metadata::id())?;
+    let (mint_authority_key, mint_authority_bump) = assert_pda_input(&[INGL_MINT_AUTHORITY_KEY.as_ref()], mint_authority_account_info);
+
+    let mpl_token_metadata_id = mpl_token_metadata::id();
+    let (nft_metadata_key, _nft_metadata_bump) = Pubkey::find_program_address(
+        &[
+            PREFIX.as_bytes(),
+            mpl_token_metadata_id.as_ref(),
+            mint_account_info.key.as_ref(),
+        ],
+        &mpl_token_metadata_id,
+    );
+    assert_pubkeys_exactitude(&nft_metadata_key, metadata_account_info.key)
+        .expect("Error: @meta_data_account_info");
+
+    let metadata_registry = load_metadata_registry(metadata_registry_account_info)?;
+    let gem_metadata = Metadata::deserialize(&mut &metadata_account_info.data.borrow()[..])?;
+
+    invoke_signed(
+        &mpl_token_metadata::instruction::update_metadata_accounts_v2(
+            mpl_token_metadata_id,
+            *metadata_account_info.key,
+            mint_authority_key,
+            Some(mint_authority_key),
+            Some(DataV2 {
+                uri: nfts::gem_metadata_uri(metadata_registry.as_ref(), gem_data),
+                uses: gem_metadata.uses,
+                name: nfts::resolve_name(metadata_registry.as_ref(), gem_data.class, gem_data.numeration),
+                symbol: nfts::resolve_symbol(metadata_registry.as_ref()),
+                collection: gem_metadata.collection,
+                creators: gem_metadata.data.creators,
+                seller_fee_basis_points: gem_metadata.data.seller_fee_basis_points,
+            }),
+            Some(gem_metadata.primary_sale_happened),
+            Some(gem_metadata.is_mutable),
+        ),
+        &[
+            metadata_account_info.clone(),
+            mint_authority_account_info.clone(),
+        ],
+        &[&[INGL_MINT_AUTHORITY_KEY.as_ref(), &[mint_authority_bump]]],
+    )?;
+
+    Ok(())
+}
+
+/// Selects the randomness source for imprints initialized from now on. Gems already awaiting their imprint keep the source they recorded.
+pub fn set_randomness_source(_program_id: &Pubkey, accounts: &[AccountInfo], source: RandomnessSource, vrf_account: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    if source == RandomnessSource::SwitchboardVrf && vrf_account == Pubkey::default() {
+        msg!("The switchboard VRF source needs a VRF account");
+        Err(ProgramError::InvalidArgument)?
+    }
+
+    let mut protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    protocol_config.randomness_source = source;
+    protocol_config.vrf_account = vrf_account;
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Backfills the per-class gem statistics, which miss the gems that predate them, from a snapshot of every gem.
+/// The snapshot must be taken at a slot after which no gem was minted, burned, imprinted or moved.
+pub fn set_gem_statistics(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    class_supply: [u32; 6],
+    class_rarity_supply: [[u32; 5]; 6],
+    class_allocated: [u64; 6],
+    class_delegated: [u64; 6],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let global_gem_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+
+    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+    global_gem_data.set_statistics(class_supply, class_rarity_supply, class_allocated, class_delegated)?;
+    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Sets the re-roll fee, and the vote account whose delegated gems receive the part of it not sent to the treasury.
+pub fn set_reroll_fee(_program_id: &Pubkey, accounts: &[AccountInfo], fee: u64, treasury_bps: u16, rewards_vote_account: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    if treasury_bps as u64 > BASIS_POINTS {
+        Err(InglError::BeyondBounds.utilize(Some("reroll treasury share")))?
+    }
+    if (treasury_bps as u64) < BASIS_POINTS && rewards_vote_account == Pubkey::default() {
+        Err(InglError::AddressMismatch.utilize(Some("A rewards share needs a re-roll rewards vote account")))?
+    }
+
+    let mut protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    protocol_config.reroll_fee = fee;
+    protocol_config.reroll_treasury_bps = treasury_bps;
+    protocol_config.reroll_rewards_vote_account = rewards_vote_account;
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Pays the re-roll fee and starts a new rarity imprint for an imprinted gem, which `imprint_rarity` then reveals.
+/// A gem whose imprint is still pending, still frozen, has its commitment renewed instead, which is the only way to renew
+/// one that can no longer be revealed.
+/// Accounts are those of `init_rarity_imprint`, followed by the treasury, the configured re-roll rewards vote account and its vote data,
+/// the system program, the gem's metadata, the metadata registry, the global gems account and the accounts read by
+/// the configured randomness source's commitment.
+/// The rewards share of the fee is paid into that vote account, reaching its delegated gems with the next processed rewards.
+pub fn reroll_rarity(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let gem_account_info = next_account_info(account_info_iter)?;
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let _associated_token_account_info = next_account_info(account_info_iter)?;
+    let freeze_authority_account_info = next_account_info(account_info_iter)?;
+    let _nft_edition_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+    let program_treasury_account_info = next_account_info(account_info_iter)?;
+    let vote_account_info = next_account_info(account_info_iter)?;
+    let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+    let metadata_account_info = next_account_info(account_info_iter)?;
+    let metadata_registry_account_info = next_account_info(account_info_iter)?;
+    let global_gem_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(gem_account_info)?;
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+    assert_pubkeys_exactitude(system_program_account_info.key, &system_program::id()).expect("Error: @system_program_account_info");
+    let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_info);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    let (program_treasury_id, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], program_treasury_account_info);
+    let (_vote_data_key, _vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+    InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    if protocol_config.reroll_fee == 0 {
+        Err(InglError::TooEarly.utilize(Some("Rarity re-rolls aren't enabled")))?
+    }
+    assert_pubkeys_exactitude(&protocol_config.reroll_rewards_vote_account, vote_account_info.key)?;
+
+    let mut gem_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(&gem_account_info.data.borrow())?)?;
+    if gem_data.rarity_seed_time.is_none() {
+        Err(InglError::TooEarly.utilize(Some("Only gems with a rarity imprint, revealed or pending, can be re-rolled")))?
+    }
+    if let FundsLocation::VoteAccount { .. } = gem_data.funds_location {
+        // Rewards owed under the current rarity's bonus are settled by undelegating first.
+        Err(InglError::InvalidFundsLocation.utilize(Some("Undelegate the gem before re-rolling")))?
+    }
+
+    let treasury_fee = (protocol_config.reroll_fee as u128 * protocol_config.reroll_treasury_bps as u128 / BASIS_POINTS as u128) as u64;
+    let rewards_fee = protocol_config.reroll_fee - treasury_fee;
+    invoke(
+        &system_instruction::transfer(payer_account_info.key, &program_treasury_id, treasury_fee),
+        &[payer_account_info.clone(), program_treasury_account_info.clone()],
+    )?;
+    invoke(
+        &system_instruction::transfer(payer_account_info.key, vote_account_info.key, rewards_fee),
+        &[payer_account_info.clone(), vote_account_info.clone()],
+    )?;
+
+    gem_data.rarity_rolls = gem_data.rarity_rolls.checked_add(1).unwrap();
+    match gem_data.rarity.take() {
+        Some(rarity) => {
+            let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+            global_gem_data.remove_rarity(gem_data.class, &rarity);
+            global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+            gem_data.rarity_seed_time = None;
+            gem_data.serialize(&mut &mut gem_account_info.data.borrow_mut()[..])?;
+
+            // Seeds the new round and freezes the gem until it is revealed.
+            let mut imprint_accounts = accounts[..7].to_vec();
+            imprint_accounts.extend(account_info_iter.cloned());
+            init_rarity_imprint(program_id, &imprint_accounts)?;
+        }
+        None => {
+            randomness::commit_seed(account_info_iter, &mut gem_data, &protocol_config, &Clock::get()?)?;
+            gem_data.serialize(&mut &mut gem_account_info.data.borrow_mut()[..])?;
+        }
+    }
+
+    let gem_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(&gem_account_info.data.borrow())?)?;
+    sync_gem_metadata(
+        mint_account_info,
+        metadata_account_info,
+        freeze_authority_account_info,
+        metadata_registry_account_info,
+        &gem_data,
+    )?;
+
+    Ok(())
+}
+
+/// Sets the rarity bonuses. Reward bonuses apply to snapshots processed from now on, vote bonuses to votes cast from now on.
+pub fn set_rarity_multipliers(_program_id: &Pubkey, accounts: &[AccountInfo], bonus_pool_bps: u16, reward_bonus_bps: [u16; 5], vote_bonus_bps: [u16; 5]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    if bonus_pool_bps as u64 > BASIS_POINTS {
+        Err(InglError::BeyondBounds.utilize(Some("rarity bonus pool share")))?
+    }
+
+    let mut protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    protocol_config.rarity_bonus_pool_bps = bonus_pool_bps;
+    protocol_config.rarity_reward_bonus_bps = reward_bonus_bps;
+    protocol_config.rarity_vote_bonus_bps = vote_bonus_bps;
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Returns what is left unclaimed of a vote account's rarity bonus pools older than RARITY_BONUS_CLAIM_EPOCHS to the treasury,
+/// and drops those pools. Gems that haven't withdrawn by then lose the bonus. Anyone can crank it.
+pub fn sweep_rarity_bonuses(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let _payer_account_info = next_account_info(account_info_iter)?;
+    let vote_account_info = next_account_info(account_info_iter)?;
+    let ingl_vote_data_account_info = next_account_info(account_info_iter)?;
+    let authorized_withdrawer_info = next_account_info(account_info_iter)?;
+    let treasury_account_info = next_account_info(account_info_iter)?;
+    let _system_program_account_info = next_account_info(account_info_iter)?;
+
+    let (_expected_vote_data_pubkey, _expected_vote_data_bump) = assert_pda_input(&[VOTE_DATA_ACCOUNT_KEY.as_ref(), vote_account_info.key.as_ref()], ingl_vote_data_account_info);
+    assert_program_owned(ingl_vote_data_account_info)?;
+    let mut ingl_vote_account_data = InglVoteAccountData::decode(ingl_vote_data_account_info)?;
+    let (_authorized_withdrawer, authorized_withdrawer_bump) = assert_pda_input(&[AUTHORIZED_WITHDRAWER_KEY.as_ref()], authorized_withdrawer_info);
+    let (_treasury_key, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], treasury_account_info);
+
+    let current_epoch = Clock::get()?.epoch;
+    let (expired, live): (Vec<RarityBonus>, Vec<RarityBonus>) = ingl_vote_account_data
+        .rarity_bonuses
+        .iter()
+        .partition(|rarity_bonus| rarity_bonus.epoch_number + RARITY_BONUS_CLAIM_EPOCHS < current_epoch);
+    if expired.is_empty() {
+        Err(InglError::TooEarly.utilize(Some("No rarity bonus pool has expired yet")))?
+    }
+    let unclaimed = expired
+        .iter()
+        .fold(0u64, |total, rarity_bonus| total.checked_add(rarity_bonus.pool.saturating_sub(rarity_bonus.claimed)).unwrap());
+
+    invoke_signed(
+        &system_instruction::transfer(
+            authorized_withdrawer_info.key,
+            treasury_account_info.key,
+            unclaimed,
+        ),
+        &[
+            authorized_withdrawer_info.clone(),
+            treasury_account_info.clone(),
+        ],
+        &[&[
+            AUTHORIZED_WITHDRAWER_KEY.as_ref(),
+            &[authorized_withdrawer_bump],
+        ]],
+    )?;
+    ingl_vote_account_data.rarity_bonuses = live;
+    ingl_vote_account_data.serialize(&mut &mut ingl_vote_data_account_info.data.borrow_mut()[..])?;
+    InglEvent::RarityBonusesSwept { vote_account: *vote_account_info.key, lamports: unclaimed }.emit();
+
+    Ok(())
+}
+
+/// Replaces the redeem fee schedule. An empty schedule restores the default one.
+pub fn set_redeem_fee_schedule(_program_id: &Pubkey, accounts: &[AccountInfo], schedule: Vec<RedeemFeePoint>) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+    if schedule.len() > MAX_REDEEM_FEE_POINTS {
+        Err(InglError::BeyondBounds.utilize(Some("redeem fee schedule length")))?
+    }
+    if schedule.iter().any(|point| point.fee_bps as u64 > BASIS_POINTS) {
+        Err(InglError::BeyondBounds.utilize(Some("redeem fee")))?
+    }
+    if schedule.windows(2).any(|pair| pair[0].elapsed >= pair[1].elapsed) {
+        Err(InglError::BeyondBounds.utilize(Some("redeem fee points must be in increasing time order")))?
+    }
+
+    let mut protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    protocol_config.redeem_fee_schedule = schedule;
+    protocol_config.serialize(&mut &mut protocol_config_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Read-only. Returns the fee redeeming the gem would cost now as a little-endian u64 in the return data, for clients to simulate.
+pub fn quote_redeem_fee(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let mint_account_info = next_account_info(account_info_iter)?;
+    let gem_account_info = next_account_info(account_info_iter)?;
+    let protocol_config_account_info = next_account_info(account_info_iter)?;
+
+    assert_program_owned(gem_account_info)?;
+    let (_gem_pubkey, _gem_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_info);
+    let (_protocol_config_key, _protocol_config_bump) = assert_pda_input(&[PROTOCOL_CONFIG_KEY.as_ref()], protocol_config_account_info);
+
+    let gem_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(&gem_account_info.data.borrow())?)?;
+    let protocol_config = ProtocolConfig::decode(protocol_config_account_info)?;
+    let now = Clock::get()?.unix_timestamp as u32;
+    let redeem_fee = protocol_config.redeem_fee(gem_data.class.get_class_lamports(), now.saturating_sub(gem_data.date_created));
+
+    msg!("Redeem fee: {:?} lamports of {:?}", redeem_fee, gem_data.class.get_class_lamports());
+    set_return_data(&redeem_fee.to_le_bytes());
+    Ok(())
+}
+
+/// Opens a proposal to pay `amount` lamports from the treasury to `recipient`, to be voted on by gem holders.
+pub fn create_treasury_proposal(program_id: &Pubkey, accounts: &[AccountInfo], recipient: Pubkey, amount: u64, memo: String) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let global_gem_account_info = next_account_info(account_info_iter)?;
+    let proposal_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_program_owned(global_gem_account_info)?;
+    let (_global_gem_pubkey, _global_gem_bump) = assert_pda_input(&[GLOBAL_GEM_KEY.as_ref()], global_gem_account_info);
+    assert_pubkeys_exactitude(system_program_account_info.key, &system_program::id()).expect("Error: @system_program_account_info");
+    if memo.len() > MAX_TREASURY_MEMO_LENGTH {
+        Err(InglError::BeyondBounds.utilize(Some("treasury proposal memo")))?
+    }
+    if amount == 0 {
+        Err(InglError::BeyondBounds.utilize(Some("treasury proposal amount")))?
+    }
+
+    let mut global_gem_data = GlobalGems::decode(global_gem_account_info)?;
+    let numeration = global_gem_data.treasury_proposal_numeration;
+    let (expected_proposal_id, expected_proposal_bump) = assert_pda_input(&[TREASURY_PROPOSAL_KEY.as_ref(), &numeration.to_be_bytes()], proposal_account_info);
+
+    let proposal_data = TreasuryProposal {
+        validation_phrase: TREASURY_PROPOSAL_VAL_PHRASE,
+        numeration,
+        proposer: *payer_account_info.key,
+        recipient,
+        amount,
+        memo,
+        date_created: Clock::get()?.unix_timestamp as u32,
+        votes_for: 0,
+        votes_against: 0,
+        date_executed: None,
+    };
+    let space = proposal_data.try_to_vec()?.len();
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(payer_account_info.key, &expected_proposal_id, rent_lamports, space as u64, program_id),
+        &[payer_account_info.clone(), proposal_account_info.clone()],
+        &[&[TREASURY_PROPOSAL_KEY.as_ref(), &numeration.to_be_bytes(), &[expected_proposal_bump]]],
+    )?;
+    proposal_data.serialize(&mut &mut proposal_account_info.data.borrow_mut()[..])?;
+    InglEvent::TreasuryProposalCreated { proposal: expected_proposal_id, proposer: *payer_account_info.key, recipient, amount }.emit();
+
+    global_gem_data.treasury_proposal_numeration = numeration.checked_add(1).unwrap();
+    global_gem_data.serialize(&mut &mut global_gem_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Votes on a treasury proposal with the payer's gems, each weighing its class lamports.
+/// A vote record keyed by the gem's mint is created per gem, so a gem votes once per proposal whoever holds it.
+pub fn vote_treasury_proposal(program_id: &Pubkey, accounts: &[AccountInfo], approve: bool, num_nfts: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let proposal_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(payer_account_info)?;
+    assert_pubkeys_exactitude(system_program_account_info.key, &system_program::id()).expect("Error: @system_program_account_info");
+    let mut proposal_data = TreasuryProposal::decode(proposal_account_info)?;
+    if Clock::get()?.unix_timestamp as u32 >= proposal_data.voting_ends() {
+        Err(InglError::TooLate.utilize(Some("Treasury proposal voting has ended")))?
+    }
+
+    for _ in 0..num_nfts {
+        let mint_account_info = next_account_info(account_info_iter)?;
+        let associated_token_account_info = next_account_info(account_info_iter)?;
+        let gem_account_data_info = next_account_info(account_info_iter)?;
+        let vote_record_account_info = next_account_info(account_info_iter)?;
+
+        let (_gem_account_pubkey, _gem_account_bump) = assert_pda_input(&[GEM_ACCOUNT_CONST.as_ref(), mint_account_info.key.as_ref()], gem_account_data_info);
+        assert_program_owned(gem_account_data_info)?;
+        assert_owned_by(mint_account_info, &spl_program::id())?;
+        assert_owned_by(associated_token_account_info, &spl_program::id())?;
+        assert_pubkeys_exactitude(
+            &get_associated_token_address(payer_account_info.key, mint_account_info.key),
+            associated_token_account_info.key,
+        )
+        .expect("Error: @associated_token_address");
+        let associated_token_address_data = Account::unpack(&associated_token_account_info.data.borrow())?;
+        if associated_token_address_data.amount != 1 {
+            Err(ProgramError::InsufficientFunds)?
+        }
+
+        let (vote_record_id, vote_record_bump) = assert_pda_input(
+            &[TREASURY_VOTE_KEY.as_ref(), proposal_account_info.key.as_ref(), mint_account_info.key.as_ref()],
+            vote_record_account_info,
+        );
+        if vote_record_account_info.owner == program_id {
+            Err(InglError::AlreadyVoted.utilize(Some(mint_account_info.key.to_string().as_ref() as &str)))?
+        }
+
+        let gem_account_data = GemAccountV0_0_1::validate(GemAccountVersions::decode_unchecked(&gem_account_data_info.data.borrow())?)?;
+        // Only gems that existed when the proposal was created can vote on it. Conversions mint new gems, so a gem that
+        // voted can't be converted into a fresh mint to vote again, and stake added during the vote doesn't count.
+        if gem_account_data.date_created >= proposal_data.date_created {
+            Err(InglError::TooLate.utilize(Some("Gem was minted after the treasury proposal was created")))?
+        }
+        let vote_data = TreasuryVote {
+            validation_phrase: TREASURY_VOTE_VAL_PHRASE,
+            proposal: *proposal_account_info.key,
+            gem_mint: *mint_account_info.key,
+            approve,
+            weight: gem_account_data.class.get_class_lamports(),
+        };
+        let space = vote_data.try_to_vec()?.len();
+        let rent_lamports = Rent::get()?.minimum_balance(space);
+        invoke_signed(
+            &system_instruction::create_account(payer_account_info.key, &vote_record_id, rent_lamports, space as u64, program_id),
+            &[payer_account_info.clone(), vote_record_account_info.clone()],
+            &[&[
+                TREASURY_VOTE_KEY.as_ref(),
+                proposal_account_info.key.as_ref(),
+                mint_account_info.key.as_ref(),
+                &[vote_record_bump],
+            ]],
+        )?;
+        vote_data.serialize(&mut &mut vote_record_account_info.data.borrow_mut()[..])?;
+
+        if approve {
+            proposal_data.votes_for = proposal_data.votes_for.checked_add(vote_data.weight).unwrap();
+        } else {
+            proposal_data.votes_against = proposal_data.votes_against.checked_add(vote_data.weight).unwrap();
+        }
+        InglEvent::TreasuryVoteCast { proposal: *proposal_account_info.key, mint: *mint_account_info.key, approve, weight: vote_data.weight }.emit();
+    }
+
+    proposal_data.serialize(&mut &mut proposal_account_info.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// Pays out a passed treasury proposal once its timelock has run. Callable by anyone.
+pub fn execute_treasury_proposal(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let proposal_account_info = next_account_info(account_info_iter)?;
+    let program_treasury_account_info = next_account_info(account_info_iter)?;
+    let recipient_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_pubkeys_exactitude(system_program_account_info.key, &system_program::id()).expect("Error: @system_program_account_info");
+    let (program_treasury_id, treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], program_treasury_account_info);
+    let mut proposal_data = TreasuryProposal::decode(proposal_account_info)?;
+    assert_pubkeys_exactitude(&proposal_data.recipient, recipient_account_info.key).expect("Error: @recipient_account_info");
+
+    if proposal_data.date_executed.is_some() {
+        Err(InglError::TooLate.utilize(Some("Treasury proposal already executed")))?
+    }
+    let now = Clock::get()?.unix_timestamp as u32;
+    if now < proposal_data.executable_from() {
+        Err(InglError::TooEarly.utilize(Some("Treasury proposal timelock hasn't elapsed")))?
+    }
+    if !proposal_data.has_passed() {
+        Err(InglError::ProposalNotPassed.utilize(Some("treasury proposal")))?
+    }
+
+    let spendable = program_treasury_account_info.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+    if proposal_data.amount > spendable {
+        Err(ProgramError::InsufficientFunds)?
+    }
+    invoke_signed(
+        &system_instruction::transfer(&program_treasury_id, recipient_account_info.key, proposal_data.amount),
+        &[program_treasury_account_info.clone(), recipient_account_info.clone()],
+        &[&[INGL_TREASURY_ACCOUNT_KEY.as_ref(), &[treasury_bump]]],
+    )?;
+
+    proposal_data.date_executed = Some(now);
+    proposal_data.serialize(&mut &mut proposal_account_info.data.borrow_mut()[..])?;
+    InglEvent::TreasuryProposalExecuted { proposal: *proposal_account_info.key, recipient: proposal_data.recipient, amount: proposal_data.amount }.emit();
+    Ok(())
+}
+
+/// Sweeps the legacy reward treasury into the program treasury, so every treasury lamport is spendable by proposal. Callable by anyone.
+pub fn migrate_legacy_treasury(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let legacy_treasury_account_info = next_account_info(account_info_iter)?;
+    let program_treasury_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_pubkeys_exactitude(system_program_account_info.key, &system_program::id()).expect("Error: @system_program_account_info");
+    let (legacy_treasury_id, legacy_treasury_bump) = assert_pda_input(&[TREASURY_ACCOUNT_KEY.as_ref()], legacy_treasury_account_info);
+    let (program_treasury_id, _treasury_bump) = assert_pda_input(&[INGL_TREASURY_ACCOUNT_KEY.as_ref()], program_treasury_account_info);
+
+    let lamports = legacy_treasury_account_info.lamports();
+    if lamports == 0 {
+        return Ok(());
+    }
+    invoke_signed(
+        &system_instruction::transfer(&legacy_treasury_id, &program_treasury_id, lamports),
+        &[legacy_treasury_account_info.clone(), program_treasury_account_info.clone()],
+        &[&[TREASURY_ACCOUNT_KEY.as_ref(), &[legacy_treasury_bump]]],
+    )?;
+
+    Ok(())
+}
+
+/// Creates the team vesting schedule over the team vault. Set once by the config authority; the beneficiary can be rotated later.
+pub fn init_team_vesting(program_id: &Pubkey, accounts: &[AccountInfo], beneficiary: Pubkey, start: u32, cliff: u32, duration: u32) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_account_info = next_account_info(account_info_iter)?;
+    let program_data_account_info = next_account_info(account_info_iter)?;
+    let team_vesting_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_config_authority(program_data_account_info, payer_account_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    let (team_vesting_key, team_vesting_bump) = assert_pda_input(&[TEAM_VESTING_KEY.as_ref()], team_vesting_account_info);
+    if cliff > duration {
+        Err(InglError::BeyondBounds.utilize(Some("vesting cliff")))?
+    }
+
+    let team_vesting = TeamVesting {
+        validation_phrase: TEAM_VESTING_VAL_PHRASE,
+        beneficiary,
+        start,
+        cliff,
+        duration,
+        claimed: 0,
+        tranches: Vec::new(),
+        matured: 0,
+        recorded_intake: 0,
+    };
+    let space = TeamVesting::SPACE;
+    let rent_lamports = Rent::get()?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(payer_account_info.key, &team_vesting_key, rent_lamports, space as u64, program_id),
+        &[payer_account_info.clone(), team_vesting_account_info.clone()],
+        &[&[TEAM_VESTING_KEY.as_ref(), &[team_vesting_bump]]],
+    )?;
+    team_vesting.serialize(&mut &mut team_vesting_account_info.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+pub fn set_team_beneficiary(_program_id: &Pubkey, accounts: &[AccountInfo], beneficiary: Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let beneficiary_account_info = next_account_info(account_info_iter)?;
+    let team_vesting_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(beneficiary_account_info)?;
+    let (_team_vesting_key, _team_vesting_bump) = assert_pda_input(&[TEAM_VESTING_KEY.as_ref()], team_vesting_account_info);
+    let mut team_vesting = TeamVesting::decode(team_vesting_account_info)?;
+    assert_pubkeys_exactitude(&team_vesting.beneficiary, beneficiary_account_info.key).expect("Error: @beneficiary_account_info");
+
+    team_vesting.beneficiary = beneficiary;
+    team_vesting.serialize(&mut &mut team_vesting_account_info.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// Pays the beneficiary whatever has vested of the team vault's revenue and hasn't been claimed yet. Revenue starts vesting
+/// at the first claim that sees it, so claiming often keeps each deposit's schedule close to its arrival.
+pub fn claim_team_vesting(_program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let beneficiary_account_info = next_account_info(account_info_iter)?;
+    let team_vesting_account_info = next_account_info(account_info_iter)?;
+    let team_vault_account_info = next_account_info(account_info_iter)?;
+    let system_program_account_info = next_account_info(account_info_iter)?;
+
+    assert_is_signer(beneficiary_account_info)?;
+    assert_pubkeys_exactitude(&system_program::id(), system_program_account_info.key).expect("Error: @system_program_account_info");
+    let (_team_vesting_key, _team_vesting_bump) = assert_pda_input(&[TEAM_VESTING_KEY.as_ref()], team_vesting_account_info);
+    let (team_vault_key, team_vault_bump) = assert_pda_input(&[TEAM_VAULT_KEY.as_ref()], team_vault_account_info);
+    let mut team_vesting = TeamVesting::decode(team_vesting_account_info)?;
+    assert_pubkeys_exactitude(&team_vesting.beneficiary, beneficiary_account_info.key).expect("Error: @beneficiary_account_info");
+
+    let vault_balance = team_vault_account_info.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+    let now = Clock::get()?.unix_timestamp as u32;
+    team_vesting.record_intake(vault_balance, now);
+    let claimable = team_vesting.claimable(vault_balance, now);
+    // A claim with nothing vested yet still succeeds, to start vesting the revenue it recorded.
+    if claimable > 0 {
+        invoke_signed(
+            &system_instruction::transfer(&team_vault_key, beneficiary_account_info.key, claimable),
+            &[team_vault_account_info.clone(), beneficiary_account_info.clone()],
+            &[&[TEAM_VAULT_KEY.as_ref(), &[team_vault_bump]]],
+        )?;
+        team_vesting.claimed = team_vesting.claimed.checked_add(claimable).unwrap();
+        InglEvent::TeamVestingClaimed { beneficiary: *beneficiary_account_info.key, lamports: claimable }.emit();
+    }
+    team_vesting.serialize(&mut &mut team_vesting_account_info.data.borrow_mut()[..])?;
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use std::str::FromStr;
+
+use num_traits::Pow;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    hash::hashv,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{self, Sysvar},
+};
+use switchboard_v2::{AggregatorHistoryBuffer, VrfAccountData};
+
+use crate::{
+    error::InglError,
+    state::{constants::*, GemAccountV0_0_1, ProtocolConfig, RandomnessSource},
+    utils::assert_pubkeys_exactitude,
+};
+
+/// Commits a gem's rarity to entropy that doesn't exist yet, under the configured randomness source: a seed time
+/// PRICE_TIME_INTERVAL out, the current slot, or the VRF round requested next. SwitchboardVrf reads the configured
+/// switchboard VRF account, which follows.
+pub fn commit_seed<'a, 'b: 'a>(
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    gem_data: &mut GemAccountV0_0_1,
+    protocol_config: &ProtocolConfig,
+    clock: &Clock,
+) -> ProgramResult {
+    gem_data.rarity_seed_time = Some(clock.unix_timestamp as u32 + PRICE_TIME_INTERVAL as u32);
+    gem_data.randomness_source = protocol_config.randomness_source;
+    gem_data.rarity_commit_slot = clock.slot;
+    if protocol_config.randomness_source == RandomnessSource::SwitchboardVrf {
+        let vrf_account_info = next_account_info(account_info_iter)?;
+        assert_switchboard_account(vrf_account_info, &protocol_config.vrf_account)?;
+        gem_data.rarity_vrf_round = VrfAccountData::new(vrf_account_info)?.counter + 1;
+    }
+    Ok(())
+}
+
+/// Draws the seed a gem's rarity is derived from, using the source recorded on the gem.
+/// The accounts each source reads follow in order:
+///   PriceFeeds: BTC, SOL, ETH and BNB switchboard history feeds.
+///   SwitchboardVrf: the configured switchboard VRF account.
+///   SlotHash: the slot hashes sysvar.
+/// Fails with TooLate once the gem's commitment can no longer be revealed, after which only a paid re-roll renews it.
+pub fn draw_seed<'a, 'b: 'a>(
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    gem_data: &GemAccountV0_0_1,
+    protocol_config: &ProtocolConfig,
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<[u8; 32], ProgramError> {
+    let entropy = match gem_data.randomness_source {
+        RandomnessSource::PriceFeeds => price_feeds_entropy(account_info_iter, gem_data.rarity_seed_time.unwrap())?,
+        RandomnessSource::SwitchboardVrf => {
+            let vrf_account_info = next_account_info(account_info_iter)?;
+            vrf_entropy(vrf_account_info, &protocol_config.vrf_account, gem_data.rarity_vrf_round)?
+        }
+        RandomnessSource::SlotHash => {
+            let slot_hashes_account_info = next_account_info(account_info_iter)?;
+            slot_hash_entropy(slot_hashes_account_info, gem_data.rarity_commit_slot)?
+        }
+    };
+
+    // Mixing in the mint keeps gems revealed from the same entropy apart. The mint always exists before its seed time is
+    // committed to, so it can't be chosen against entropy that is already known.
+    Ok(hashv(&[&entropy, &mint.to_bytes(), &program_id.to_bytes()]).to_bytes())
+}
+
+/// Maps a seed onto the 0..10_000 range rarities are picked from.
+pub fn rarity_roll(seed: [u8; 32]) -> u64 {
+    let mut byte_sum: u64 = 0;
+    for byte in seed {
+        byte_sum += (byte as u64).pow(2);
+    }
+    byte_sum * 10 % 10000
+}
+
+fn price_feeds_entropy<'a, 'b: 'a>(
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    seed_time: u32,
+) -> Result<[u8; 32], ProgramError> {
+    let feeds = [
+        (BTC_FEED_PUBLIC_KEY, BTC_FEED_MAX_STALENESS),
+        (SOL_FEED_PUBLIC_KEY, SOL_FEED_MAX_STALENESS),
+        (ETH_FEED_PUBLIC_KEY, ETH_FEED_MAX_STALENESS),
+        (BNB_FEED_PUBLIC_KEY, BNB_FEED_MAX_STALENESS),
+    ];
+    let now = Clock::get()?.unix_timestamp;
+    let mut prices: Vec<[u8; 16]> = Vec::new();
+    for (feed_key, max_staleness) in feeds {
+        let feed_account_info = next_account_info(account_info_iter)?;
+        prices.push(feed_price(feed_account_info, feed_key, max_staleness, seed_time, now)?);
+    }
+
+    Ok(hashv(&[&prices[0], &prices[1], &prices[2], &prices[3]]).to_bytes())
+}
+
+/// Reads the price a feed reported as of the seed time, once the feed is shown to still be reporting.
+fn feed_price(feed_account_info: &AccountInfo, feed_key: &str, max_staleness: i64, seed_time: u32, now: i64) -> Result<[u8; 16], ProgramError> {
+    assert_switchboard_account(feed_account_info, &Pubkey::from_str(feed_key).unwrap())?;
+
+    let history = AggregatorHistoryBuffer::new(feed_account_info)?;
+    let latest = history
+        .lower_bound(now)
+        .ok_or_else(|| InglError::MissingOracleReport.utilize(Some(feed_key)))?;
+    let seed_report = history
+        .lower_bound(seed_time as i64)
+        .ok_or_else(|| InglError::MissingOracleReport.utilize(Some(feed_key)))?;
+    assert_feed_reports(latest.timestamp, seed_report.timestamp, seed_time as i64, now, max_staleness)?;
+
+    let price = seed_report.value.mantissa * 10.pow(seed_report.value.scale) as i128;
+    Ok(price.to_be_bytes())
+}
+
+/// A feed must still be reporting, and must have reported shortly before the seed time, for its price there to stand for the market.
+fn assert_feed_reports(latest_timestamp: i64, seed_report_timestamp: i64, seed_time: i64, now: i64, max_staleness: i64) -> ProgramResult {
+    if now - latest_timestamp > max_staleness {
+        Err(InglError::StaleOracle.utilize(Some("latest report")))?
+    }
+    if seed_report_timestamp > seed_time || seed_time - seed_report_timestamp > PRICE_REPORT_TOLERANCE {
+        Err(InglError::MissingOracleReport.utilize(Some("seed time report")))?
+    }
+    Ok(())
+}
+
+fn assert_switchboard_account(account_info: &AccountInfo, expected_key: &Pubkey) -> ProgramResult {
+    if account_info.owner != &SWITCHBOARD_PROGRAM_ID || account_info.key != expected_key {
+        Err(InglError::InvalidOracle.utilize(Some(&account_info.key.to_string())))?
+    }
+    Ok(())
+}
+
+fn vrf_entropy(vrf_account_info: &AccountInfo, expected_vrf: &Pubkey, round: u128) -> Result<[u8; 32], ProgramError> {
+    assert_switchboard_account(vrf_account_info, expected_vrf)?;
+
+    let vrf = VrfAccountData::new(vrf_account_info)?;
+    round_result(vrf.counter, vrf.current_round.result, round)
+}
+
+/// Only the gem's own round counts: any other could be chosen among by whoever reveals it. The VRF account only keeps
+/// its current round, so one requested past the gem's round loses it.
+fn round_result(counter: u128, result: [u8; 32], round: u128) -> Result<[u8; 32], ProgramError> {
+    if counter < round {
+        Err(InglError::TooEarly.utilize(Some("The gem's VRF round hasn't been requested yet")))?
+    }
+    if counter > round {
+        Err(InglError::TooLate.utilize(Some("The gem's VRF round was superseded, renew it with a re-roll")))?
+    }
+    if result == [0; 32] {
+        Err(InglError::TooEarly.utilize(Some("VRF round not yet fulfilled")))?
+    }
+    Ok(result)
+}
+
+/// Reads the hash of the first slot at or after the commitment's reveal slot straight from the sysvar data,
+/// which is too large to deserialize whole. Entries are (slot: u64, hash: [u8; 32]), newest first, after a u64 length.
+fn slot_hash_entropy(slot_hashes_account_info: &AccountInfo, commit_slot: u64) -> Result<[u8; 32], ProgramError> {
+    assert_pubkeys_exactitude(&sysvar::slot_hashes::id(), slot_hashes_account_info.key)
+        .expect("Error: @slot_hashes_account_info");
+    let reveal_slot = commit_slot + SLOT_HASH_REVEAL_DELAY;
+
+    let data = slot_hashes_account_info.data.borrow();
+    let entries = u64::from_le_bytes(data[0..8].try_into().unwrap()) as usize;
+    let mut revealed: Option<(u64, [u8; 32])> = None;
+    let mut passed_reveal_slot = false;
+    for i in 0..entries {
+        let offset = 8 + i * 40;
+        let slot = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        if slot < reveal_slot {
+            passed_reveal_slot = true;
+            break;
+        }
+        revealed = Some((slot, data[offset + 8..offset + 40].try_into().unwrap()));
+    }
+
+    match revealed {
+        None => Err(InglError::TooEarly.utilize(Some("Reveal slot not yet reached")))?,
+        // A skipped reveal slot falls through to the next produced slot.
+        Some((slot, hash)) if passed_reveal_slot || slot == reveal_slot => Ok(hash),
+        // Every retained slot is newer than the reveal slot, whose hash has rolled out of the sysvar. Renewing the
+        // commitment for free would let a holder who dislikes the revealed hash wait for another, so it takes a re-roll.
+        Some(_) => Err(InglError::TooLate.utilize(Some("Slot hash commitment expired, renew it with a re-roll")))?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED_TIME: u32 = 1_660_000_000;
+
+    /// Lays out a switchboard history buffer: discriminator, insertion index, then (timestamp, mantissa, scale) rows.
+    fn history_buffer(rows: &[(i64, i128)]) -> Vec<u8> {
+        let mut data = b"BUFFERxx".to_vec();
+        data.extend_from_slice(&(rows.len() as u32 - 1).to_le_bytes());
+        for (timestamp, mantissa) in rows {
+            data.extend_from_slice(&timestamp.to_le_bytes());
+            data.extend_from_slice(&mantissa.to_le_bytes());
+            data.extend_from_slice(&0u32.to_le_bytes());
+        }
+        data
+    }
+
+    fn read_feed(rows: &[(i64, i128)], owner: &Pubkey, now: i64) -> Result<[u8; 16], ProgramError> {
+        let key = Pubkey::from_str(BTC_FEED_PUBLIC_KEY).unwrap();
+        let mut lamports = 0;
+        let mut data = history_buffer(rows);
+        let feed_account_info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, owner, false, 0);
+        feed_price(&feed_account_info, BTC_FEED_PUBLIC_KEY, BTC_FEED_MAX_STALENESS, SEED_TIME, now)
+    }
+
+    #[test]
+    fn reads_the_price_reported_before_the_seed_time() {
+        let seed = SEED_TIME as i64;
+        let rows = [(seed - 60, 21_000), (seed - 10, 21_500), (seed + 30, 22_000)];
+        let price = read_feed(&rows, &SWITCHBOARD_PROGRAM_ID, seed + 60).unwrap();
+        assert_eq!(price, 21_500i128.to_be_bytes());
+    }
+
+    #[test]
+    fn rejects_a_feed_whose_latest_report_is_stale() {
+        let seed = SEED_TIME as i64;
+        let rows = [(seed - 10, 21_500), (seed + 30, 22_000)];
+        let now = seed + 30 + BTC_FEED_MAX_STALENESS + 1;
+        assert_eq!(read_feed(&rows, &SWITCHBOARD_PROGRAM_ID, now), Err(InglError::StaleOracle.into()));
+    }
+
+    #[test]
+    fn rejects_a_seed_report_older_than_the_tolerance() {
+        let seed = SEED_TIME as i64;
+        let rows = [(seed - PRICE_REPORT_TOLERANCE - 1, 21_500), (seed + 30, 22_000)];
+        assert_eq!(read_feed(&rows, &SWITCHBOARD_PROGRAM_ID, seed + 60), Err(InglError::MissingOracleReport.into()));
+    }
+
+    #[test]
+    fn reveals_only_the_gems_own_vrf_round() {
+        let result = [7; 32];
+        assert_eq!(round_result(5, result, 5), Ok(result));
+        assert_eq!(round_result(4, result, 5), Err(InglError::TooEarly.into()));
+        assert_eq!(round_result(6, result, 5), Err(InglError::TooLate.into()));
+        assert_eq!(round_result(5, [0; 32], 5), Err(InglError::TooEarly.into()));
+    }
+
+    /// Lays out the slot hashes sysvar: a u64 length, then (slot, hash) entries, newest first.
+    fn slot_hashes(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+
+    fn reveal(slots: &[u64], commit_slot: u64) -> Result<[u8; 32], ProgramError> {
+        let key = sysvar::slot_hashes::id();
+        let mut lamports = 0;
+        let mut data = slot_hashes(slots);
+        let owner = sysvar::id();
+        let slot_hashes_account_info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        slot_hash_entropy(&slot_hashes_account_info, commit_slot)
+    }
+
+    #[test]
+    fn expired_slot_hash_commitments_are_not_renewed() {
+        let reveal_slot = 100 + SLOT_HASH_REVEAL_DELAY;
+        assert_eq!(reveal(&[reveal_slot + 1, reveal_slot, reveal_slot - 1], 100), Ok([reveal_slot as u8; 32]));
+        // A skipped reveal slot falls through to the next produced one.
+        assert_eq!(reveal(&[reveal_slot + 2, reveal_slot + 1, reveal_slot - 1], 100), Ok([(reveal_slot + 1) as u8; 32]));
+        assert_eq!(reveal(&[reveal_slot - 1], 100), Err(InglError::TooEarly.into()));
+        assert_eq!(reveal(&[reveal_slot + 2, reveal_slot + 1], 100), Err(InglError::TooLate.into()));
+    }
+
+    #[test]
+    fn rejects_a_feed_owned_by_another_program() {
+        let seed = SEED_TIME as i64;
+        let rows = [(seed - 10, 21_500), (seed + 30, 22_000)];
+        assert_eq!(read_feed(&rows, &Pubkey::new_unique(), seed + 60), Err(InglError::InvalidOracle.into()));
+    }
+}
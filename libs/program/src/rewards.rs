@@ -0,0 +1,390 @@
+use solana_program::{stake::config::DEFAULT_WARMUP_COOLDOWN_RATE, stake_history::StakeHistory};
+
+use crate::state::{
+    constants::{BASIS_POINTS, NFTS_SHARE, TEAM_SHARE, TREASURY_SHARE, VALIDATOR_ID_SHARE},
+    GemAccountV0_0_1, InglVoteAccountData, Rarity, RarityBonus, VoteRewards,
+};
+
+/// Percentages of a vote account's rewards paid out by process_rewards. Whatever they leave unassigned,
+/// along with the lamports lost to rounding down to whole percents, stays with the authorized withdrawer.
+#[derive(Clone, Copy, Debug)]
+pub struct RewardShares {
+    pub validator_id: u64,
+    pub treasury: u64,
+    pub team: u64,
+    pub nfts: u64,
+}
+
+pub const REWARD_SHARES: RewardShares = RewardShares {
+    validator_id: VALIDATOR_ID_SHARE,
+    treasury: TREASURY_SHARE,
+    team: TEAM_SHARE,
+    nfts: NFTS_SHARE,
+};
+
+/// Lamports each party receives out of one reward withdrawal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RewardSplit {
+    pub validator_id: u64,
+    pub team: u64,
+    pub treasury: u64, // Net of the rarity bonus pool.
+    pub rarity_bonus_pool: u64, // Held back from the treasury share, next to the NFTs share it tops up.
+    pub nfts: u64,
+}
+
+impl RewardShares {
+    pub fn split(&self, lamports: u64, rarity_bonus_pool_bps: u16) -> RewardSplit {
+        let one_percent = lamports / 100;
+        let treasury_rewards = one_percent.checked_mul(self.treasury).unwrap();
+        let rarity_bonus_pool = (treasury_rewards as u128 * rarity_bonus_pool_bps as u128 / BASIS_POINTS as u128) as u64;
+        RewardSplit {
+            validator_id: one_percent.checked_mul(self.validator_id).unwrap(),
+            team: one_percent.checked_mul(self.team).unwrap(),
+            treasury: treasury_rewards - rarity_bonus_pool,
+            rarity_bonus_pool,
+            nfts: one_percent.checked_mul(self.nfts).unwrap(),
+        }
+    }
+
+    /// Lamports owed to a gem for one reward snapshot, out of the NFTs share.
+    pub fn gem_epoch_reward(&self, class_lamports: u64, epoch_reward: &VoteRewards) -> u64 {
+        if epoch_reward.total_stake == 0 {
+            return 0;
+        }
+        (class_lamports as u128 * self.nfts as u128 * epoch_reward.total_reward as u128
+            / (100 * epoch_reward.total_stake as u128)) as u64
+    }
+
+    /// Lamports a gem delegated during `delegation_epoch` can claim from the snapshots taken after `interested_epoch`.
+    /// Snapshots whose reward epoch precedes the gem's first fully active epoch pay nothing.
+    pub fn gem_pending_rewards(
+        &self,
+        class_lamports: u64,
+        delegation_epoch: u64,
+        interested_epoch: u64,
+        vote_rewards: &[VoteRewards],
+        stake_history: &StakeHistory,
+    ) -> u64 {
+        let first_active = match first_active_epoch(delegation_epoch, stake_history) {
+            Some(epoch) => epoch,
+            None => return 0,
+        };
+        vote_rewards
+            .iter()
+            .filter(|epoch_reward| epoch_reward.epoch_number > interested_epoch)
+            .filter(|epoch_reward| reward_epoch(epoch_reward) >= first_active)
+            .fold(0u64, |total, epoch_reward| {
+                total
+                    .checked_add(self.gem_epoch_reward(class_lamports, epoch_reward))
+                    .unwrap()
+            })
+    }
+}
+
+/// First epoch in which stake delegated during `delegation_epoch` is fully effective, following the cluster's warmup in the stake history.
+/// Every delegation activating in a given epoch warms up at the same rate, so the result doesn't depend on the delegated amount.
+/// Returns None while the stake is still activating.
+pub fn first_active_epoch(delegation_epoch: u64, stake_history: &StakeHistory) -> Option<u64> {
+    let oldest_epoch = stake_history.iter().map(|(epoch, _)| *epoch).min()?;
+    let mut epoch = delegation_epoch;
+    loop {
+        match stake_history.get(epoch) {
+            Some(entry) => {
+                if entry.activating == 0
+                    || entry.effective as f64 * DEFAULT_WARMUP_COOLDOWN_RATE >= entry.activating as f64
+                {
+                    return Some(epoch + 1);
+                }
+            }
+            // Epochs older than the history are long past any warmup.
+            None if epoch < oldest_epoch => return Some(epoch + 1),
+            None => return None,
+        }
+        epoch += 1;
+    }
+}
+
+/// A reward snapshot taken during epoch `n` pays for the stake active during epoch `n - 1`.
+pub fn reward_epoch(epoch_reward: &VoteRewards) -> u64 {
+    epoch_reward.epoch_number.saturating_sub(1)
+}
+
+/// Lamports owed to a gem for one reward snapshot, out of the NFTs share.
+pub fn gem_epoch_reward(class_lamports: u64, epoch_reward: &VoteRewards) -> u64 {
+    REWARD_SHARES.gem_epoch_reward(class_lamports, epoch_reward)
+}
+
+/// Lamports a gem delegated during `delegation_epoch` can claim from the snapshots taken after `interested_epoch`.
+pub fn gem_pending_rewards(
+    class_lamports: u64,
+    delegation_epoch: u64,
+    interested_epoch: u64,
+    vote_rewards: &[VoteRewards],
+    stake_history: &StakeHistory,
+) -> u64 {
+    REWARD_SHARES.gem_pending_rewards(class_lamports, delegation_epoch, interested_epoch, vote_rewards, stake_history)
+}
+
+/// Bonus owed to a gem for one snapshot's rarity bonus pool. Weights are scaled against the largest bonus in force,
+/// so the pool would be exactly used up if all of the snapshot's stake held that rarity, and is never overdrawn.
+pub fn gem_epoch_rarity_bonus(class_lamports: u64, rarity: &Rarity, rarity_bonus: &RarityBonus) -> u64 {
+    let max_bonus_bps = *rarity_bonus.bonus_bps.iter().max().unwrap();
+    if rarity_bonus.total_stake == 0 || max_bonus_bps == 0 {
+        return 0;
+    }
+    (rarity_bonus.pool as u128 * class_lamports as u128 * rarity_bonus.bonus_bps[rarity.index()] as u128
+        / (rarity_bonus.total_stake as u128 * max_bonus_bps as u128)) as u64
+}
+
+/// Part of a snapshot's rarity bonus pool its gems can claim. Liquid gems are paid through the liquid reserve instead,
+/// and a snapshot with no active stake or no bonus in force pays none, so their share goes straight back to the treasury.
+pub fn claimable_rarity_bonus_pool(pool: u64, active_stake: u64, liquid_active_stake: u64, bonus_bps: &[u16; 5]) -> u64 {
+    if active_stake == 0 || *bonus_bps.iter().max().unwrap() == 0 {
+        return 0;
+    }
+    (pool as u128 * active_stake.saturating_sub(liquid_active_stake) as u128 / active_stake as u128) as u64
+}
+
+/// Index and amount of each rarity bonus a gem is owed, over the same snapshots as `gem_pending_rewards`. Unimprinted gems earn none.
+fn owed_rarity_bonuses<'r>(
+    class_lamports: u64,
+    rarity: &'r Option<Rarity>,
+    delegation_epoch: u64,
+    interested_epoch: u64,
+    rarity_bonuses: &'r [RarityBonus],
+    stake_history: &StakeHistory,
+) -> impl Iterator<Item = (usize, u64)> + 'r {
+    let earning = rarity.as_ref().zip(first_active_epoch(delegation_epoch, stake_history));
+    rarity_bonuses.iter().enumerate().filter_map(move |(index, rarity_bonus)| {
+        let (rarity, first_active) = earning?;
+        if rarity_bonus.epoch_number <= interested_epoch || rarity_bonus.epoch_number.saturating_sub(1) < first_active {
+            return None;
+        }
+        Some((index, gem_epoch_rarity_bonus(class_lamports, rarity, rarity_bonus)))
+    })
+}
+
+/// Rarity bonuses a gem can claim alongside `gem_pending_rewards`, over the same snapshots. Unimprinted gems earn none.
+pub fn gem_pending_rarity_bonus(
+    class_lamports: u64,
+    rarity: &Option<Rarity>,
+    delegation_epoch: u64,
+    interested_epoch: u64,
+    rarity_bonuses: &[RarityBonus],
+    stake_history: &StakeHistory,
+) -> u64 {
+    owed_rarity_bonuses(class_lamports, rarity, delegation_epoch, interested_epoch, rarity_bonuses, stake_history)
+        .fold(0u64, |total, (_, bonus)| total.checked_add(bonus).unwrap())
+}
+
+/// Epochs bounding the snapshots a delegated gem hasn't been paid for: its delegation epoch, and the last epoch it was paid through.
+fn claim_window(gem_data: &GemAccountV0_0_1) -> Option<(u64, u64)> {
+    let delegation_epoch = gem_data.last_delegation_epoch?;
+    let interested_epoch = gem_data.last_withdrawal_epoch.unwrap_or(delegation_epoch).max(delegation_epoch);
+    Some((delegation_epoch, interested_epoch))
+}
+
+/// Records the rarity bonuses `gem_claimable_rewards` pays the gem against their pools, so that the unclaimed rest can be swept.
+pub fn record_rarity_bonus_claims(
+    gem_data: &GemAccountV0_0_1,
+    ingl_vote_account_data: &mut InglVoteAccountData,
+    stake_history: &StakeHistory,
+) {
+    let (delegation_epoch, interested_epoch) = match claim_window(gem_data) {
+        Some(window) => window,
+        None => return,
+    };
+    let owed: Vec<(usize, u64)> = owed_rarity_bonuses(
+        gem_data.class.get_class_lamports(),
+        &gem_data.rarity,
+        delegation_epoch,
+        interested_epoch,
+        &ingl_vote_account_data.rarity_bonuses,
+        stake_history,
+    )
+    .collect();
+    for (index, bonus) in owed {
+        let rarity_bonus = &mut ingl_vote_account_data.rarity_bonuses[index];
+        rarity_bonus.claimed = rarity_bonus.claimed.checked_add(bonus).unwrap();
+    }
+}
+
+/// Rewards and rarity bonuses nft_withdraw would pay a delegated gem now. Snapshots up to the gem's last withdrawal have been paid.
+/// Gems that were never delegated have nothing to claim.
+pub fn gem_claimable_rewards(
+    gem_data: &GemAccountV0_0_1,
+    ingl_vote_account_data: &InglVoteAccountData,
+    stake_history: &StakeHistory,
+) -> u64 {
+    let (delegation_epoch, interested_epoch) = match claim_window(gem_data) {
+        Some(window) => window,
+        None => return 0,
+    };
+    let class_lamports = gem_data.class.get_class_lamports();
+    gem_pending_rewards(class_lamports, delegation_epoch, interested_epoch, &ingl_vote_account_data.vote_rewards, stake_history)
+        .checked_add(gem_pending_rarity_bonus(
+            class_lamports,
+            &gem_data.rarity,
+            delegation_epoch,
+            interested_epoch,
+            &ingl_vote_account_data.rarity_bonuses,
+            stake_history,
+        ))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{
+        constants::{INGL_VOTE_ACCOUNT_DATA_VAL_PHRASE, VOTE_REWARDS_VAL_PHRASE},
+        Class, FundsLocation, GemAccountVersions, RandomnessSource, Rarity, RebalancingData,
+    };
+    use solana_program::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, stake_history::StakeHistoryEntry};
+
+    fn snapshot(epoch_number: u64, total_reward: u64, total_stake: u64) -> VoteRewards {
+        VoteRewards { validation_phrase: VOTE_REWARDS_VAL_PHRASE, epoch_number, total_reward, total_stake }
+    }
+
+    fn vote_data(vote_rewards: Vec<VoteRewards>, rarity_bonuses: Vec<RarityBonus>) -> InglVoteAccountData {
+        InglVoteAccountData {
+            validation_phrase: INGL_VOTE_ACCOUNT_DATA_VAL_PHRASE,
+            total_delegated: 0,
+            last_withdraw_epoch: 0,
+            dealloced: 0,
+            rebalancing_data: RebalancingData { pending_validator_rewards: 0, unclaimed_validator_rewards: 0, is_rebalancing_active: false },
+            validator_id: Pubkey::new_unique(),
+            last_total_staked: 0,
+            is_t_stake_initialized: false,
+            pending_delegation_total: 0,
+            vote_rewards,
+            liquid_delegated: 0,
+            stake_activation_epoch: None,
+            active_stake: 0,
+            liquid_active_stake: 0,
+            pending_activations: vec![],
+            rarity_bonuses,
+            stake_tracking_initialized: true,
+        }
+    }
+
+    /// Stake history in which stake delegated during `warmup_epoch` is still warming up, and every other epoch is settled.
+    fn stake_history(warmup_epoch: u64) -> StakeHistory {
+        let mut stake_history = StakeHistory::default();
+        for epoch in 0..warmup_epoch + 2 {
+            let activating = if epoch == warmup_epoch { 100 * LAMPORTS_PER_SOL } else { 0 };
+            stake_history.add(epoch, StakeHistoryEntry { effective: 10 * LAMPORTS_PER_SOL, activating, deactivating: 0 });
+        }
+        stake_history
+    }
+
+    const BONUS_BPS: [u16; 5] = [0, 0, 500, 1000, 2000];
+
+    fn rarity_bonus(epoch_number: u64, pool: u64, total_stake: u64) -> RarityBonus {
+        RarityBonus { epoch_number, pool, total_stake, bonus_bps: BONUS_BPS, claimed: 0 }
+    }
+
+    fn delegated_gem(class: Class, delegation_epoch: u64, last_withdrawal_epoch: Option<u64>) -> GemAccountV0_0_1 {
+        GemAccountV0_0_1 {
+            struct_id: GemAccountVersions::GemAccountV0_0_1,
+            validation_phrase: 0,
+            date_created: 0,
+            class,
+            redeemable_date: 0,
+            numeration: 1,
+            rarity: None,
+            funds_location: FundsLocation::VoteAccount { vote_account_id: Pubkey::new_unique() },
+            rarity_seed_time: None,
+            date_allocated: Some(0),
+            last_voted_proposal: None,
+            last_withdrawal_epoch,
+            last_delegation_epoch: Some(delegation_epoch),
+            all_withdraws: Vec::new(),
+            all_votes: Vec::new(),
+            is_liquid: false,
+            auto_delegate: false,
+            compound_rewards: false,
+            randomness_source: RandomnessSource::SlotHash,
+            rarity_commit_slot: 0,
+            rarity_rolls: 0,
+            rarity_vrf_round: 0,
+        }
+    }
+
+    #[test]
+    fn splits_rewards_by_whole_percents() {
+        let split = REWARD_SHARES.split(1_000_099, 5_000);
+        assert_eq!(split.validator_id, 150_000);
+        assert_eq!(split.team, 120_000);
+        assert_eq!(split.treasury + split.rarity_bonus_pool, 130_000);
+        assert_eq!(split.rarity_bonus_pool, 65_000);
+        assert_eq!(split.nfts, 600_000);
+    }
+
+    #[test]
+    fn gems_earn_their_stake_share_of_the_nfts_share() {
+        let class_lamports = Class::Ruby.get_class_lamports();
+        let epoch_reward = snapshot(5, 1_000_000, 4 * class_lamports);
+        assert_eq!(gem_epoch_reward(class_lamports, &epoch_reward), 150_000);
+        assert_eq!(gem_epoch_reward(class_lamports, &snapshot(5, 1_000_000, 0)), 0);
+    }
+
+    #[test]
+    fn settles_only_snapshots_after_activation_and_the_last_withdrawal() {
+        let class_lamports = Class::Ruby.get_class_lamports();
+        let vote_rewards: Vec<VoteRewards> = (1..=6).map(|epoch| snapshot(epoch, 1_000_000, class_lamports)).collect();
+        // Delegated during epoch 2, which the cluster is still warming up through, so it is active from epoch 4 and first paid by the snapshot of epoch 5.
+        let stake_history = stake_history(2);
+        assert_eq!(first_active_epoch(2, &stake_history), Some(4));
+        assert_eq!(gem_pending_rewards(class_lamports, 2, 2, &vote_rewards, &stake_history), 2 * 600_000);
+        assert_eq!(gem_pending_rewards(class_lamports, 2, 5, &vote_rewards, &stake_history), 600_000);
+
+        let vote_data = vote_data(vote_rewards, vec![]);
+        assert_eq!(gem_claimable_rewards(&delegated_gem(Class::Ruby, 2, Some(5)), &vote_data, &stake_history), 600_000);
+        assert_eq!(gem_claimable_rewards(&delegated_gem(Class::Ruby, 2, Some(6)), &vote_data, &stake_history), 0);
+    }
+
+    #[test]
+    fn rarity_bonuses_never_exceed_their_pool() {
+        let class_lamports = Class::Ruby.get_class_lamports();
+        let pool = rarity_bonus(5, 1_000_000, 4 * class_lamports);
+        // A pool whose whole stake holds the top rarity pays out exactly, any other mix pays out less.
+        assert_eq!(gem_epoch_rarity_bonus(class_lamports, &Rarity::Mythic, &pool), 250_000);
+        let mixed: u64 = [Rarity::Common, Rarity::Rare, Rarity::Exalted, Rarity::Mythic]
+            .iter()
+            .map(|rarity| gem_epoch_rarity_bonus(class_lamports, rarity, &pool))
+            .sum();
+        assert_eq!(mixed, 437_500);
+        assert_eq!(gem_epoch_rarity_bonus(class_lamports, &Rarity::Mythic, &rarity_bonus(5, 1_000_000, 0)), 0);
+        let no_bonus = RarityBonus { bonus_bps: [0; 5], ..pool };
+        assert_eq!(gem_epoch_rarity_bonus(class_lamports, &Rarity::Mythic, &no_bonus), 0);
+    }
+
+    #[test]
+    fn liquid_stake_leaves_its_share_of_the_bonus_pool_unclaimable() {
+        let class_lamports = Class::Ruby.get_class_lamports();
+        assert_eq!(claimable_rarity_bonus_pool(1_000_000, 4 * class_lamports, class_lamports, &BONUS_BPS), 750_000);
+        assert_eq!(claimable_rarity_bonus_pool(1_000_000, 0, 0, &BONUS_BPS), 0);
+        assert_eq!(claimable_rarity_bonus_pool(1_000_000, 4 * class_lamports, 0, &[0; 5]), 0);
+    }
+
+    #[test]
+    fn records_the_rarity_bonuses_it_pays() {
+        let class_lamports = Class::Ruby.get_class_lamports();
+        let vote_rewards: Vec<VoteRewards> = (1..=6).map(|epoch| snapshot(epoch, 1_000_000, 4 * class_lamports)).collect();
+        let rarity_bonuses = vec![rarity_bonus(5, 1_000_000, 4 * class_lamports), rarity_bonus(6, 1_000_000, 4 * class_lamports)];
+        let mut vote_data = vote_data(vote_rewards, rarity_bonuses);
+        let stake_history = stake_history(2);
+        let gem = GemAccountV0_0_1 { rarity: Some(Rarity::Mythic), ..delegated_gem(Class::Ruby, 2, Some(4)) };
+
+        assert_eq!(gem_claimable_rewards(&gem, &vote_data, &stake_history), 2 * 150_000 + 2 * 250_000);
+        record_rarity_bonus_claims(&gem, &mut vote_data, &stake_history);
+        assert!(vote_data.rarity_bonuses.iter().all(|rarity_bonus| rarity_bonus.claimed == 250_000));
+
+        // Paid through epoch 6, the gem has nothing left to claim or record.
+        let paid = GemAccountV0_0_1 { last_withdrawal_epoch: Some(6), ..gem };
+        assert_eq!(gem_claimable_rewards(&paid, &vote_data, &stake_history), 0);
+        record_rarity_bonus_claims(&paid, &mut vote_data, &stake_history);
+        assert!(vote_data.rarity_bonuses.iter().all(|rarity_bonus| rarity_bonus.claimed == 250_000));
+    }
+}
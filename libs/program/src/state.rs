@@ -12,7 +12,7 @@ use solana_program::{
 
 use self::constants::*;
 pub mod constants {
-    use solana_program::{declare_id, native_token::LAMPORTS_PER_SOL};
+    use solana_program::{declare_id, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
     declare_id!("5izbLh4Xk5mE9XneotmVGfWpBM1xZMK7fkdJxKr9k8MQ");
 
     pub const INGL_TREASURY_ACCOUNT_KEY: &str = "ingl_treasury_account_key";
@@ -22,15 +22,38 @@ pub mod constants {
     pub const COLLECTION_HOLDER_KEY: &str = "collection_holder";
     pub const GLOBAL_GEM_KEY: &str = "global_gem_account";
     pub const GEM_ACCOUNT_CONST: &str = "gem_account";
-    pub const FEE_MULTIPLYER: u8 = 10;
     pub const PRICE_TIME_INTERVAL: u8 = 20;
     pub const TREASURY_FEE_MULTIPLYER: u8 = 50;
     pub const MAXIMUM_DELEGATABLE_STAKE: u64 = 5 * LAMPORTS_PER_SOL; //10_000 * LAMPORTS_PER_SOL;
     pub const ALLOCATE_LOCK_TIME: u32 = /*86400**/1*365*2; //Needs to be changed back to 86400*... before deployment on mainnet. reduced for testing purposes during development
+    // Devnet builds read the devnet switchboard deployment and its feeds.
+    #[cfg(not(feature = "mainnet"))]
     pub const BTC_FEED_PUBLIC_KEY: &str = "9ATrvi6epR5hVYtwNs7BB7VCiYnd4WM7e8MfafWpfiXC";
+    #[cfg(not(feature = "mainnet"))]
     pub const SOL_FEED_PUBLIC_KEY: &str = "7LLvRhMs73FqcLkA8jvEE1AM2mYZXTmqfUv8GAEurymx";
+    #[cfg(not(feature = "mainnet"))]
     pub const ETH_FEED_PUBLIC_KEY: &str = "6fhxFvPocWapZ5Wa2miDnrX2jYRFKvFqYnX11GGkBo2f";
+    #[cfg(not(feature = "mainnet"))]
     pub const BNB_FEED_PUBLIC_KEY: &str = "DR6PqK15tD21MEGSLmDpXwLA7Fw47kwtdZeUMdT7vd7L";
+    #[cfg(not(feature = "mainnet"))]
+    pub const SWITCHBOARD_PROGRAM_ID: Pubkey = switchboard_v2::SWITCHBOARD_V2_DEVNET;
+    // Mainnet builds take the mainnet history buffers of the same feeds at build time, e.g. INGL_BTC_FEED=<key> cargo build-bpf --features mainnet.
+    #[cfg(feature = "mainnet")]
+    pub const BTC_FEED_PUBLIC_KEY: &str = env!("INGL_BTC_FEED");
+    #[cfg(feature = "mainnet")]
+    pub const SOL_FEED_PUBLIC_KEY: &str = env!("INGL_SOL_FEED");
+    #[cfg(feature = "mainnet")]
+    pub const ETH_FEED_PUBLIC_KEY: &str = env!("INGL_ETH_FEED");
+    #[cfg(feature = "mainnet")]
+    pub const BNB_FEED_PUBLIC_KEY: &str = env!("INGL_BNB_FEED");
+    #[cfg(feature = "mainnet")]
+    pub const SWITCHBOARD_PROGRAM_ID: Pubkey = switchboard_v2::SWITCHBOARD_V2_MAINNET;
+    // Longest gap, in seconds, allowed between a feed's latest report and the imprint. Feeds update at different rates.
+    pub const BTC_FEED_MAX_STALENESS: i64 = 300;
+    pub const SOL_FEED_MAX_STALENESS: i64 = 300;
+    pub const ETH_FEED_MAX_STALENESS: i64 = 300;
+    pub const BNB_FEED_MAX_STALENESS: i64 = 900;
+    pub const PRICE_REPORT_TOLERANCE: i64 = 120; // How long before a gem's seed time the report its rarity uses may be.
     pub const PD_POOL_KEY: &str = "pd_pool";
     pub const PROPOSAL_KEY: &str = "ingl_proposals";
     pub const COUNCIL_MINT_KEY: &str = "council_mint";
@@ -39,10 +62,42 @@ pub mod constants {
     pub const VOTE_ACCOUNT_KEY: &str = "InglVote";
     pub const VOTE_DATA_ACCOUNT_KEY: &str = "InglVoteData";
     pub const STAKE_ACCOUNT_KEY: &str = "staking_account_key";
-    pub const TREASURY_ACCOUNT_KEY: &str = "Treasury_account_key";
+    pub const TREASURY_ACCOUNT_KEY: &str = "Treasury_account_key"; // Legacy reward treasury, swept into INGL_TREASURY_ACCOUNT_KEY by migrate_legacy_treasury.
     pub const T_STAKE_ACCOUNT_KEY: &str = "Temporary_stake_account_key";
     pub const T_WITHDRAW_KEY: &str = "Temporary_withdraw";
     pub const DUPKEYBYTES: &[u8] = b"dupkey";
+    pub const LIQUID_MINT_KEY: &str = "ingl_liquid_mint";
+    pub const LIQUID_MINT_AUTHORITY_KEY: &str = "ingl_liquid_mint_authority";
+    pub const LIQUID_STAKE_DATA_KEY: &str = "ingl_liquid_stake_data";
+    pub const LIQUID_RESERVE_KEY: &str = "ingl_liquid_reserve";
+    pub const LIQUID_MINT_DECIMALS: u8 = 9;
+    pub const COMPOUND_RESERVE_KEY: &str = "ingl_compound_reserve";
+    pub const FRACTIONAL_VAULT_KEY: &str = "ingl_fractional_vault";
+    pub const VAULT_SHARE_MINT_KEY: &str = "ingl_vault_share_mint";
+    pub const VAULT_SHARE_ACCOUNT_KEY: &str = "ingl_vault_share_account";
+    pub const VAULT_SHARES_SUPPLY: u64 = 1_000_000;
+    pub const VAULT_SHARE_DECIMALS: u8 = 0;
+    pub const VAULT_REWARDS_PRECISION: u128 = 1_000_000_000_000;
+    pub const PROTOCOL_CONFIG_KEY: &str = "ingl_protocol_config";
+    pub const LISTING_KEY: &str = "ingl_listing";
+    pub const ALLOWLIST_ALLOCATION_KEY: &str = "ingl_allowlist_allocation";
+    pub const BASIS_POINTS: u64 = 10_000;
+    pub const MAX_BATCH_MINT: usize = 2; // Each gem adds a mint signature and five accounts, and a third one overflows a legacy transaction.
+    pub const METADATA_REGISTRY_KEY: &str = "ingl_metadata_registry";
+    pub const MAX_REDEEM_FEE_POINTS: usize = 8;
+    // (seconds since mint, fee in basis points) points of the redeem fee used until one is configured, tracing 10% * sqrt(1 - t^2) over a year.
+    pub const DEFAULT_REDEEM_FEE_SCHEDULE: [(u32, u16); 5] = [(0, 1000), (15_768_000, 866), (23_652_000, 661), (28_382_400, 436), (31_536_000, 0)];
+    pub const TREASURY_PROPOSAL_KEY: &str = "ingl_treasury_proposal";
+    pub const TREASURY_VOTE_KEY: &str = "ingl_treasury_vote";
+    pub const TREASURY_PROPOSAL_VOTING_PERIOD: u32 = 86400 * 7;
+    pub const TREASURY_PROPOSAL_TIMELOCK: u32 = 86400 * 2; // Delay between the end of voting and a passed proposal becoming executable.
+    pub const TREASURY_PROPOSAL_QUORUM: u64 = 1_000_000_000_000; // Class lamports of approving gems a proposal needs to pass.
+    pub const MAX_TREASURY_MEMO_LENGTH: usize = 200;
+    pub const TEAM_VAULT_KEY: &str = "ingl_team_vault"; // Collects team revenue, kept apart from the mint authority's operational balance.
+    pub const TEAM_VESTING_KEY: &str = "ingl_team_vesting";
+    pub const MAX_VESTING_TRANCHES: usize = 32; // Team vault revenue vesting at once. Further revenue joins the newest tranche.
+    pub const SLOT_HASH_REVEAL_DELAY: u64 = 10; // Slots between a slot hash commitment and the slot whose hash reveals it.
+    pub const RARITY_BONUS_CLAIM_EPOCHS: u64 = 150; // Epochs a rarity bonus pool stays claimable before its unclaimed lamports can be swept back to the treasury.
 
     pub const VALIDATOR_ID_SHARE: u64 = 15;
     pub const TREASURY_SHARE: u64 = 13;
@@ -55,6 +110,17 @@ pub mod constants {
     pub const INGL_VOTE_ACCOUNT_DATA_VAL_PHRASE: u32 = 842_154_348;
     pub const GEM_ACCOUNT_VAL_PHRASE: u32 = 516_248_961;
     pub const VOTE_REWARDS_VAL_PHRASE: u32 = 584_625_418;
+    pub const LIQUID_STAKE_DATA_VAL_PHRASE: u32 = 731_486_295;
+    pub const COMPOUND_RESERVE_VAL_PHRASE: u32 = 465_921_387;
+    pub const FRACTIONAL_VAULT_VAL_PHRASE: u32 = 328_574_916;
+    pub const VAULT_SHARE_ACCOUNT_VAL_PHRASE: u32 = 917_236_458;
+    pub const PROTOCOL_CONFIG_VAL_PHRASE: u32 = 603_194_827;
+    pub const LISTING_VAL_PHRASE: u32 = 251_847_693;
+    pub const ALLOWLIST_ALLOCATION_VAL_PHRASE: u32 = 846_372_519;
+    pub const METADATA_REGISTRY_VAL_PHRASE: u32 = 573_918_264;
+    pub const TREASURY_PROPOSAL_VAL_PHRASE: u32 = 692_481_537;
+    pub const TREASURY_VOTE_VAL_PHRASE: u32 = 318_765_249;
+    pub const TEAM_VESTING_VAL_PHRASE: u32 = 847_215_396;
 
     pub mod spl_program {
         use solana_program::declare_id;
@@ -94,6 +160,9 @@ pub enum Class {
 }
 
 impl Class {
+    /// Every class, in declaration order.
+    pub const ALL: [Class; 6] = [Self::Ruby, Self::Diamond, Self::Sapphire, Self::Emerald, Self::Serendibite, Self::Benitoite];
+
     pub fn get_class_lamports(self) -> u64 {
         LAMPORTS_PER_SOL
             * match self {
@@ -105,6 +174,26 @@ impl Class {
                 Self::Benitoite => 1,
             }
     }
+
+    /// Position of the class in per-class arrays, following declaration order.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The most valuable class `lamports` can pay for, if any. Classes are declared from the most to the least valuable.
+    pub fn largest_affordable(lamports: u64) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|class| class.get_class_lamports() <= lamports)
+    }
+}
+
+/// Minting limits for one class. Zeroes leave the class uncapped and its window open.
+#[derive(BorshDeserialize, BorshSerialize, Copy, Clone, Default)]
+pub struct ClassLimit {
+    pub max_supply: u32,
+    pub mint_open: u32,
+    pub mint_close: u32,
 }
 
 #[derive(BorshDeserialize, Debug, BorshSerialize, Clone)]
@@ -115,6 +204,20 @@ pub enum Rarity {
     Exalted,
     Mythic,
 }
+impl Rarity {
+    /// Position of the rarity in per-rarity arrays, following declaration order.
+    pub fn index(&self) -> usize {
+        self.clone() as usize
+    }
+}
+
+/// Where imprinted rarities draw their randomness from. Selected in the protocol config and recorded on each gem when its imprint is initialized.
+#[derive(BorshDeserialize, Debug, BorshSerialize, Copy, Clone, PartialEq)]
+pub enum RandomnessSource {
+    PriceFeeds, // Hash of the switchboard BTC, SOL, ETH and BNB prices at the gem's seed time.
+    SwitchboardVrf, // Result of the configured switchboard VRF account's first round requested after the gem's commitment.
+    SlotHash, // Hash of the slot SLOT_HASH_REVEAL_DELAY slots after the gem's commitment.
+}
 
 #[derive(BorshDeserialize, Clone, BorshSerialize)]
 pub struct GlobalGems {
@@ -129,6 +232,14 @@ pub struct GlobalGems {
     pub pending_delegation_total: u64,
     pub validator_list: Vec<Pubkey>, //This is not the validator list to display for proposals check instead the ValidatorProposal Struct.
                                      // pub winners_list: Vec<u32>, // To include next so as to
+    pub class_minted: [u32; 6], // Gems minted per class (indexed by Class::index) since per-class counting was introduced.
+    // The statistics below count from their introduction, so gems that predate them are missing, and removals saturate at zero,
+    // until the config authority sets them from a snapshot through set_gem_statistics.
+    pub class_supply: [u32; 6], // Gems currently in existence per class.
+    pub class_rarity_supply: [[u32; 5]; 6], // Imprinted gems currently in existence per class and rarity (indexed by Rarity::index).
+    pub class_allocated: [u64; 6], // Class lamports of each class's gems sitting in the PD pool.
+    pub class_delegated: [u64; 6], // Class lamports of each class's gems delegated to vote accounts.
+    pub treasury_proposal_numeration: u32,
 }
 impl GlobalGems {
     pub fn validate(self) -> Result<Self, ProgramError> {
@@ -142,9 +253,87 @@ impl GlobalGems {
         let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
         a.validate()
     }
+
+    pub fn add_gem(&mut self, class: Class) {
+        self.class_supply[class.index()] = self.class_supply[class.index()].checked_add(1).unwrap();
+    }
+
+    pub fn remove_gem(&mut self, class: Class, rarity: &Option<Rarity>) {
+        self.class_supply[class.index()] = self.class_supply[class.index()].saturating_sub(1);
+        if let Some(rarity) = rarity {
+            self.remove_rarity(class, rarity);
+        }
+    }
+
+    pub fn add_rarity(&mut self, class: Class, rarity: &Rarity) {
+        let count = &mut self.class_rarity_supply[class.index()][rarity.index()];
+        *count = count.checked_add(1).unwrap();
+    }
+
+    pub fn remove_rarity(&mut self, class: Class, rarity: &Rarity) {
+        let count = &mut self.class_rarity_supply[class.index()][rarity.index()];
+        *count = count.saturating_sub(1);
+    }
+
+    /// Adds a gem's class lamports to the per-class total of where they sit. The minting pool isn't tracked per class.
+    pub fn add_class_funds(&mut self, class: Class, location: &FundsLocation) {
+        let lamports = class.get_class_lamports();
+        match location {
+            FundsLocation::MintingPool => {}
+            FundsLocation::PDPool => {
+                self.class_allocated[class.index()] = self.class_allocated[class.index()].checked_add(lamports).unwrap();
+            }
+            FundsLocation::VoteAccount { .. } => {
+                self.class_delegated[class.index()] = self.class_delegated[class.index()].checked_add(lamports).unwrap();
+            }
+        }
+    }
+
+    pub fn remove_class_funds(&mut self, class: Class, location: &FundsLocation) {
+        let lamports = class.get_class_lamports();
+        match location {
+            FundsLocation::MintingPool => {}
+            FundsLocation::PDPool => {
+                self.class_allocated[class.index()] = self.class_allocated[class.index()].saturating_sub(lamports);
+            }
+            FundsLocation::VoteAccount { .. } => {
+                self.class_delegated[class.index()] = self.class_delegated[class.index()].saturating_sub(lamports);
+            }
+        }
+    }
+
+    /// Replaces the per-class statistics with a snapshot of every gem, e.g. replayed by the indexer, after checking that
+    /// each class's imprinted, allocated and delegated gems fit within its supply.
+    pub fn set_statistics(
+        &mut self,
+        class_supply: [u32; 6],
+        class_rarity_supply: [[u32; 5]; 6],
+        class_allocated: [u64; 6],
+        class_delegated: [u64; 6],
+    ) -> Result<(), ProgramError> {
+        for class in Class::ALL {
+            let (index, lamports) = (class.index(), class.get_class_lamports());
+            let supply = class_supply[index] as u64;
+            let imprinted: u64 = class_rarity_supply[index].iter().map(|count| *count as u64).sum();
+            if imprinted > supply {
+                Err(InglError::BeyondBounds.utilize(Some("imprinted gems beyond the class supply")))?
+            }
+            if !class_allocated[index].is_multiple_of(lamports) || !class_delegated[index].is_multiple_of(lamports) {
+                Err(InglError::BeyondBounds.utilize(Some("class funds that aren't whole gems")))?
+            }
+            if (class_allocated[index] + class_delegated[index]) / lamports > supply {
+                Err(InglError::BeyondBounds.utilize(Some("allocated gems beyond the class supply")))?
+            }
+        }
+        self.class_supply = class_supply;
+        self.class_rarity_supply = class_rarity_supply;
+        self.class_allocated = class_allocated;
+        self.class_delegated = class_delegated;
+        Ok(())
+    }
 }
 
-#[derive(BorshDeserialize,  Debug, BorshSerialize)]
+#[derive(BorshDeserialize,  Debug, BorshSerialize, Clone, PartialEq)]
 pub enum FundsLocation {
     MintingPool,
     PDPool,
@@ -188,6 +377,13 @@ pub struct GemAccountV0_0_1 {
     pub last_delegation_epoch: Option<u64>,
     pub all_withdraws: Vec<u64>,
     pub all_votes: Vec<ValidatorVote>,
+    pub is_liquid: bool, // True while the gem's NFT is escrowed against liquid staking tokens.
+    pub auto_delegate: bool, // Opts the gem in to being delegated from the PD pool by the auto delegation crank.
+    pub compound_rewards: bool, // Credits the gem's rewards to its holder's compound reserve instead of paying them out.
+    pub randomness_source: RandomnessSource, // Source the gem's rarity is drawn from, fixed when its imprint is initialized.
+    pub rarity_commit_slot: u64, // Slot at which the gem's imprint was initialized, revealed by a later slot hash.
+    pub rarity_rolls: u32, // Paid re-rolls of the gem's rarity.
+    pub rarity_vrf_round: u128, // VRF round revealing the gem's rarity: the first requested after its imprint was committed.
 }
 impl GemAccountV0_0_1 {
     pub fn validate(account_data: Self) -> Result<Self, ProgramError> {
@@ -196,6 +392,62 @@ impl GemAccountV0_0_1 {
         }
         Ok(account_data)
     }
+
+    /// Copies the state a converted gem inherits from the gem(s) it replaces, creation date included, so conversions keep the
+    /// gem's place on the redeem fee curve and its right to vote on treasury proposals made after it was first minted.
+    /// The rarity imprint is not inherited: the new mint has to run init_rarity_imprint again, so its seed is committed to only once the mint it mixes in is fixed.
+    pub fn conversion_template(&self) -> Self {
+        GemAccountV0_0_1 {
+            struct_id: GemAccountVersions::GemAccountV0_0_1,
+            validation_phrase: GEM_ACCOUNT_VAL_PHRASE,
+            date_created: self.date_created,
+            class: self.class,
+            redeemable_date: self.redeemable_date,
+            numeration: self.numeration,
+            rarity: None,
+            funds_location: self.funds_location.clone(),
+            rarity_seed_time: None,
+            date_allocated: self.date_allocated,
+            last_voted_proposal: self.last_voted_proposal,
+            last_withdrawal_epoch: self.last_withdrawal_epoch,
+            last_delegation_epoch: self.last_delegation_epoch,
+            all_withdraws: Vec::new(),
+            all_votes: Vec::new(),
+            is_liquid: false,
+            auto_delegate: self.auto_delegate,
+            compound_rewards: self.compound_rewards,
+            randomness_source: self.randomness_source,
+            rarity_commit_slot: 0,
+            rarity_rolls: 0,
+            rarity_vrf_round: 0,
+        }
+    }
+
+    /// Folds another gem of the same class into a merge template. Merged gems must sit in the same place and have identical
+    /// reward state, so that the merged gem accrues exactly their combined rewards. Dates take the latest of the two, so merging
+    /// can't shorten a lock, lower a redeem fee or open a treasury vote.
+    pub fn merge(&mut self, gem_data: &GemAccountV0_0_1) -> Result<(), ProgramError> {
+        if self.class.get_class_lamports() != gem_data.class.get_class_lamports() {
+            Err(InglError::InvalidConversion.utilize(Some("Merged gems must share a class")))?
+        }
+        if self.funds_location != gem_data.funds_location {
+            Err(InglError::InvalidFundsLocation.utilize(Some("Merged gems must share a funds location")))?
+        }
+        if self.last_delegation_epoch != gem_data.last_delegation_epoch
+            || self.last_withdrawal_epoch != gem_data.last_withdrawal_epoch
+        {
+            Err(InglError::InvalidConversion.utilize(Some("Merged gems must share delegation and withdrawal epochs")))?
+        }
+        if self.last_voted_proposal != gem_data.last_voted_proposal {
+            Err(InglError::InvalidConversion.utilize(Some("Merged gems must share their last voted proposal")))?
+        }
+        self.date_created = self.date_created.max(gem_data.date_created);
+        self.redeemable_date = self.redeemable_date.max(gem_data.redeemable_date);
+        self.date_allocated = self.date_allocated.max(gem_data.date_allocated);
+        self.auto_delegate = self.auto_delegate && gem_data.auto_delegate;
+        self.compound_rewards = self.compound_rewards && gem_data.compound_rewards;
+        Ok(())
+    }
 }
 
 #[derive(BorshDeserialize, Debug,  BorshSerialize)]
@@ -244,6 +496,57 @@ impl ValidatorProposal {
     }
 }
 
+/// A request to pay `amount` lamports out of the treasury, decided by gem holders.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TreasuryProposal {
+    pub validation_phrase: u32,
+    pub numeration: u32,
+    pub proposer: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub memo: String,
+    pub date_created: u32,
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub date_executed: Option<u32>,
+}
+impl TreasuryProposal {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != TREASURY_PROPOSAL_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("Treasury Proposal")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+
+    pub fn voting_ends(&self) -> u32 {
+        self.date_created + TREASURY_PROPOSAL_VOTING_PERIOD
+    }
+
+    /// A passed proposal can be executed once the timelock following its vote has elapsed.
+    pub fn executable_from(&self) -> u32 {
+        self.voting_ends() + TREASURY_PROPOSAL_TIMELOCK
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.votes_for > self.votes_against && self.votes_for >= TREASURY_PROPOSAL_QUORUM
+    }
+}
+
+/// Marks a gem as having voted on a treasury proposal. Keyed by gem mint, so transferring the gem doesn't allow a second vote.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TreasuryVote {
+    pub validation_phrase: u32,
+    pub proposal: Pubkey,
+    pub gem_mint: Pubkey,
+    pub approve: bool,
+    pub weight: u64,
+}
+
 #[derive(BorshDeserialize, Copy, Clone, BorshSerialize)]
 pub struct VoteRewards {
     pub validation_phrase: u32,
@@ -264,6 +567,17 @@ impl VoteRewards {
         a.validate()
     }
 }
+/// Lamports held back from a reward snapshot's treasury share to pay rarity bonuses to the non-liquid gems active during it.
+/// Whatever is left unclaimed after RARITY_BONUS_CLAIM_EPOCHS goes back to the treasury.
+#[derive(BorshDeserialize, Copy, Clone, BorshSerialize)]
+pub struct RarityBonus {
+    pub epoch_number: u64, // Epoch of the matching VoteRewards snapshot.
+    pub pool: u64,
+    pub total_stake: u64, // Non-liquid active stake, the only stake that can claim the pool.
+    pub bonus_bps: [u16; 5], // Reward bonus per rarity (indexed by Rarity::index) in force when the snapshot was taken.
+    pub claimed: u64, // Lamports of the pool paid out to gems so far.
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct InglVoteAccountData {
     pub validation_phrase: u32,
@@ -276,6 +590,13 @@ pub struct InglVoteAccountData {
     pub is_t_stake_initialized: bool,
     pub pending_delegation_total: u64,
     pub vote_rewards: Vec<VoteRewards>,
+    pub liquid_delegated: u64, // Class lamports of the delegated gems currently backing liquid staking tokens.
+    pub stake_activation_epoch: Option<u64>, // Epoch the pooled stake account was last delegated to the vote account.
+    pub active_stake: u64, // Class lamports of the delegated gems whose stake is fully active, used for reward snapshots.
+    pub liquid_active_stake: u64,
+    pub pending_activations: Vec<StakeCohort>, // Delegated stake still warming up, grouped by delegation epoch.
+    pub rarity_bonuses: Vec<RarityBonus>, // Snapshots that funded a rarity bonus pool.
+    pub stake_tracking_initialized: bool, // False on vote data created before active stake tracking, until it is migrated.
 }
 impl InglVoteAccountData {
     pub fn validate(self) -> Result<Self, ProgramError> {
@@ -287,10 +608,95 @@ impl InglVoteAccountData {
     pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
         assert_program_owned(account).unwrap();
         let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
-        a.validate()
+        let mut a = a.validate()?;
+        a.migrate_stake_tracking();
+        Ok(a)
+    }
+
+    /// Vote data created before active stake tracking holds no active stake, and its delegated gems, which are long active,
+    /// are only counted in total_delegated. Counts them as active the first time the data is decoded, leaving out the cohorts
+    /// delegated since the upgrade, which are still tracked as pending.
+    pub fn migrate_stake_tracking(&mut self) {
+        if self.stake_tracking_initialized {
+            return;
+        }
+        let pending: u64 = self.pending_activations.iter().map(|cohort| cohort.lamports).sum();
+        let liquid_pending: u64 = self.pending_activations.iter().map(|cohort| cohort.liquid_lamports).sum();
+        self.active_stake = self.total_delegated.saturating_sub(pending);
+        self.liquid_active_stake = self.liquid_delegated.saturating_sub(liquid_pending);
+        self.stake_tracking_initialized = true;
+    }
+
+    /// All gems delegated in the same epoch activate together, so a gem's stake is counted in active_stake once its cohort left the pending list.
+    pub fn is_stake_active(&self, delegation_epoch: u64) -> bool {
+        !self
+            .pending_activations
+            .iter()
+            .any(|cohort| cohort.delegation_epoch == delegation_epoch)
+    }
+
+    pub fn add_pending_activation(&mut self, delegation_epoch: u64, lamports: u64, liquid_lamports: u64) {
+        match self
+            .pending_activations
+            .iter_mut()
+            .find(|cohort| cohort.delegation_epoch == delegation_epoch)
+        {
+            Some(cohort) => {
+                cohort.lamports = cohort.lamports.checked_add(lamports).unwrap();
+                cohort.liquid_lamports = cohort.liquid_lamports.checked_add(liquid_lamports).unwrap();
+            }
+            None => self.pending_activations.push(StakeCohort {
+                delegation_epoch,
+                lamports,
+                liquid_lamports,
+            }),
+        }
+    }
+
+    pub fn remove_pending_activation(&mut self, delegation_epoch: u64, lamports: u64, liquid_lamports: u64) {
+        let index = self
+            .pending_activations
+            .iter()
+            .position(|cohort| cohort.delegation_epoch == delegation_epoch)
+            .unwrap();
+        let cohort = &mut self.pending_activations[index];
+        cohort.lamports = cohort.lamports.checked_sub(lamports).unwrap();
+        cohort.liquid_lamports = cohort.liquid_lamports.checked_sub(liquid_lamports).unwrap();
+        if cohort.lamports == 0 {
+            self.pending_activations.remove(index);
+        }
+    }
+
+    /// Class lamports the vote account can still take, pending delegations included. None while it is rebalancing.
+    pub fn remaining_capacity(&self) -> Option<u64> {
+        if self.rebalancing_data.is_rebalancing_active {
+            return None;
+        }
+        Some(MAXIMUM_DELEGATABLE_STAKE.saturating_sub(self.total_delegated))
     }
 }
 
+/// Index of the vote account auto delegation sends a gem of `class_lamports` to: the one with the most remaining capacity
+/// that can take it, the first one sent on ties.
+pub fn auto_delegation_target<'a>(vote_data: impl Iterator<Item = &'a InglVoteAccountData>, class_lamports: u64) -> Option<usize> {
+    vote_data
+        .enumerate()
+        .filter_map(|(index, data)| Some((index, data.remaining_capacity()?)))
+        .filter(|(_, remaining)| *remaining >= class_lamports)
+        .fold(None, |max: Option<(usize, u64)>, (index, remaining)| match max {
+            Some((_, max_remaining)) if max_remaining >= remaining => max,
+            _ => Some((index, remaining)),
+        })
+        .map(|(index, _)| index)
+}
+
+#[derive(BorshDeserialize, Copy, Clone, BorshSerialize)]
+pub struct StakeCohort {
+    pub delegation_epoch: u64,
+    pub lamports: u64,
+    pub liquid_lamports: u64,
+}
+
 pub struct VoteState {}
 impl VoteState {
     pub fn space() -> usize {
@@ -307,4 +713,868 @@ pub struct RebalancingData {
     pub pending_validator_rewards: u64,
     pub unclaimed_validator_rewards: u64,
     pub is_rebalancing_active: bool,    
-}
\ No newline at end of file
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LiquidStakeData {
+    pub validation_phrase: u32,
+    pub total_gem_lamports: u64, // Class lamports of all gems escrowed against liquid tokens.
+    pub accrued_rewards: u64,    // Rewards credited to the liquid reserve by process_rewards.
+    pub total_supply: u64,
+    pub auto_delegate: bool, // Lets the auto delegation crank delegate liquid gems from the PD pool. Set by the config authority.
+}
+impl LiquidStakeData {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != LIQUID_STAKE_DATA_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("liquid stake data")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+
+    pub fn total_lamports(&self) -> u64 {
+        self.total_gem_lamports.checked_add(self.accrued_rewards).unwrap()
+    }
+
+    /// Rewards left in the reserve once every liquid token has been burnt. No holder can redeem them, and the first mint
+    /// at a 1:1 rate would hand them to the next liquifier, so liquify_nft sweeps them to the treasury first.
+    pub fn orphaned_rewards(&self) -> u64 {
+        if self.total_supply == 0 {
+            self.accrued_rewards
+        } else {
+            0
+        }
+    }
+
+    /// Amount of liquid tokens worth `lamports` at the current exchange rate, 1:1 while none are outstanding. Rounds down.
+    pub fn lamports_to_tokens(&self, lamports: u64) -> u64 {
+        if self.total_supply == 0 || self.total_lamports() == 0 {
+            return lamports;
+        }
+        (lamports as u128 * self.total_supply as u128 / self.total_lamports() as u128) as u64
+    }
+
+    /// Amount of liquid tokens that must be burnt to take back `lamports`. Rounds up, so the exchange rate never drops.
+    pub fn tokens_to_burn(&self, lamports: u64) -> u64 {
+        if self.total_supply == 0 || self.total_lamports() == 0 {
+            return lamports;
+        }
+        let total_lamports = self.total_lamports() as u128;
+        (lamports as u128 * self.total_supply as u128).div_ceil(total_lamports) as u64
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct CompoundReserve {
+    pub validation_phrase: u32,
+    pub owner: Pubkey,
+    pub balance: u64, // Compounded rewards, excluding the account's rent.
+    pub gems_minted: u32,
+}
+impl CompoundReserve {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != COMPOUND_RESERVE_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("compound reserve")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FractionalVault {
+    pub validation_phrase: u32,
+    pub gem_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub deposited_shares: u64, // Shares escrowed by holders to earn rewards.
+    pub rewards_per_share: u128, // Cumulative rewards per share of the whole supply, scaled by VAULT_REWARDS_PRECISION.
+    pub undistributed_rewards: u64, // Rewards earned by shares held in wallets, taken pro rata as those shares are deposited.
+}
+impl FractionalVault {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != FRACTIONAL_VAULT_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("fractional vault")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+
+    /// Spreads harvested rewards over the whole share supply. Deposited shares accrue theirs through rewards_per_share, and
+    /// the part earned by shares held in wallets is pooled until those shares are deposited.
+    pub fn distribute(&mut self, rewards: u64) {
+        let supply = VAULT_SHARES_SUPPLY as u128;
+        let held_shares = VAULT_SHARES_SUPPLY.checked_sub(self.deposited_shares).unwrap() as u128;
+        self.rewards_per_share = self
+            .rewards_per_share
+            .checked_add(rewards as u128 * VAULT_REWARDS_PRECISION / supply)
+            .unwrap();
+        self.undistributed_rewards = self
+            .undistributed_rewards
+            .checked_add((rewards as u128 * held_shares / supply) as u64)
+            .unwrap();
+    }
+
+    /// Takes the pooled rewards of `shares` leaving wallets for the vault, an even part of what all wallet-held shares earned.
+    /// Must be called before the shares are added to deposited_shares.
+    pub fn take_held_rewards(&mut self, shares: u64) -> u64 {
+        let held_shares = VAULT_SHARES_SUPPLY.checked_sub(self.deposited_shares).unwrap();
+        if held_shares == 0 {
+            return 0;
+        }
+        let taken = (self.undistributed_rewards as u128 * shares as u128 / held_shares as u128) as u64;
+        self.undistributed_rewards = self.undistributed_rewards.checked_sub(taken).unwrap();
+        taken
+    }
+
+    pub fn accrued(&self, shares: u64) -> u128 {
+        shares as u128 * self.rewards_per_share / VAULT_REWARDS_PRECISION
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct VaultShareAccount {
+    pub validation_phrase: u32,
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub shares: u64,
+    pub reward_debt: u128, // Rewards already accounted for on the deposited shares.
+}
+impl VaultShareAccount {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != VAULT_SHARE_ACCOUNT_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("vault share account")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+
+    pub fn pending_rewards(&self, vault: &FractionalVault) -> u64 {
+        vault.accrued(self.shares).saturating_sub(self.reward_debt) as u64
+    }
+}
+
+/// One point of the piecewise-linear redeem fee curve, anchored on the gem's mint date.
+#[derive(BorshDeserialize, BorshSerialize, Copy, Clone, Debug)]
+pub struct RedeemFeePoint {
+    pub elapsed: u32, // Seconds since the gem was minted.
+    pub fee_bps: u16, // Share of the class lamports kept as fee at that point.
+}
+
+/// Protocol parameters adjustable by the program's upgrade authority. New fields are appended so older accounts decode with zeroed defaults.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ProtocolConfig {
+    pub validation_phrase: u32,
+    pub marketplace_fee_bps: u16, // Share of each marketplace sale sent to the treasury, in basis points.
+    pub presale_merkle_root: [u8; 32], // Root of the (wallet, allocation) allowlist. All zeroes disables the presale.
+    pub presale_start: u32,
+    pub public_mint_start: u32, // Public minting opens at this timestamp, which also ends the presale.
+    pub class_limits: [ClassLimit; 6], // Indexed by Class::index.
+    pub randomness_source: RandomnessSource, // Source new rarity imprints draw from.
+    pub vrf_account: Pubkey, // Switchboard VRF account read by the SwitchboardVrf source.
+    pub reroll_fee: u64, // Lamports charged per rarity re-roll. Zero disables re-rolls.
+    pub reroll_treasury_bps: u16, // Share of the re-roll fee sent to the treasury, the rest goes to NFT rewards.
+    pub rarity_bonus_pool_bps: u16, // Share of each snapshot's treasury cut held back to pay rarity reward bonuses.
+    pub rarity_reward_bonus_bps: [u16; 5], // Reward weight added per rarity, indexed by Rarity::index.
+    pub rarity_vote_bonus_bps: [u16; 5], // Proposal vote weight added per rarity, indexed by Rarity::index.
+    pub redeem_fee_schedule: Vec<RedeemFeePoint>, // Empty until configured, in which case DEFAULT_REDEEM_FEE_SCHEDULE applies.
+    pub reroll_rewards_vote_account: Pubkey, // Ingl vote account whose delegated gems receive the rewards share of re-roll fees.
+}
+impl ProtocolConfig {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != PROTOCOL_CONFIG_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("protocol config")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+
+    pub fn is_presale_open(&self, now: u32) -> bool {
+        self.presale_merkle_root != [0; 32] && now >= self.presale_start && now < self.public_mint_start
+    }
+
+    pub fn is_public_mint_open(&self, now: u32) -> bool {
+        now >= self.public_mint_start
+    }
+
+    /// Fee for redeeming a gem `elapsed` seconds after its mint. The fee is interpolated between the schedule's points,
+    /// and holds at the first or last point's fee outside of them.
+    pub fn redeem_fee(&self, class_lamports: u64, elapsed: u32) -> u64 {
+        let schedule: Vec<RedeemFeePoint> = if self.redeem_fee_schedule.is_empty() {
+            DEFAULT_REDEEM_FEE_SCHEDULE
+                .iter()
+                .map(|(elapsed, fee_bps)| RedeemFeePoint { elapsed: *elapsed, fee_bps: *fee_bps })
+                .collect()
+        } else {
+            self.redeem_fee_schedule.clone()
+        };
+
+        let first = schedule[0];
+        let last = schedule[schedule.len() - 1];
+        let fee_bps = if elapsed <= first.elapsed {
+            first.fee_bps as u64
+        } else if elapsed >= last.elapsed {
+            last.fee_bps as u64
+        } else {
+            let pair = schedule.windows(2).find(|pair| elapsed < pair[1].elapsed).unwrap();
+            let (start, end) = (pair[0], pair[1]);
+            let progress = (elapsed - start.elapsed) as i64;
+            let span = (end.elapsed - start.elapsed) as i64;
+            (start.fee_bps as i64 + (end.fee_bps as i64 - start.fee_bps as i64) * progress / span) as u64
+        };
+        (class_lamports as u128 * fee_bps as u128 / BASIS_POINTS as u128) as u64
+    }
+
+    /// Proposal votes a gem casts, its class weight raised by its rarity's vote bonus.
+    pub fn vote_weight(&self, class: Class, rarity: &Option<Rarity>) -> u32 {
+        let base = class.get_class_lamports() / LAMPORTS_PER_SOL;
+        let bonus_bps = match rarity {
+            Some(rarity) => self.rarity_vote_bonus_bps[rarity.index()] as u64,
+            None => 0,
+        };
+        (base * (BASIS_POINTS + bonus_bps) / BASIS_POINTS) as u32
+    }
+
+    /// Part of a marketplace sale's price sent to the treasury. The seller receives the rest.
+    pub fn marketplace_fee(&self, price: u64) -> u64 {
+        (price as u128 * self.marketplace_fee_bps as u128 / BASIS_POINTS as u128) as u64
+    }
+
+    pub fn is_class_mint_open(&self, class: Class, now: u32) -> bool {
+        let limit = self.class_limits[class.index()];
+        now >= limit.mint_open && (limit.mint_close == 0 || now < limit.mint_close)
+    }
+
+    /// Checks the class's mint window and its supply cap, given the gems of the class minted so far.
+    /// Both bound every mint of the class, conversions and compounded mints included.
+    pub fn assert_class_mint_allowed(&self, class: Class, class_minted: u32, now: u32) -> Result<(), ProgramError> {
+        if !self.is_class_mint_open(class, now) {
+            Err(InglError::TooEarly.utilize(Some("Minting of this class isn't open")))?
+        }
+        let max_supply = self.class_limits[class.index()].max_supply;
+        if max_supply != 0 && class_minted >= max_supply {
+            Err(InglError::BeyondBounds.utilize(Some("Class supply cap reached")))?
+        }
+        Ok(())
+    }
+
+    /// Checks the mint phase, then the class's mint window and its supply cap, before a payer-funded mint.
+    pub fn assert_mint_allowed(&self, class: Class, class_minted: u32, presale: bool, now: u32) -> Result<(), ProgramError> {
+        if presale && !self.is_presale_open(now) {
+            Err(InglError::TooLate.utilize(Some("Presale isn't open")))?
+        }
+        if !presale && !self.is_public_mint_open(now) {
+            Err(InglError::TooEarly.utilize(Some("Public mint isn't open yet")))?
+        }
+        self.assert_class_mint_allowed(class, class_minted, now)
+    }
+}
+
+/// Splits a redeem fee into its treasury and team vault parts, the treasury taking `treasury_fee_multiplyer` percent.
+pub fn split_redeem_fee(redeem_fees: u64, treasury_fee_multiplyer: u8) -> (u64, u64) {
+    let treasury_funds = redeem_fees * treasury_fee_multiplyer as u64 / 100;
+    (treasury_funds, redeem_fees - treasury_funds)
+}
+
+/// A gem listed on the marketplace. Listings are found by filtering program accounts on LISTING_VAL_PHRASE.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Listing {
+    pub validation_phrase: u32,
+    pub seller: Pubkey,
+    pub gem_mint: Pubkey,
+    pub price: u64,
+    pub listed_at: u32,
+}
+impl Listing {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != LISTING_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("listing")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+}
+
+/// Presale mints consumed by a wallet under a given allowlist root.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AllowlistAllocation {
+    pub validation_phrase: u32,
+    pub wallet: Pubkey,
+    pub minted: u32,
+}
+impl AllowlistAllocation {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != ALLOWLIST_ALLOCATION_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("allowlist allocation")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+
+    /// Records one more presale mint against the wallet's `allocation`, failing once it is used up.
+    pub fn consume(&mut self, allocation: u32) -> Result<(), ProgramError> {
+        if self.minted >= allocation {
+            Err(InglError::BeyondBounds.utilize(Some("allowlist allocation already consumed")))?
+        }
+        self.minted = self.minted.checked_add(1).unwrap();
+        Ok(())
+    }
+}
+
+/// Revenue the team vault took in between two claims, vesting linearly over the schedule's duration from `start`.
+#[derive(BorshDeserialize, BorshSerialize, Copy, Clone, Debug, PartialEq)]
+pub struct VestingTranche {
+    pub start: u32,
+    pub lamports: u64,
+}
+
+/// Vesting schedule of the team vault. Each part of the vault's revenue vests linearly over `duration`, from the later of
+/// `start` and the claim that first sees it, and nothing can be claimed before `start + cliff`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TeamVesting {
+    pub validation_phrase: u32,
+    pub beneficiary: Pubkey,
+    pub start: u32,
+    pub cliff: u32,
+    pub duration: u32,
+    pub claimed: u64,
+    pub tranches: Vec<VestingTranche>, // Revenue still vesting, oldest first.
+    pub matured: u64, // Revenue of the tranches that fully vested, which are dropped.
+    pub recorded_intake: u64, // Vault revenue, its balance plus past claims, already split into tranches.
+}
+impl TeamVesting {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != TEAM_VESTING_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("team vesting")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+
+    pub const SPACE: usize = 4 + 32 + 4 + 4 + 4 + 8 + (4 + MAX_VESTING_TRANCHES * 12) + 8 + 8;
+
+    /// Starts vesting the revenue the vault took in since the last claim, given its balance above its rent-exempt minimum,
+    /// and folds the tranches that fully vested into `matured`. Once MAX_VESTING_TRANCHES are vesting, new revenue joins
+    /// the newest tranche, whose start moves up to now, so no revenue ever vests sooner than its own schedule.
+    pub fn record_intake(&mut self, vault_balance: u64, now: u32) {
+        let (matured, vesting): (Vec<VestingTranche>, Vec<VestingTranche>) =
+            self.tranches.iter().partition(|tranche| now >= tranche.start.saturating_add(self.duration));
+        self.matured = matured.iter().fold(self.matured, |total, tranche| total.checked_add(tranche.lamports).unwrap());
+        self.tranches = vesting;
+
+        let intake = vault_balance.checked_add(self.claimed).unwrap();
+        let revenue = intake.saturating_sub(self.recorded_intake);
+        self.recorded_intake = self.recorded_intake.max(intake);
+        if revenue == 0 {
+            return;
+        }
+        let start = now.max(self.start);
+        let full = self.tranches.len() >= MAX_VESTING_TRANCHES;
+        match self.tranches.last_mut() {
+            Some(newest) if newest.start == start || full => {
+                newest.start = start;
+                newest.lamports = newest.lamports.checked_add(revenue).unwrap();
+            }
+            _ if self.duration == 0 => self.matured = self.matured.checked_add(revenue).unwrap(),
+            _ => self.tranches.push(VestingTranche { start, lamports: revenue }),
+        }
+    }
+
+    pub fn vested(&self, now: u32) -> u64 {
+        self.tranches.iter().fold(self.matured, |total, tranche| {
+            let elapsed = now.saturating_sub(tranche.start).min(self.duration);
+            let vested = tranche.lamports as u128 * elapsed as u128 / self.duration as u128;
+            total.checked_add(vested as u64).unwrap()
+        })
+    }
+
+    /// Lamports claimable now, given the vault's balance above its rent-exempt minimum, once its revenue is recorded.
+    pub fn claimable(&self, vault_balance: u64, now: u32) -> u64 {
+        if now < self.start.saturating_add(self.cliff) {
+            return 0;
+        }
+        self.vested(now).saturating_sub(self.claimed).min(vault_balance)
+    }
+}
+
+/// Metadata templates for gem NFTs. Uris are indexed by nfts::registry_index; empty entries use the built-in links.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MetadataRegistry {
+    pub validation_phrase: u32,
+    pub name_prefix: String,
+    pub symbol: String,
+    pub uris: Vec<String>,
+    pub gem_uri_base: String, // Serves each gem's JSON at "<base>/<numeration>.json". Empty until set, and on registries that predate it.
+}
+impl MetadataRegistry {
+    pub fn validate(self) -> Result<Self, ProgramError> {
+        if self.validation_phrase != METADATA_REGISTRY_VAL_PHRASE {
+            Err(InglError::InvalidValPhrase.utilize(Some("metadata registry")))?
+        }
+        Ok(self)
+    }
+    pub fn decode(account: &AccountInfo) -> Result<Self, ProgramError> {
+        assert_program_owned(account).unwrap();
+        let a: Self = try_from_slice_unchecked(&account.data.borrow()).unwrap();
+        a.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn liquid_stake_data(total_gem_lamports: u64, accrued_rewards: u64, total_supply: u64) -> LiquidStakeData {
+        LiquidStakeData { validation_phrase: LIQUID_STAKE_DATA_VAL_PHRASE, total_gem_lamports, accrued_rewards, total_supply, auto_delegate: false }
+    }
+
+    fn vote_data(total_delegated: u64, is_rebalancing_active: bool) -> InglVoteAccountData {
+        InglVoteAccountData {
+            validation_phrase: INGL_VOTE_ACCOUNT_DATA_VAL_PHRASE,
+            total_delegated,
+            last_withdraw_epoch: 0,
+            dealloced: 0,
+            rebalancing_data: RebalancingData { pending_validator_rewards: 0, unclaimed_validator_rewards: 0, is_rebalancing_active },
+            validator_id: Pubkey::new_unique(),
+            last_total_staked: 0,
+            is_t_stake_initialized: false,
+            pending_delegation_total: 0,
+            vote_rewards: vec![],
+            liquid_delegated: 0,
+            stake_activation_epoch: None,
+            active_stake: 0,
+            liquid_active_stake: 0,
+            pending_activations: vec![],
+            rarity_bonuses: vec![],
+            stake_tracking_initialized: true,
+        }
+    }
+
+    #[test]
+    fn auto_delegates_to_the_vote_account_with_the_most_room() {
+        let fifth = MAXIMUM_DELEGATABLE_STAKE / 5;
+        let vote_accounts = [vote_data(4 * fifth, false), vote_data(fifth, true), vote_data(2 * fifth, false), vote_data(2 * fifth, false)];
+        // The rebalancing vote account has the most room, and the first of the two tied ones is picked.
+        assert_eq!(auto_delegation_target(vote_accounts.iter(), fifth), Some(2));
+        assert_eq!(auto_delegation_target(vote_accounts.iter(), 3 * fifth), Some(2));
+        assert_eq!(auto_delegation_target(vote_accounts.iter(), 4 * fifth), None);
+        assert_eq!(vote_accounts[1].remaining_capacity(), None);
+    }
+
+    #[test]
+    fn tracks_stake_cohorts_until_they_activate() {
+        let mut vote_data = vote_data(0, false);
+        vote_data.add_pending_activation(7, 10, 0);
+        vote_data.add_pending_activation(7, 5, 5);
+        vote_data.add_pending_activation(8, 1, 0);
+        assert!(!vote_data.is_stake_active(7));
+        assert!(vote_data.is_stake_active(6));
+
+        vote_data.remove_pending_activation(7, 5, 5);
+        assert_eq!((vote_data.pending_activations[0].lamports, vote_data.pending_activations[0].liquid_lamports), (10, 0));
+        vote_data.remove_pending_activation(7, 10, 0);
+        assert!(vote_data.is_stake_active(7));
+        assert_eq!(vote_data.pending_activations.len(), 1);
+    }
+
+    #[test]
+    fn migrates_legacy_vote_data_to_active_stake_tracking() {
+        let mut legacy = vote_data(3 * LAMPORTS_PER_SOL, false);
+        legacy.stake_tracking_initialized = false;
+        // A gem delegated after the upgrade but before the first snapshot.
+        legacy.add_pending_activation(9, LAMPORTS_PER_SOL, 0);
+        legacy.migrate_stake_tracking();
+        assert_eq!(legacy.active_stake, 2 * LAMPORTS_PER_SOL);
+        assert!(legacy.stake_tracking_initialized);
+
+        // Migrated data is left alone, even once all of its stake is gone.
+        legacy.total_delegated = 0;
+        legacy.active_stake = 0;
+        legacy.migrate_stake_tracking();
+        assert_eq!(legacy.active_stake, 0);
+    }
+
+    #[test]
+    fn prices_liquid_tokens_against_accrued_rewards() {
+        let liquid_stake_data = liquid_stake_data(10 * LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL);
+        assert_eq!(liquid_stake_data.lamports_to_tokens(2 * LAMPORTS_PER_SOL), LAMPORTS_PER_SOL);
+        assert_eq!(liquid_stake_data.tokens_to_burn(3), 2);
+        assert_eq!(liquid_stake_data.orphaned_rewards(), 0);
+    }
+
+    #[test]
+    fn rewards_left_without_liquid_tokens_are_orphaned() {
+        assert_eq!(liquid_stake_data(0, 5_000, 0).orphaned_rewards(), 5_000);
+        assert_eq!(liquid_stake_data(0, 0, 0).lamports_to_tokens(LAMPORTS_PER_SOL), LAMPORTS_PER_SOL);
+    }
+
+    fn global_gems() -> GlobalGems {
+        GlobalGems {
+            validation_phrase: GLOBAL_GEMS_VAL_PHRASE,
+            counter: 0,
+            total_raised: 0,
+            pd_pool_total: 0,
+            delegated_total: 0,
+            dealloced_total: 0,
+            is_proposal_ongoing: false,
+            proposal_numeration: 0,
+            pending_delegation_total: 0,
+            validator_list: Vec::new(),
+            class_minted: [0; 6],
+            class_supply: [0; 6],
+            class_rarity_supply: [[0; 5]; 6],
+            class_allocated: [0; 6],
+            class_delegated: [0; 6],
+            treasury_proposal_numeration: 0,
+        }
+    }
+
+    #[test]
+    fn gem_statistics_snapshots_must_fit_the_class_supply() {
+        let ruby = Class::Ruby.get_class_lamports();
+        let mut class_rarity_supply = [[0; 5]; 6];
+        class_rarity_supply[Class::Ruby.index()] = [1, 0, 1, 0, 1];
+        let mut global_gems = global_gems();
+        global_gems.set_statistics([3, 0, 0, 0, 0, 0], class_rarity_supply, [ruby, 0, 0, 0, 0, 0], [2 * ruby, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(global_gems.class_supply[Class::Ruby.index()], 3);
+        // Removals now count down from the snapshot rather than saturating.
+        global_gems.remove_gem(Class::Ruby, &Some(Rarity::Mythic));
+        assert_eq!(global_gems.class_rarity_supply[Class::Ruby.index()], [1, 0, 1, 0, 0]);
+
+        let beyond_supply = global_gems.set_statistics([2, 0, 0, 0, 0, 0], class_rarity_supply, [0; 6], [0; 6]);
+        assert_eq!(beyond_supply, Err(InglError::BeyondBounds.into()));
+        let partial_gem = global_gems.set_statistics([3, 0, 0, 0, 0, 0], class_rarity_supply, [ruby / 2, 0, 0, 0, 0, 0], [0; 6]);
+        assert_eq!(partial_gem, Err(InglError::BeyondBounds.into()));
+        let over_allocated = global_gems.set_statistics([3, 0, 0, 0, 0, 0], class_rarity_supply, [2 * ruby, 0, 0, 0, 0, 0], [2 * ruby, 0, 0, 0, 0, 0]);
+        assert_eq!(over_allocated, Err(InglError::BeyondBounds.into()));
+        assert_eq!(global_gems.class_supply[Class::Ruby.index()], 2);
+    }
+
+    fn gem(class: Class, date_created: u32) -> GemAccountV0_0_1 {
+        GemAccountV0_0_1 {
+            struct_id: GemAccountVersions::GemAccountV0_0_1,
+            validation_phrase: GEM_ACCOUNT_VAL_PHRASE,
+            date_created,
+            class,
+            redeemable_date: date_created + ALLOCATE_LOCK_TIME,
+            numeration: 1,
+            rarity: Some(Rarity::Rare),
+            funds_location: FundsLocation::PDPool,
+            rarity_seed_time: Some(date_created),
+            date_allocated: Some(date_created),
+            last_voted_proposal: None,
+            last_withdrawal_epoch: None,
+            last_delegation_epoch: None,
+            all_withdraws: Vec::new(),
+            all_votes: Vec::new(),
+            is_liquid: false,
+            auto_delegate: true,
+            compound_rewards: false,
+            randomness_source: RandomnessSource::SlotHash,
+            rarity_commit_slot: 7,
+            rarity_rolls: 2,
+            rarity_vrf_round: 0,
+        }
+    }
+
+    #[test]
+    fn conversions_keep_the_creation_date_and_drop_the_imprint() {
+        let template = gem(Class::Ruby, 1_000).conversion_template();
+        assert_eq!(template.date_created, 1_000);
+        assert_eq!(template.redeemable_date, 1_000 + ALLOCATE_LOCK_TIME);
+        assert!(template.rarity.is_none() && template.rarity_seed_time.is_none());
+        assert_eq!((template.rarity_commit_slot, template.rarity_rolls), (0, 0));
+    }
+
+    #[test]
+    fn merges_take_the_latest_dates_of_matching_gems() {
+        let mut template = gem(Class::Ruby, 1_000).conversion_template();
+        let mut younger = gem(Class::Ruby, 2_000);
+        younger.auto_delegate = false;
+        template.merge(&younger).unwrap();
+        assert_eq!(template.date_created, 2_000);
+        assert_eq!(template.redeemable_date, 2_000 + ALLOCATE_LOCK_TIME);
+        assert_eq!(template.date_allocated, Some(2_000));
+        assert!(!template.auto_delegate);
+
+        assert!(template.merge(&gem(Class::Sapphire, 1_000)).is_err());
+        let mut elsewhere = gem(Class::Ruby, 1_000);
+        elsewhere.funds_location = FundsLocation::MintingPool;
+        assert!(template.merge(&elsewhere).is_err());
+        let mut voted = gem(Class::Ruby, 1_000);
+        voted.last_voted_proposal = Some(Pubkey::new_unique());
+        assert!(template.merge(&voted).is_err());
+    }
+
+    #[test]
+    fn vault_rewards_follow_shares_in_and_out_of_wallets() {
+        let mut vault = FractionalVault {
+            validation_phrase: FRACTIONAL_VAULT_VAL_PHRASE,
+            gem_mint: Pubkey::new_unique(),
+            share_mint: Pubkey::new_unique(),
+            deposited_shares: 0,
+            rewards_per_share: 0,
+            undistributed_rewards: 0,
+        };
+        // Harvested while every share sits in a wallet: the first depositor of a quarter of the supply takes a quarter.
+        vault.distribute(4_000_000);
+        assert_eq!(vault.take_held_rewards(VAULT_SHARES_SUPPLY / 4), 1_000_000);
+        vault.deposited_shares = VAULT_SHARES_SUPPLY / 4;
+        let depositor = VaultShareAccount {
+            validation_phrase: VAULT_SHARE_ACCOUNT_VAL_PHRASE,
+            vault: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            shares: VAULT_SHARES_SUPPLY / 4,
+            reward_debt: vault.accrued(VAULT_SHARES_SUPPLY / 4),
+        };
+
+        // Later rewards split between the depositor and the shares still held in wallets.
+        vault.distribute(4_000_000);
+        assert_eq!(depositor.pending_rewards(&vault), 1_000_000);
+        assert_eq!(vault.undistributed_rewards, 6_000_000);
+        // A reclaimer holding every remaining share gets exactly what those shares earned.
+        assert_eq!(vault.take_held_rewards(VAULT_SHARES_SUPPLY * 3 / 4), 6_000_000);
+    }
+
+    fn protocol_config() -> ProtocolConfig {
+        ProtocolConfig {
+            validation_phrase: PROTOCOL_CONFIG_VAL_PHRASE,
+            marketplace_fee_bps: 0,
+            presale_merkle_root: [0; 32],
+            presale_start: 0,
+            public_mint_start: 0,
+            class_limits: [ClassLimit::default(); 6],
+            randomness_source: RandomnessSource::PriceFeeds,
+            vrf_account: Pubkey::default(),
+            reroll_fee: 0,
+            reroll_treasury_bps: 0,
+            rarity_bonus_pool_bps: 0,
+            rarity_reward_bonus_bps: [0; 5],
+            rarity_vote_bonus_bps: [0; 5],
+            redeem_fee_schedule: Vec::new(),
+            reroll_rewards_vote_account: Pubkey::default(),
+        }
+    }
+
+    fn team_vesting() -> TeamVesting {
+        TeamVesting {
+            validation_phrase: TEAM_VESTING_VAL_PHRASE,
+            beneficiary: Pubkey::new_unique(),
+            start: 1_000,
+            cliff: 100,
+            duration: 1_000,
+            claimed: 0,
+            tranches: Vec::new(),
+            matured: 0,
+            recorded_intake: 0,
+        }
+    }
+
+    /// Claims from a vault whose balance is `vault`, as claim_team_vesting does.
+    fn claim(team_vesting: &mut TeamVesting, vault: &mut u64, now: u32) -> u64 {
+        team_vesting.record_intake(*vault, now);
+        let claimable = team_vesting.claimable(*vault, now);
+        team_vesting.claimed += claimable;
+        *vault -= claimable;
+        claimable
+    }
+
+    #[test]
+    fn each_deposit_vests_over_its_own_schedule() {
+        let mut team_vesting = team_vesting();
+        let mut vault = 1_000;
+        assert_eq!(claim(&mut team_vesting, &mut vault, 1_000), 0);
+        assert_eq!(claim(&mut team_vesting, &mut vault, 1_099), 0); // Before the cliff.
+        assert_eq!(claim(&mut team_vesting, &mut vault, 1_500), 500);
+
+        // A deposit halfway through the schedule starts vesting from zero rather than half vested.
+        vault += 1_000;
+        assert_eq!(claim(&mut team_vesting, &mut vault, 1_500), 0);
+        assert_eq!(claim(&mut team_vesting, &mut vault, 2_000), 500 + 500);
+        assert_eq!(team_vesting.tranches, vec![VestingTranche { start: 1_500, lamports: 1_000 }]);
+        assert_eq!(team_vesting.matured, 1_000);
+
+        // Nor is revenue arriving after the schedule's end claimable at once.
+        vault += 1_000;
+        assert_eq!(claim(&mut team_vesting, &mut vault, 5_000), 500);
+        assert_eq!(claim(&mut team_vesting, &mut vault, 5_250), 250);
+        assert_eq!(claim(&mut team_vesting, &mut vault, 6_000), 750);
+        assert_eq!((vault, team_vesting.claimed, team_vesting.recorded_intake), (0, 3_000, 3_000));
+    }
+
+    #[test]
+    fn team_vesting_tranches_are_bounded() {
+        let mut team_vesting = team_vesting();
+        let mut vault = 0;
+        for now in 1_100..1_100 + MAX_VESTING_TRANCHES as u32 + 5 {
+            vault += 100;
+            claim(&mut team_vesting, &mut vault, now);
+        }
+        assert_eq!(team_vesting.tranches.len(), MAX_VESTING_TRANCHES);
+        // The overflow joined the newest tranche, which restarted with it.
+        let newest = team_vesting.tranches.last().unwrap();
+        assert_eq!((newest.start, newest.lamports), (1_100 + MAX_VESTING_TRANCHES as u32 + 4, 600));
+        assert_eq!(team_vesting.try_to_vec().unwrap().len(), TeamVesting::SPACE);
+    }
+
+    fn treasury_proposal(votes_for: u64, votes_against: u64) -> TreasuryProposal {
+        TreasuryProposal {
+            validation_phrase: TREASURY_PROPOSAL_VAL_PHRASE,
+            numeration: 0,
+            proposer: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            amount: LAMPORTS_PER_SOL,
+            memo: String::new(),
+            date_created: 1_000,
+            votes_for,
+            votes_against,
+            date_executed: None,
+        }
+    }
+
+    #[test]
+    fn treasury_proposals_pass_with_a_majority_reaching_quorum() {
+        assert!(treasury_proposal(TREASURY_PROPOSAL_QUORUM, 0).has_passed());
+        assert!(treasury_proposal(TREASURY_PROPOSAL_QUORUM, TREASURY_PROPOSAL_QUORUM - 1).has_passed());
+        assert!(!treasury_proposal(TREASURY_PROPOSAL_QUORUM - 1, 0).has_passed());
+        assert!(!treasury_proposal(TREASURY_PROPOSAL_QUORUM, TREASURY_PROPOSAL_QUORUM).has_passed());
+    }
+
+    #[test]
+    fn treasury_proposals_execute_only_after_the_timelock() {
+        let proposal = treasury_proposal(TREASURY_PROPOSAL_QUORUM, 0);
+        assert_eq!(proposal.voting_ends(), 1_000 + TREASURY_PROPOSAL_VOTING_PERIOD);
+        assert_eq!(proposal.executable_from(), proposal.voting_ends() + TREASURY_PROPOSAL_TIMELOCK);
+        assert!(proposal.executable_from() > proposal.voting_ends());
+    }
+
+    #[test]
+    fn redeem_fees_follow_the_schedule() {
+        let ruby = Class::Ruby.get_class_lamports();
+        let mut config = protocol_config();
+        // Without a configured schedule, DEFAULT_REDEEM_FEE_SCHEDULE applies: 10% at mint, free after a year.
+        assert_eq!(config.redeem_fee(ruby, 0), ruby / 10);
+        assert_eq!(config.redeem_fee(ruby, 7_884_000), ruby * 933 / BASIS_POINTS);
+        assert_eq!(config.redeem_fee(ruby, 15_768_000), ruby * 866 / BASIS_POINTS);
+        assert_eq!(config.redeem_fee(ruby, 31_536_000), 0);
+        assert_eq!(config.redeem_fee(ruby, u32::MAX), 0);
+
+        config.redeem_fee_schedule = vec![RedeemFeePoint { elapsed: 100, fee_bps: 500 }, RedeemFeePoint { elapsed: 200, fee_bps: 100 }];
+        assert_eq!(config.redeem_fee(ruby, 50), ruby * 500 / BASIS_POINTS);
+        assert_eq!(config.redeem_fee(ruby, 150), ruby * 300 / BASIS_POINTS);
+        assert_eq!(config.redeem_fee(ruby, 300), ruby * 100 / BASIS_POINTS);
+    }
+
+    #[test]
+    fn marketplace_fee_never_exceeds_the_price() {
+        let mut config = protocol_config();
+        assert_eq!(config.marketplace_fee(LAMPORTS_PER_SOL), 0);
+        config.marketplace_fee_bps = 250;
+        assert_eq!(config.marketplace_fee(LAMPORTS_PER_SOL), LAMPORTS_PER_SOL / 40);
+        assert_eq!(config.marketplace_fee(39), 0);
+        config.marketplace_fee_bps = BASIS_POINTS as u16;
+        assert_eq!(config.marketplace_fee(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn presale_runs_until_the_public_mint_when_a_root_is_set() {
+        let mut config = protocol_config();
+        config.presale_start = 100;
+        config.public_mint_start = 200;
+        assert!(!config.is_presale_open(150));
+        config.presale_merkle_root = [7; 32];
+        assert!(!config.is_presale_open(99));
+        assert!(config.is_presale_open(100) && config.is_presale_open(199));
+        assert!(!config.is_presale_open(200));
+        assert!(!config.is_public_mint_open(199) && config.is_public_mint_open(200));
+    }
+
+    #[test]
+    fn class_caps_and_windows_bound_every_mint() {
+        let mut config = protocol_config();
+        config.presale_merkle_root = [7; 32];
+        config.presale_start = 100;
+        config.public_mint_start = 200;
+        config.class_limits[Class::Ruby.index()] = ClassLimit { max_supply: 3, mint_open: 150, mint_close: 300 };
+
+        // Phases only gate payer-funded mints.
+        assert!(config.assert_mint_allowed(Class::Ruby, 0, false, 199).is_err());
+        assert!(config.assert_mint_allowed(Class::Ruby, 0, true, 200).is_err());
+        assert!(config.assert_mint_allowed(Class::Ruby, 0, true, 150).is_ok());
+        assert!(config.assert_mint_allowed(Class::Ruby, 0, false, 200).is_ok());
+        // The class window and cap bound conversions and compounded mints too.
+        assert!(config.assert_class_mint_allowed(Class::Ruby, 0, 149).is_err());
+        assert!(config.assert_class_mint_allowed(Class::Ruby, 0, 300).is_err());
+        assert!(config.assert_class_mint_allowed(Class::Ruby, 2, 250).is_ok());
+        assert!(config.assert_class_mint_allowed(Class::Ruby, 3, 250).is_err());
+        assert!(config.assert_mint_allowed(Class::Ruby, 3, false, 250).is_err());
+        // Unlimited classes open at 0 and never close.
+        assert!(config.assert_class_mint_allowed(Class::Sapphire, u32::MAX, 0).is_ok());
+    }
+
+    /// Serialized size of a transaction batch minting `gems` gems: a compute budget request, then mint_nfts with the
+    /// associated token and token metadata programs it invokes appended to its accounts.
+    fn batch_mint_transaction_size(gems: usize) -> usize {
+        use solana_program::{instruction::{AccountMeta, Instruction}, message::Message};
+        let payer = Pubkey::new_unique();
+        let mut accounts = vec![AccountMeta::new(payer, true)];
+        accounts.extend((0..13).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+        for _ in 0..gems {
+            accounts.push(AccountMeta::new(Pubkey::new_unique(), true));
+            accounts.extend((0..4).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+        }
+        let classes = vec![Class::Ruby; gems];
+        let mint_nfts = Instruction::new_with_borsh(id(), &crate::instruction::InstructionEnum::MintNFTs { classes }, accounts);
+        let compute_budget = Instruction::new_with_bytes(Pubkey::new_unique(), &[2, 0, 0, 0, 0], vec![]);
+        let message = Message::new(&[compute_budget, mint_nfts], Some(&payer));
+        1 + 64 * message.header.num_required_signatures as usize + message.serialize().len()
+    }
+
+    #[test]
+    fn batch_mints_fit_in_one_transaction() {
+        const PACKET_DATA_SIZE: usize = 1232;
+        assert!(batch_mint_transaction_size(MAX_BATCH_MINT) <= PACKET_DATA_SIZE);
+        assert!(batch_mint_transaction_size(MAX_BATCH_MINT + 1) > PACKET_DATA_SIZE);
+    }
+
+    #[test]
+    fn allowlist_allocations_run_out() {
+        let mut allocation = AllowlistAllocation { validation_phrase: ALLOWLIST_ALLOCATION_VAL_PHRASE, wallet: Pubkey::new_unique(), minted: 0 };
+        allocation.consume(2).unwrap();
+        allocation.consume(2).unwrap();
+        assert!(allocation.consume(2).is_err());
+        assert_eq!(allocation.minted, 2);
+        assert!(AllowlistAllocation { minted: 0, ..allocation }.consume(0).is_err());
+    }
+}
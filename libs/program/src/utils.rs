@@ -1,4 +1,6 @@
 use solana_program::{
+    bpf_loader_upgradeable,
+    hash::hashv,
     pubkey::Pubkey,
     program_error::ProgramError, account_info::AccountInfo, entrypoint::ProgramResult,
 };
@@ -38,4 +40,62 @@ pub fn assert_pda_input(seeds : &[&[u8]], account_info: &AccountInfo) -> (Pubkey
     assert_pubkeys_exactitude(&pda_key, account_info.key).unwrap();
     (pda_key, pda_bump)
 
-}
\ No newline at end of file
+}
+/// Asserts that `authority_info` signed and is the upgrade authority recorded in the program's ProgramData account.
+pub fn assert_config_authority(program_data_info: &AccountInfo, authority_info: &AccountInfo) -> ProgramResult {
+    assert_is_signer(authority_info)?;
+    let (program_data_key, _program_data_bump) = Pubkey::find_program_address(&[constants::id().as_ref()], &bpf_loader_upgradeable::id());
+    assert_pubkeys_exactitude(&program_data_key, program_data_info.key)?;
+
+    // Bincode layout of UpgradeableLoaderState::ProgramData: u32 variant (3), u64 slot, Option<Pubkey> authority.
+    let data = program_data_info.data.borrow();
+    if data.len() < 45 || data[0..4] != [3, 0, 0, 0] || data[12] != 1 {
+        Err(ProgramError::InvalidAccountData)?
+    }
+    assert_pubkeys_exactitude(&Pubkey::new(&data[13..45]), authority_info.key)
+}
+
+/// Leaf of a wallet's presale allocation in the allowlist tree.
+pub fn allowlist_leaf(wallet: &Pubkey, allocation: u32) -> [u8; 32] {
+    hashv(&[wallet.as_ref(), &allocation.to_le_bytes()]).to_bytes()
+}
+
+/// Checks a merkle proof built with sorted pair hashing, so proofs don't carry left/right flags.
+pub fn verify_merkle_proof(leaf: [u8; 32], proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    let mut computed = leaf;
+    for node in proof {
+        computed = if computed <= *node {
+            hashv(&[&computed, node]).to_bytes()
+        } else {
+            hashv(&[node, &computed]).to_bytes()
+        };
+    }
+    computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        if a <= b { hashv(&[a, b]).to_bytes() } else { hashv(&[b, a]).to_bytes() }
+    }
+
+    #[test]
+    fn verifies_allowlist_proofs() {
+        let wallets = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let leaves = [allowlist_leaf(&wallets[0], 2), allowlist_leaf(&wallets[1], 1), allowlist_leaf(&wallets[2], 5)];
+        // An odd leaf is paired with itself.
+        let (left, right) = (parent(&leaves[0], &leaves[1]), parent(&leaves[2], &leaves[2]));
+        let root = parent(&left, &right);
+
+        assert!(verify_merkle_proof(leaves[0], &[leaves[1], right], &root));
+        assert!(verify_merkle_proof(leaves[1], &[leaves[0], right], &root));
+        assert!(verify_merkle_proof(leaves[2], &[leaves[2], left], &root));
+        // A wallet can't claim a larger allocation, another wallet's proof, or a proof against another root.
+        assert!(!verify_merkle_proof(allowlist_leaf(&wallets[0], 3), &[leaves[1], right], &root));
+        assert!(!verify_merkle_proof(allowlist_leaf(&Pubkey::new_unique(), 2), &[leaves[1], right], &root));
+        assert!(!verify_merkle_proof(leaves[0], &[leaves[1], right], &left));
+        assert!(!verify_merkle_proof(leaves[0], &[], &root));
+    }
+}
@@ -0,0 +1,106 @@
+use ingl::{
+    rewards::{RewardShares, REWARD_SHARES},
+    state::{constants::*, Class},
+};
+use serde::Deserialize;
+
+/// Classes in Class::index order.
+pub const CLASSES: [Class; 6] = [Class::Ruby, Class::Diamond, Class::Sapphire, Class::Emerald, Class::Serendibite, Class::Benitoite];
+
+/// Parameters of a simulation run. Missing fields take the defaults below, whose protocol parameters are the program's current constants.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub epochs: u64,
+    pub epoch_seconds: u32,
+    pub holders: u32,
+    pub max_gems_per_holder: u32,
+    pub mint_probability: f64, // Chance per holder and epoch of minting a gem.
+    pub class_weights: [f64; 6], // Relative popularity of each class among mints, indexed by Class::index.
+    pub allocate_probability: f64, // Chance per epoch of an unallocated gem being allocated.
+    pub redeem_probability: f64, // Chance per epoch of an unallocated gem being redeemed.
+    pub claim_interval: u64, // Epochs between each holder's reward claims.
+    pub staking_yield: f64, // Lamports credited yearly to a vote account per lamport of its active stake.
+
+    pub validator_id_share: u64,
+    pub treasury_share: u64,
+    pub team_share: u64,
+    pub nfts_share: u64,
+    pub treasury_fee_multiplyer: u8,
+    pub maximum_delegatable_stake: u64,
+    pub rarity_odds: [[u64; 4]; 6], // Class::rarity_odds of each class, indexed by Class::index.
+    pub rarity_bonus_pool_bps: u16,
+    pub rarity_reward_bonus_bps: [u16; 5],
+    pub redeem_fee_schedule: Vec<(u32, u16)>, // (seconds since mint, fee in basis points) points.
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            seed: 0,
+            epochs: 365,
+            epoch_seconds: 172_800,
+            holders: 1000,
+            max_gems_per_holder: 5,
+            mint_probability: 0.02,
+            class_weights: [1.0, 2.0, 4.0, 8.0, 16.0, 32.0],
+            allocate_probability: 0.05,
+            redeem_probability: 0.002,
+            claim_interval: 15,
+            staking_yield: 0.07,
+
+            validator_id_share: REWARD_SHARES.validator_id,
+            treasury_share: REWARD_SHARES.treasury,
+            team_share: REWARD_SHARES.team,
+            nfts_share: REWARD_SHARES.nfts,
+            treasury_fee_multiplyer: TREASURY_FEE_MULTIPLYER,
+            maximum_delegatable_stake: MAXIMUM_DELEGATABLE_STAKE,
+            rarity_odds: CLASSES.map(Class::rarity_odds),
+            rarity_bonus_pool_bps: 0,
+            rarity_reward_bonus_bps: [0; 5],
+            redeem_fee_schedule: DEFAULT_REDEEM_FEE_SCHEDULE.to_vec(),
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn reward_shares(&self) -> RewardShares {
+        RewardShares {
+            validator_id: self.validator_id_share,
+            treasury: self.treasury_share,
+            team: self.team_share,
+            nfts: self.nfts_share,
+        }
+    }
+
+    /// Rejects parameters the program couldn't run with, such as shares paying out more than the rewards.
+    pub fn validate(&self) -> Result<(), String> {
+        let shares = self.validator_id_share + self.treasury_share + self.team_share + self.nfts_share;
+        if shares > 100 {
+            return Err(format!("Reward shares add up to {}%", shares));
+        }
+        if self.treasury_fee_multiplyer > 100 {
+            return Err(String::from("treasury_fee_multiplyer is above 100%"));
+        }
+        if self.redeem_fee_schedule.is_empty()
+            || self.redeem_fee_schedule.windows(2).any(|pair| pair[0].0 >= pair[1].0)
+            || self.redeem_fee_schedule.iter().any(|(_, fee_bps)| *fee_bps as u64 > BASIS_POINTS)
+        {
+            return Err(String::from("redeem_fee_schedule must be non-empty, ordered by time and charge at most 100%"));
+        }
+        if self.epoch_seconds == 0 {
+            return Err(String::from("epoch_seconds must be positive"));
+        }
+        for probability in [self.mint_probability, self.allocate_probability, self.redeem_probability] {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(format!("Probability {} is outside of [0, 1]", probability));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn epochs_per_year(&self) -> f64 {
+        365.0 * 86400.0 / self.epoch_seconds as f64
+    }
+}
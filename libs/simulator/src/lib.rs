@@ -0,0 +1,14 @@
+//! Economic simulator for the ingl protocol's parameters. Models holders minting, allocating, delegating, redeeming and claiming
+//! over simulated epochs, using the program's reward, fee and rarity functions, and reports yields per class along with
+//! treasury, team and validator income.
+pub mod config;
+pub mod report;
+pub mod rng;
+pub mod simulation;
+
+use crate::{config::SimulationConfig, report::Report, simulation::Simulation};
+
+pub fn simulate(config: SimulationConfig) -> Result<Report, String> {
+    config.validate()?;
+    Ok(Simulation::new(config).run())
+}
@@ -0,0 +1,22 @@
+use std::{env, fs, process};
+
+use ingl_simulator::{config::SimulationConfig, simulate};
+
+fn main() {
+    // Parameters are read from an optional JSON file, whose missing fields take their defaults.
+    let config: SimulationConfig = match env::args().nth(1) {
+        Some(path) => {
+            let contents = fs::read_to_string(&path).unwrap_or_else(|error| exit(&format!("Couldn't read {}: {}", path, error)));
+            serde_json::from_str(&contents).unwrap_or_else(|error| exit(&format!("Invalid config {}: {}", path, error)))
+        }
+        None => SimulationConfig::default(),
+    };
+
+    let report = simulate(config).unwrap_or_else(|error| exit(&error));
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -0,0 +1,81 @@
+use ingl::state::Class;
+use serde::Serialize;
+
+use crate::simulation::annualize;
+
+/// Outcome of a simulation run. Amounts are in lamports and yields are yearly fractions, e.g. 0.05 for 5%.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub epochs: u64,
+    pub years: f64,
+    pub gems_minted: u64,
+    pub total_raised: u64,
+    pub redeemed_to_holders: u64, // Class lamports refunded to redeeming holders, net of fees.
+    pub pd_pool_total: u64, // Allocated lamports left undelegated at the end.
+    pub validator_count: u64,
+    pub validator_income: u64,
+    pub treasury: IncomeReport,
+    pub team: IncomeReport,
+    pub nfts_rewards: u64, // NFTs share of the rewards, claimed or not.
+    pub rarity_bonus_pools: u64,
+    pub unassigned_rewards: u64, // Rewards no share pays out, including rounding, which stay with the authorized withdrawer.
+    pub classes: Vec<ClassReport>,
+    pub validators: Vec<ValidatorReport>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct IncomeReport {
+    pub rewards: u64,
+    pub redeem_fees: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClassReport {
+    pub class: String,
+    pub minted: u64,
+    pub redeemed: u64,
+    pub delegated: u64,
+    pub undelegated_allocated: u64, // Allocated gems still waiting in the PD pool at the end.
+    pub rarities: [u64; 5], // Gems minted per rarity, indexed by Rarity::index.
+    pub rewards_claimed: u64,
+    pub rewards_pending: u64,
+    pub holder_apy: f64, // Rewards earned over the class lamports held from mint to redemption or the end of the run.
+    pub delegated_apy: f64, // Rewards earned over the class lamports of delegated gems while their stake was active.
+    #[serde(skip)]
+    pub held_lamport_epochs: u128,
+    #[serde(skip)]
+    pub delegated_lamport_epochs: u128,
+}
+
+impl ClassReport {
+    pub fn new(class: Class) -> Self {
+        ClassReport {
+            class: format!("{:?}", class),
+            minted: 0,
+            redeemed: 0,
+            delegated: 0,
+            undelegated_allocated: 0,
+            rarities: [0; 5],
+            rewards_claimed: 0,
+            rewards_pending: 0,
+            holder_apy: 0.0,
+            delegated_apy: 0.0,
+            held_lamport_epochs: 0,
+            delegated_lamport_epochs: 0,
+        }
+    }
+
+    pub fn finish(&mut self, epochs_per_year: f64) {
+        let earned = self.rewards_claimed as u128 + self.rewards_pending as u128;
+        self.holder_apy = annualize(earned, self.held_lamport_epochs, epochs_per_year);
+        self.delegated_apy = annualize(earned, self.delegated_lamport_epochs, epochs_per_year);
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ValidatorReport {
+    pub total_delegated: u64,
+    pub total_rewards: u64, // Everything the vote account earned, before the split.
+    pub income: u64, // The validator id's share.
+    pub income_apy: f64, // Income over the validator's active stake.
+}
@@ -0,0 +1,356 @@
+use ingl::{
+    nfts::rarity_from_odds,
+    randomness::rarity_roll,
+    rewards::{claimable_rarity_bonus_pool, gem_pending_rarity_bonus, RewardShares},
+    state::{
+        constants::*, split_redeem_fee, Class, ClassLimit, ProtocolConfig, RandomnessSource, Rarity, RarityBonus, RedeemFeePoint,
+        VoteRewards,
+    },
+};
+use solana_program::{
+    hash::hashv,
+    pubkey::Pubkey,
+    stake_history::{StakeHistory, StakeHistoryEntry},
+};
+
+use crate::{
+    config::{SimulationConfig, CLASSES},
+    report::{ClassReport, IncomeReport, Report, ValidatorReport},
+    rng::Rng,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Location {
+    MintingPool,
+    PDPool,
+    Validator(usize),
+    Redeemed,
+}
+
+struct SimGem {
+    holder: u32,
+    class: Class,
+    rarity: Rarity,
+    minted_epoch: u64,
+    closed_epoch: Option<u64>, // Epoch the gem was redeemed in.
+    location: Location,
+    delegation_epoch: u64,
+    last_claim_epoch: u64,
+    claimed: u64,
+}
+
+#[derive(Default)]
+struct SimValidator {
+    total_delegated: u64,
+    active_stake: u64,
+    activating: u64, // Delegated during the current epoch, active from the next one.
+    vote_rewards: Vec<VoteRewards>,
+    rarity_bonuses: Vec<RarityBonus>,
+    active_lamport_epochs: u128,
+    total_rewards: u64,
+    income: u64,
+}
+
+/// Simulates holders minting, allocating, redeeming and claiming over `config.epochs` epochs, pooling allocated gems into validators
+/// as the program's proposals would, and paying rewards, fees and rarities with the program's own functions.
+///
+/// Each epoch, in order: holders mint, unallocated gems are redeemed or allocated, allocated gems are delegated first fit,
+/// vote accounts earn `staking_yield` on their active stake and have it processed, and holders claim every `claim_interval` epochs.
+/// A new validator is onboarded whenever the PD pool holds MAXIMUM_DELEGATABLE_STAKE, the amount finalize_proposal requires.
+/// Stake warms up in a single epoch, and delegated gems are never undelegated.
+pub struct Simulation {
+    config: SimulationConfig,
+    shares: RewardShares,
+    protocol_config: ProtocolConfig,
+    rng: Rng,
+    gems: Vec<SimGem>,
+    holder_gems: Vec<u32>,
+    validators: Vec<SimValidator>,
+    stake_history: StakeHistory,
+    pd_pool_total: u64,
+    total_raised: u64,
+    redeemed_to_holders: u64,
+    treasury: IncomeReport,
+    team: IncomeReport,
+    nfts_rewards: u64,
+    rarity_bonus_pools: u64,
+    unassigned_rewards: u64,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Self {
+        let protocol_config = ProtocolConfig {
+            validation_phrase: PROTOCOL_CONFIG_VAL_PHRASE,
+            marketplace_fee_bps: 0,
+            presale_merkle_root: [0; 32],
+            presale_start: 0,
+            public_mint_start: 0,
+            class_limits: [ClassLimit::default(); 6],
+            randomness_source: RandomnessSource::PriceFeeds,
+            vrf_account: Pubkey::default(),
+            reroll_fee: 0,
+            reroll_treasury_bps: 0,
+            rarity_bonus_pool_bps: config.rarity_bonus_pool_bps,
+            rarity_reward_bonus_bps: config.rarity_reward_bonus_bps,
+            rarity_vote_bonus_bps: [0; 5],
+            redeem_fee_schedule: config
+                .redeem_fee_schedule
+                .iter()
+                .map(|(elapsed, fee_bps)| RedeemFeePoint { elapsed: *elapsed, fee_bps: *fee_bps })
+                .collect(),
+            reroll_rewards_vote_account: Pubkey::default(),
+        };
+        Simulation {
+            shares: config.reward_shares(),
+            protocol_config,
+            rng: Rng::new(config.seed),
+            gems: Vec::new(),
+            holder_gems: vec![0; config.holders as usize],
+            validators: Vec::new(),
+            stake_history: StakeHistory::default(),
+            pd_pool_total: 0,
+            total_raised: 0,
+            redeemed_to_holders: 0,
+            treasury: IncomeReport::default(),
+            team: IncomeReport::default(),
+            nfts_rewards: 0,
+            rarity_bonus_pools: 0,
+            unassigned_rewards: 0,
+            config,
+        }
+    }
+
+    pub fn run(mut self) -> Report {
+        for epoch in 0..self.config.epochs {
+            self.step(epoch);
+        }
+        self.report()
+    }
+
+    fn step(&mut self, epoch: u64) {
+        let active_stake = self.validators.iter().map(|validator| validator.active_stake).sum();
+        self.stake_history.add(epoch, StakeHistoryEntry { effective: active_stake, activating: 0, deactivating: 0 });
+
+        self.mint(epoch);
+        self.redeem_and_allocate(epoch);
+        self.delegate(epoch);
+        self.process_rewards(epoch);
+        if (epoch + 1).is_multiple_of(self.config.claim_interval.max(1)) {
+            for index in 0..self.gems.len() {
+                self.claim(index, epoch + 1);
+            }
+        }
+    }
+
+    fn mint(&mut self, epoch: u64) {
+        for holder in 0..self.config.holders {
+            if self.holder_gems[holder as usize] >= self.config.max_gems_per_holder || !self.rng.chance(self.config.mint_probability) {
+                continue;
+            }
+            let class = match self.rng.weighted(&self.config.class_weights) {
+                Some(index) => CLASSES[index],
+                None => return,
+            };
+            // Rarities are imprinted right away, rolled from a random seed as imprint_rarity rolls the revealed one.
+            let seed = hashv(&[&self.rng.next_u64().to_le_bytes()]).to_bytes();
+            let rarity = rarity_from_odds(rarity_roll(seed), &self.config.rarity_odds[class.index()]);
+
+            self.total_raised += class.get_class_lamports();
+            self.holder_gems[holder as usize] += 1;
+            self.gems.push(SimGem {
+                holder,
+                class,
+                rarity,
+                minted_epoch: epoch,
+                closed_epoch: None,
+                location: Location::MintingPool,
+                delegation_epoch: 0,
+                last_claim_epoch: 0,
+                claimed: 0,
+            });
+        }
+    }
+
+    fn redeem_and_allocate(&mut self, epoch: u64) {
+        for gem in self.gems.iter_mut().filter(|gem| gem.location == Location::MintingPool) {
+            let class_lamports = gem.class.get_class_lamports();
+            if self.rng.chance(self.config.redeem_probability) {
+                let elapsed = ((epoch - gem.minted_epoch) * self.config.epoch_seconds as u64).min(u32::MAX as u64) as u32;
+                let redeem_fees = self.protocol_config.redeem_fee(class_lamports, elapsed);
+                let (treasury_funds, team_funds) = split_redeem_fee(redeem_fees, self.config.treasury_fee_multiplyer);
+                self.treasury.redeem_fees += treasury_funds;
+                self.team.redeem_fees += team_funds;
+                self.redeemed_to_holders += class_lamports - redeem_fees;
+                self.holder_gems[gem.holder as usize] -= 1;
+                gem.location = Location::Redeemed;
+                gem.closed_epoch = Some(epoch);
+            } else if self.rng.chance(self.config.allocate_probability) {
+                self.pd_pool_total += class_lamports;
+                gem.location = Location::PDPool;
+            }
+        }
+    }
+
+    /// Fills validators with room first, then onboards new ones while the PD pool can fill them.
+    /// Gems larger than the maximum delegatable stake can never be delegated and stay in the PD pool.
+    fn delegate(&mut self, epoch: u64) {
+        let maximum = self.config.maximum_delegatable_stake;
+        loop {
+            for gem in self.gems.iter_mut().filter(|gem| gem.location == Location::PDPool) {
+                let class_lamports = gem.class.get_class_lamports();
+                if let Some(index) = self.validators.iter().position(|validator| validator.total_delegated + class_lamports <= maximum) {
+                    let validator = &mut self.validators[index];
+                    validator.total_delegated += class_lamports;
+                    validator.activating += class_lamports;
+                    self.pd_pool_total -= class_lamports;
+                    gem.location = Location::Validator(index);
+                    gem.delegation_epoch = epoch;
+                    gem.last_claim_epoch = epoch;
+                }
+            }
+            let delegatable: u64 = self
+                .gems
+                .iter()
+                .filter(|gem| gem.location == Location::PDPool && gem.class.get_class_lamports() <= maximum)
+                .map(|gem| gem.class.get_class_lamports())
+                .sum();
+            if maximum == 0 || delegatable < maximum {
+                break;
+            }
+            self.validators.push(SimValidator::default());
+        }
+    }
+
+    /// Mirrors process_rewards: the vote account's earnings are split with the program's shares and snapshotted against its active stake.
+    fn process_rewards(&mut self, epoch: u64) {
+        let epochs_per_year = self.config.epochs_per_year();
+        for validator in self.validators.iter_mut() {
+            let lamports = (validator.active_stake as f64 * self.config.staking_yield / epochs_per_year) as u64;
+            let split = self.shares.split(lamports, self.protocol_config.rarity_bonus_pool_bps);
+            // The simulation has no liquid gems, so the whole pool is claimable whenever a bonus is in force.
+            let rarity_bonus_pool = claimable_rarity_bonus_pool(split.rarity_bonus_pool, validator.active_stake, 0, &self.protocol_config.rarity_reward_bonus_bps);
+            validator.active_lamport_epochs += validator.active_stake as u128;
+            validator.total_rewards += lamports;
+            validator.income += split.validator_id;
+            self.team.rewards += split.team;
+            self.treasury.rewards += split.treasury + split.rarity_bonus_pool - rarity_bonus_pool;
+            self.nfts_rewards += split.nfts;
+            self.rarity_bonus_pools += rarity_bonus_pool;
+            self.unassigned_rewards += lamports - split.validator_id - split.team - split.treasury - split.rarity_bonus_pool - split.nfts;
+
+            validator.vote_rewards.push(VoteRewards {
+                validation_phrase: VOTE_REWARDS_VAL_PHRASE,
+                epoch_number: epoch + 1,
+                total_stake: validator.active_stake,
+                total_reward: lamports,
+            });
+            if rarity_bonus_pool > 0 {
+                validator.rarity_bonuses.push(RarityBonus {
+                    epoch_number: epoch + 1,
+                    pool: rarity_bonus_pool,
+                    total_stake: validator.active_stake,
+                    bonus_bps: self.protocol_config.rarity_reward_bonus_bps,
+                    claimed: 0,
+                });
+            }
+            validator.active_stake += validator.activating;
+            validator.activating = 0;
+        }
+    }
+
+    /// Rewards and rarity bonuses the gem could claim from the snapshots taken up to now.
+    fn pending(&self, index: usize) -> u64 {
+        let gem = &self.gems[index];
+        let validator = match gem.location {
+            Location::Validator(validator) => &self.validators[validator],
+            _ => return 0,
+        };
+        let class_lamports = gem.class.get_class_lamports();
+        self.shares.gem_pending_rewards(class_lamports, gem.delegation_epoch, gem.last_claim_epoch, &validator.vote_rewards, &self.stake_history)
+            + gem_pending_rarity_bonus(
+                class_lamports,
+                &Some(gem.rarity.clone()),
+                gem.delegation_epoch,
+                gem.last_claim_epoch,
+                &validator.rarity_bonuses,
+                &self.stake_history,
+            )
+    }
+
+    fn claim(&mut self, index: usize, snapshot_epoch: u64) {
+        let pending = self.pending(index);
+        let gem = &mut self.gems[index];
+        if let Location::Validator(_) = gem.location {
+            gem.claimed += pending;
+            gem.last_claim_epoch = snapshot_epoch;
+        }
+    }
+
+    fn report(&self) -> Report {
+        let epochs_per_year = self.config.epochs_per_year();
+        let mut classes: Vec<ClassReport> = CLASSES.iter().map(|class| ClassReport::new(*class)).collect();
+        for (index, gem) in self.gems.iter().enumerate() {
+            let class_report = &mut classes[gem.class.index()];
+            let class_lamports = gem.class.get_class_lamports();
+            let earned = gem.claimed + self.pending(index);
+            let held_epochs = gem.closed_epoch.unwrap_or(self.config.epochs) - gem.minted_epoch;
+
+            class_report.minted += 1;
+            class_report.rarities[gem.rarity.index()] += 1;
+            class_report.rewards_claimed += gem.claimed;
+            class_report.rewards_pending += earned - gem.claimed;
+            class_report.held_lamport_epochs += class_lamports as u128 * held_epochs as u128;
+            match gem.location {
+                Location::Redeemed => class_report.redeemed += 1,
+                Location::PDPool => class_report.undelegated_allocated += 1,
+                Location::Validator(_) => {
+                    class_report.delegated += 1;
+                    // Delegated gems earn from the epoch after their delegation.
+                    let earning_epochs = self.config.epochs.saturating_sub(gem.delegation_epoch + 1);
+                    class_report.delegated_lamport_epochs += class_lamports as u128 * earning_epochs as u128;
+                }
+                Location::MintingPool => {}
+            }
+        }
+        for class_report in classes.iter_mut() {
+            class_report.finish(epochs_per_year);
+        }
+
+        let validators: Vec<ValidatorReport> = self
+            .validators
+            .iter()
+            .map(|validator| ValidatorReport {
+                total_delegated: validator.total_delegated,
+                total_rewards: validator.total_rewards,
+                income: validator.income,
+                income_apy: annualize(validator.income as u128, validator.active_lamport_epochs, epochs_per_year),
+            })
+            .collect();
+
+        Report {
+            epochs: self.config.epochs,
+            years: self.config.epochs as f64 / epochs_per_year,
+            gems_minted: self.gems.len() as u64,
+            total_raised: self.total_raised,
+            redeemed_to_holders: self.redeemed_to_holders,
+            pd_pool_total: self.pd_pool_total,
+            validator_count: self.validators.len() as u64,
+            validator_income: validators.iter().map(|validator| validator.income).sum(),
+            treasury: self.treasury.clone(),
+            team: self.team.clone(),
+            nfts_rewards: self.nfts_rewards,
+            rarity_bonus_pools: self.rarity_bonus_pools,
+            unassigned_rewards: self.unassigned_rewards,
+            classes,
+            validators,
+        }
+    }
+}
+
+/// Yearly yield of `earned` lamports over `lamport_epochs` of capital.
+pub fn annualize(earned: u128, lamport_epochs: u128, epochs_per_year: f64) -> f64 {
+    if lamport_epochs == 0 {
+        return 0.0;
+    }
+    earned as f64 / lamport_epochs as f64 * epochs_per_year
+}
@@ -0,0 +1,61 @@
+use ingl_simulator::{config::SimulationConfig, simulate};
+
+/// One holder minting a single Emerald in the first epoch and allocating it straight away. The stake fills the one validator,
+/// which delegates it in epoch 0, so it earns from epoch 1 on. With 128 epochs a year and a 50% yield, each active epoch
+/// credits 10 SOL * 0.5 / 128 = 39_062_500 lamports.
+fn single_emerald() -> SimulationConfig {
+    SimulationConfig {
+        seed: 7,
+        epochs: 10,
+        epoch_seconds: 246_375,
+        holders: 1,
+        max_gems_per_holder: 1,
+        mint_probability: 1.0,
+        class_weights: [0.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        allocate_probability: 1.0,
+        redeem_probability: 0.0,
+        claim_interval: 1,
+        staking_yield: 0.5,
+        validator_id_share: 15,
+        treasury_share: 13,
+        team_share: 12,
+        nfts_share: 60,
+        maximum_delegatable_stake: 10_000_000_000,
+        ..SimulationConfig::default()
+    }
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+#[test]
+fn pays_each_share_of_the_active_epochs() {
+    let report = simulate(single_emerald()).unwrap();
+    assert_eq!(report.validator_count, 1);
+    assert_eq!(report.treasury.rewards, 9 * 5_078_125);
+    assert_eq!(report.treasury.redeem_fees, 0);
+    assert_eq!(report.team.rewards, 9 * 4_687_500);
+    assert_eq!(report.validator_income, 9 * 5_859_375);
+    assert_eq!(report.nfts_rewards, 9 * 23_437_500);
+    assert_eq!(report.unassigned_rewards, 0);
+
+    let emerald = &report.classes[3];
+    assert_eq!(emerald.class, "Emerald");
+    assert_eq!(emerald.rewards_claimed + emerald.rewards_pending, report.nfts_rewards);
+    // The NFTs' 60% of a 50% yield while delegated, diluted by the epoch spent activating over the whole holding.
+    assert_close(emerald.delegated_apy, 0.3);
+    assert_close(emerald.holder_apy, 0.27);
+    for (index, class) in report.classes.iter().enumerate().filter(|(index, _)| *index != 3) {
+        assert_eq!(class.minted, 0, "class {}", index);
+        assert_close(class.holder_apy, 0.0);
+    }
+}
+
+#[test]
+fn runs_are_reproducible_from_their_seed() {
+    let config = SimulationConfig { epochs: 60, holders: 50, maximum_delegatable_stake: 50_000_000_000, ..SimulationConfig::default() };
+    let run = |seed| serde_json::to_string(&simulate(SimulationConfig { seed, ..config.clone() }).unwrap()).unwrap();
+    assert_eq!(run(3), run(3));
+    assert_ne!(run(3), run(4));
+}